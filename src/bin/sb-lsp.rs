@@ -0,0 +1,12 @@
+//! Language server for SB scripts, speaks LSP over stdin/stdout.
+//!
+//! Build it without the file logger of the library:
+//! `cargo build --bin sb-lsp --no-default-features`
+
+use core::language_service::lsp::LspServer;
+
+fn main() -> std::io::Result<()> {
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    LspServer::new().run(&mut stdin.lock(), &mut stdout.lock())
+}
@@ -0,0 +1,296 @@
+//! Headless front-end of the compiler: checks SB4 scripts without the IDE.
+//!
+//! Build it without the file logger of the library:
+//! `cargo build --bin sbc --no-default-features`
+
+use std::ffi::CString;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use core::dictionary::dictionary_str_by_str::DictStrByStr;
+use core::language_service::scan_file;
+use core::language_service::symbol_table::{SymbolTable, SymbolType};
+use core::legacy_ini::{Game, OpcodeTable};
+use core::namespaces::namespaces::Namespaces;
+use core::preprocessor::conditions::Conditions;
+use core::preprocessor::diagnostics::Severity;
+use core::preprocessor::PreProcessorBuilder;
+use core::utils::path::normalize_file_name;
+use core::v4::helpers::token_column;
+use core::v4::transform_checked;
+use core::v4::typecheck::TypeEnv;
+
+const USAGE: &str = "usage: sbc [options] <file>
+
+options:
+  --library <file>       commands library (default: sa.json)
+  --ini <file>           legacy opcode definitions (default: taken from the library)
+  --compiler-ini <file>  reserved words of the compiler
+  --include <file>       implicit include, e.g. constants.txt (can be repeated)
+  --define <name>        symbol for {$IFDEF} (can be repeated)
+  --include-dir <dir>    directory to look up relative includes in (can be repeated)
+  --game <name>          gta3, vc, sa, lcs, vcs, sa_mobile (default: sa)
+  --emit                 print the source with v4 expressions lowered to opcodes
+  --deps <format>        print the include graph as json or dot";
+
+struct Options {
+    file: String,
+    library: String,
+    ini: Option<String>,
+    compiler_ini: Option<String>,
+    includes: Vec<String>,
+    defines: Vec<String>,
+    include_dirs: Vec<PathBuf>,
+    game: Game,
+    emit: bool,
+    deps: Option<String>,
+}
+
+fn parse_game(name: &str) -> Option<Game> {
+    match name.to_ascii_lowercase().as_str() {
+        "gta3" => Some(Game::GTA3),
+        "vc" => Some(Game::VC),
+        "sa" => Some(Game::SA),
+        "lcs" => Some(Game::LCS),
+        "vcs" => Some(Game::VCS),
+        "sa_mobile" => Some(Game::SAMOBILE),
+        _ => None,
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut file = None;
+    let mut options = Options {
+        file: String::new(),
+        library: String::from("sa.json"),
+        ini: None,
+        compiler_ini: None,
+        includes: vec![],
+        defines: vec![],
+        include_dirs: vec![],
+        game: Game::SA,
+        emit: false,
+        deps: None,
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {arg}"));
+        match arg.as_str() {
+            "--library" => options.library = value()?,
+            "--ini" => options.ini = Some(value()?),
+            "--compiler-ini" => options.compiler_ini = Some(value()?),
+            "--include" => options.includes.push(value()?),
+            "--define" => options.defines.push(value()?),
+            "--include-dir" => options.include_dirs.push(value()?.into()),
+            "--game" => {
+                let name = value()?;
+                options.game = parse_game(&name).ok_or(format!("unknown game {name}"))?;
+            }
+            "--emit" => options.emit = true,
+            "--deps" => {
+                let format = value()?;
+                if format != "json" && format != "dot" {
+                    return Err(format!("unknown graph format {format}"));
+                }
+                options.deps = Some(format);
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option {arg}")),
+            _ if file.is_none() => file = Some(arg),
+            _ => return Err(format!("unexpected argument {arg}")),
+        }
+    }
+
+    options.file = file.ok_or("no input file")?;
+    Ok(options)
+}
+
+static ERRORS: AtomicUsize = AtomicUsize::new(0);
+
+/// prints warnings and errors reported by the library to stderr
+struct Reporter;
+
+impl log::Log for Reporter {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Warn
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if record.level() == log::Level::Error {
+            ERRORS.fetch_add(1, Ordering::Relaxed);
+        }
+        eprintln!(
+            "{}: {}",
+            record.level().as_str().to_ascii_lowercase(),
+            record.args()
+        );
+    }
+
+    fn flush(&self) {}
+}
+
+/// numeric constants for the v4 transform
+fn const_lookup(symbols: &SymbolTable) -> DictStrByStr {
+    let mut dict = DictStrByStr::default();
+    for (name, symbols) in &symbols.symbols {
+        let value = symbols.iter().find_map(|symbol| match symbol._type {
+            SymbolType::Number => symbol.value.as_ref(),
+            _ => None,
+        });
+        let (Ok(name), Some(Ok(value))) = (
+            CString::new(name.as_str()),
+            value.map(|v| CString::new(v.as_str())),
+        ) else {
+            continue;
+        };
+        dict.add(name, value);
+    }
+    dict
+}
+
+/// type checks each line of the file, returns the source with lowered v4 expressions
+///
+/// lines disabled by {$IFDEF} are copied as is
+fn check_file(
+    file_name: &str,
+    ns: &Namespaces,
+    table: &OpcodeTable,
+    symbols: &SymbolTable,
+    defines: &Vec<String>,
+) -> Option<Vec<String>> {
+    let text = String::from_utf8_lossy(&std::fs::read(file_name).ok()?).to_string();
+    let const_lookup = const_lookup(symbols);
+    let mut conditions = Conditions::new(defines);
+
+    let mut output = vec![];
+    for (index, line) in text.lines().enumerate() {
+        let expr = line.split("//").next().unwrap_or_default().trim();
+        let is_directive = conditions.directive_line(expr).unwrap_or(true);
+        if is_directive || !conditions.is_active() {
+            output.push(String::from(line));
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+        let env = TypeEnv {
+            ns,
+            symbols,
+            line_number: index,
+        };
+        match transform_checked(expr, &env, table, &const_lookup) {
+            Ok(Some(instruction)) => {
+                output.push(format!("{}{instruction}", &line[..indent]));
+            }
+            Ok(None) => output.push(String::from(line)),
+            Err(errors) => {
+                for e in errors {
+                    log::error!(
+                        "{file_name}:{}:{}: {}",
+                        index + 1,
+                        indent + token_column(expr, &e.token),
+                        e.message
+                    );
+                }
+                output.push(String::from(line));
+            }
+        }
+    }
+    Some(output)
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if log::set_boxed_logger(Box::new(Reporter)).is_ok() {
+        log::set_max_level(log::LevelFilter::Warn);
+    } else {
+        eprintln!("warning: the library logger is active, errors are written to core.log");
+    }
+
+    let mut ns = Namespaces::new();
+    if ns.load_library(&options.library).is_none() {
+        eprintln!("error: can't load library {}", options.library);
+        return ExitCode::FAILURE;
+    }
+
+    let mut table = OpcodeTable::new(options.game);
+    let loaded = match &options.ini {
+        Some(ini) => table.load_from_file(ini),
+        None => table.load_from_json(&ns.commands),
+    };
+    if !loaded {
+        eprintln!("error: can't load opcode definitions");
+        return ExitCode::FAILURE;
+    }
+
+    let mut builder = PreProcessorBuilder::new();
+    builder.implicit_includes(options.includes.iter().map(Into::into).collect());
+    builder.defines(options.defines.clone());
+    builder.search_roots(options.include_dirs.clone());
+    if let Some(compiler_ini) = &options.compiler_ini {
+        builder.reserved_words(compiler_ini.into());
+    }
+    let mut preprocessor = builder.build();
+    if let Err(e) = preprocessor.parse_file(options.file.as_str().into()) {
+        log::error!("{e}");
+    }
+    for d in preprocessor.diagnostics.iter() {
+        let location = format!("{}:{}:{}", d.file_name.display(), d.line + 1, d.column + 1);
+        match d.severity {
+            Severity::Error => log::error!("{location}: {}", d.message),
+            Severity::Warning => log::warn!("{location}: {}", d.message),
+        }
+    }
+
+    match options.deps.as_deref() {
+        Some("json") => println!("{}", preprocessor.dependencies.to_json()),
+        Some("dot") => print!("{}", preprocessor.dependencies.to_dot()),
+        _ => {}
+    }
+
+    let class_names = ns
+        .commands
+        .values()
+        .filter_map(|command| command.class.as_ref())
+        .map(|class| class.to_ascii_lowercase())
+        .collect::<Vec<_>>();
+
+    let main_file = normalize_file_name(Path::new(&options.file));
+    for file in &preprocessor.files {
+        let Some(file_name) = file.to_str() else {
+            continue;
+        };
+        let symbols = scan_file(
+            file_name,
+            &preprocessor.reserved_words,
+            &options.includes,
+            &class_names,
+            &options.defines,
+            &options.include_dirs,
+        )
+        .unwrap_or_else(SymbolTable::new);
+        let Some(output) = check_file(file_name, &ns, &table, &symbols, &options.defines) else {
+            log::error!("can't read file {file_name}");
+            continue;
+        };
+        if options.emit && main_file.as_ref() == Some(file) {
+            println!("{}", output.join("\n"));
+        }
+    }
+
+    match ERRORS.load(Ordering::Relaxed) {
+        0 => ExitCode::SUCCESS,
+        n => {
+            eprintln!("{n} error(s)");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use std::collections::BTreeSet;
+
+use crate::legacy_ini::Game;
+use crate::namespaces::{namespaces::Namespaces, Command, Operator};
+use crate::parser::{escape, interface::VariableType};
+use crate::scm::decoder::{decode, DecodeError, DecodedInstruction};
+use crate::scm::{ScmVariable, Value};
+use crate::v4::eval::format_float;
+
+pub struct Decompiler<'a> {
+    ns: &'a Namespaces,
+    game: Game,
+}
+
+impl<'a> Decompiler<'a> {
+    pub fn new(ns: &'a Namespaces, game: Game) -> Self {
+        Self { ns, game }
+    }
+
+    /// converts a compiled script (main.scm section or a CLEO script) into SB4 source code
+    pub fn decompile(&self, bytes: &[u8]) -> Result<String, DecodeError> {
+        let instructions = decode(bytes, self.ns, self.game)?;
+        let labels = self.collect_labels(&instructions);
+
+        let mut lines = vec![];
+        for instruction in &instructions {
+            if labels.contains(&instruction.offset) {
+                lines.push(format!(":{}", label_name(instruction.offset)));
+            }
+            // decode() only returns known commands
+            let command = self.ns.get_command(instruction.id).unwrap();
+            lines.push(format_instruction(instruction, command));
+        }
+        lines.push(String::new());
+        Ok(lines.join("\n"))
+    }
+
+    fn collect_labels(&self, instructions: &[DecodedInstruction]) -> BTreeSet<usize> {
+        let mut labels = BTreeSet::new();
+        for instruction in instructions {
+            let Some(command) = self.ns.get_command(instruction.id) else {
+                continue;
+            };
+            for (param, arg) in command.input.iter().zip(&instruction.args) {
+                if let (true, Value::Int(offset)) = (is_label(&param.r#type), arg) {
+                    labels.insert(offset.unsigned_abs() as usize);
+                }
+            }
+        }
+        labels
+    }
+}
+
+fn is_label(_type: &str) -> bool {
+    _type.eq_ignore_ascii_case("label")
+}
+
+/// positive offsets are relative to main.scm, negative ones to the start of the mission or CLEO script
+fn label_name(offset: usize) -> String {
+    format!("label{offset}")
+}
+
+fn format_instruction(instruction: &DecodedInstruction, command: &Command) -> String {
+    let args = instruction
+        .args
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| match (command.input.get(i), arg) {
+            (Some(param), Value::Int(offset)) if is_label(&param.r#type) => {
+                format!("@{}", label_name(offset.unsigned_abs() as usize))
+            }
+            _ => format_value(arg),
+        })
+        .collect::<Vec<_>>();
+
+    // variadic arguments belong to the inputs
+    let (inputs, outputs) = args.split_at(args.len() - command.output.len());
+    let not = if instruction.not { "not " } else { "" };
+
+    if let Some(operator) = command.operator {
+        if let Some(s) = format_operator(operator, inputs, outputs) {
+            return format!("{not}{s}");
+        }
+    }
+
+    if let (Some(class), Some(member)) = (&command.class, &command.member) {
+        let call = format!("{class}.{member}({})", inputs.join(", "));
+        return if outputs.is_empty() {
+            format!("{not}{call}")
+        } else {
+            format!("{not}{} = {call}", outputs.join(", "))
+        };
+    }
+
+    let mut line = format!("{not}{}", command.name.to_ascii_lowercase());
+    for arg in args.iter() {
+        line.push(' ');
+        line.push_str(arg);
+    }
+    line
+}
+
+fn format_operator(operator: Operator, inputs: &[String], outputs: &[String]) -> Option<String> {
+    let op: &str = operator.into();
+    let (dest, operands) = match (outputs, inputs) {
+        ([dest], operands) => (dest, operands),
+        ([], [dest, operands @ ..]) => (dest, operands),
+        _ => return None,
+    };
+    match (operator, operands) {
+        (Operator::IsEqualTo | Operator::IsGreaterThan | Operator::IsGreaterOrEqualTo, [right])
+            if outputs.is_empty() =>
+        {
+            Some(format!("{dest} {op} {right}"))
+        }
+        (Operator::Not, []) => Some(format!("~{dest}")),
+        (Operator::Not, [operand]) => Some(format!("{dest} = ~{operand}")),
+        (
+            Operator::Assignment
+            | Operator::CastAssignment
+            | Operator::TimedAddition
+            | Operator::TimedSubtraction,
+            [operand],
+        ) => Some(format!("{dest} {op} {operand}")),
+        (
+            Operator::IsEqualTo
+            | Operator::IsGreaterThan
+            | Operator::IsGreaterOrEqualTo
+            | Operator::Assignment
+            | Operator::CastAssignment
+            | Operator::TimedAddition
+            | Operator::TimedSubtraction,
+            _,
+        ) => None,
+        (_, [operand]) => Some(format!("{dest} {op}= {operand}")),
+        (_, [left, right]) => Some(format!("{dest} = {left} {op} {right}")),
+        _ => None,
+    }
+}
+
+pub fn format_value(value: &Value) -> String {
+    match value {
+        Value::Int(v) => v.to_string(),
+        Value::Float(v) => format_float(*v),
+        Value::Var(var) => format_var(var),
+        Value::Array { var, index, size } => {
+            let suffix = match var._type {
+                VariableType::Float => 'f',
+                VariableType::ShortString => 's',
+                VariableType::LongString => 'v',
+                _ => 'i',
+            };
+            let base = format_var(&ScmVariable {
+                _type: VariableType::Unknown,
+                ..var.clone()
+            });
+            let base = match var._type {
+                VariableType::ShortString | VariableType::LongString if var.global => {
+                    format!("{suffix}{base}")
+                }
+                _ => base,
+            };
+            format!("{base}({},{size}{suffix})", format_var(index))
+        }
+        Value::String8(s) | Value::String16(s) => format!("'{}'", escape(s, '\'')),
+        Value::VarString(s) | Value::Byte128(s) => format!("\"{}\"", escape(s, '"')),
+    }
+}
+
+fn format_var(var: &ScmVariable) -> String {
+    let prefix = match var._type {
+        VariableType::ShortString => "s",
+        VariableType::LongString => "v",
+        _ => "",
+    };
+    if var.global {
+        // global variables are 4 bytes each
+        format!("{prefix}${}", var.offset / 4)
+    } else {
+        format!("{}@{prefix}", var.offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scm::decoder::Decoder;
+
+    fn load_ns() -> Namespaces {
+        let mut ns = Namespaces::new();
+        ns.load_library("sa.json");
+        // sa.json has no operators
+        ns.load_library("src/decompiler/test/operators.json");
+        ns
+    }
+
+    #[test]
+    fn test_fixture() {
+        let ns = load_ns();
+        let bytes = std::fs::read("src/decompiler/test/sample.cs").unwrap();
+        let expected = std::fs::read_to_string("src/decompiler/test/sample.txt").unwrap();
+        let decompiler = Decompiler::new(&ns, Game::SA);
+        assert_eq!(decompiler.decompile(&bytes).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_decode() {
+        let ns = load_ns();
+
+        // 0001: wait 1000
+        let mut decoder = Decoder::new(&[0x01, 0x00, 0x05, 0xE8, 0x03], Game::SA);
+        let i = decoder.next_instruction(&ns).unwrap();
+        assert_eq!((i.id, i.not), (0x0001, false));
+        assert_eq!(i.args, vec![Value::Int(1000)]);
+        assert!(decoder.is_eof());
+
+        // vc floats are stored as fixed-point numbers
+        let bytes = [0x05, 0x00, 0x02, 0x08, 0x00, 0x06, 0x28, 0x00];
+        let i = decode(&bytes, &ns, Game::VC).unwrap();
+        assert_eq!(format_value(&i[0].args[1]), "2.5");
+
+        // text labels are stored without the data type in vc
+        let mut bytes = vec![0xE5, 0x03];
+        bytes.extend(b"HELP1\0\0\0");
+        let i = decode(&bytes, &ns, Game::VC).unwrap();
+        assert_eq!(i[0].args, vec![Value::String8(String::from("HELP1"))]);
+
+        let e = decode(&[0x01, 0x00, 0x05, 0xE8], &ns, Game::SA).unwrap_err();
+        assert_eq!(e.offset, 3);
+        assert_eq!(e.to_string(), "00000003: unexpected end of data");
+
+        let e = decode(&[0xFF, 0x7F], &ns, Game::SA).unwrap_err();
+        assert_eq!(e.message, "unknown opcode 7FFF");
+    }
+
+    #[test]
+    fn test_format_value() {
+        let var = |global, offset, _type| ScmVariable {
+            global,
+            offset,
+            _type,
+        };
+        assert_eq!(format_value(&Value::Float(-0.5)), "-0.5");
+        assert_eq!(format_value(&Value::Float(100.0)), "100.0");
+        assert_eq!(
+            format_value(&Value::Var(var(true, 8, VariableType::LongString))),
+            "v$2"
+        );
+        assert_eq!(
+            format_value(&Value::Var(var(false, 3, VariableType::ShortString))),
+            "3@s"
+        );
+        assert_eq!(
+            format_value(&Value::Array {
+                var: var(true, 40, VariableType::ShortString),
+                index: var(false, 0, VariableType::Unknown),
+                size: 4,
+            }),
+            "s$10(0@,4s)"
+        );
+        assert_eq!(
+            format_value(&Value::VarString(String::from("a\"b\n"))),
+            "\"a\\\"b\\n\""
+        );
+    }
+}
@@ -176,7 +176,7 @@ mod tests {
             assert!(loaded);
             println!("{:?}", f.as_ref().unwrap().map);
 
-            let mut ptr = 0;
+            let mut ptr = 0usize;
             use std::mem::transmute;
 
             assert!(dictionary_str_by_num_find(f, 2, transmute(&mut ptr)));
@@ -207,7 +207,7 @@ mod tests {
             let loaded = dictionary_str_by_num_load_file(f, file.as_ptr());
             assert!(loaded);
 
-            let mut ptr = 0;
+            let mut ptr = 0usize;
             use std::mem::transmute;
 
             assert!(dictionary_str_by_num_find(f, 2, transmute(&mut ptr)));
@@ -237,7 +237,7 @@ mod tests {
             let loaded = dictionary_str_by_num_load_file(f, file.as_ptr());
             assert!(loaded);
 
-            let mut ptr = 0;
+            let mut ptr = 0usize;
             use std::mem::transmute;
 
             assert!(dictionary_str_by_num_find(f, 1, transmute(&mut ptr)));
@@ -267,7 +267,7 @@ mod tests {
             let loaded = dictionary_str_by_num_load_file(f, file.as_ptr());
             assert!(loaded);
 
-            let mut ptr = 0;
+            let mut ptr = 0usize;
             use std::mem::transmute;
 
             assert!(dictionary_str_by_num_find(f, 1, transmute(&mut ptr)));
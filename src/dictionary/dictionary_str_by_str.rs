@@ -146,7 +146,7 @@ mod tests {
             let loaded = dictionary_str_by_str_load_file(f, file.as_ptr());
             assert!(loaded);
 
-            let mut ptr = 0;
+            let mut ptr = 0usize;
             use std::mem::transmute;
 
             let op = std::ffi::CString::new("0002").unwrap();
@@ -185,7 +185,7 @@ mod tests {
             let loaded = dictionary_str_by_str_load_file(f, file.as_ptr());
             assert!(loaded);
 
-            let mut ptr = 0;
+            let mut ptr = 0usize;
             use std::mem::transmute;
 
             let op = std::ffi::CString::new("0002").unwrap();
@@ -224,7 +224,7 @@ mod tests {
             let loaded = dictionary_str_by_str_load_file(f, file.as_ptr());
             assert!(loaded);
 
-            let mut ptr = 0;
+            let mut ptr = 0usize;
             use std::mem::transmute;
 
             let op = std::ffi::CString::new("0001").unwrap();
@@ -263,7 +263,7 @@ mod tests {
             let loaded = dictionary_str_by_str_load_file(f, file.as_ptr());
             assert!(loaded);
 
-            let mut ptr = 0;
+            let mut ptr = 0usize;
             use std::mem::transmute;
 
             let op = std::ffi::CString::new("0001").unwrap();
@@ -1,5 +1,3 @@
-#![cfg(windows)]
-
 use std::collections::HashMap;
 use std::ffi::CString;
 use std::path::Path;
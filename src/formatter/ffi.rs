@@ -0,0 +1,46 @@
+use super::{FormatOptions, Formatter, FormatterBuilder};
+use crate::common_ffi::*;
+
+#[no_mangle]
+pub extern "C" fn formatter_new(
+    compiler_ini: PChar,
+    indent_width: u32,
+    use_tabs: bool,
+    keyword_case: u8,
+    space_around_operators: bool,
+    align_const: bool,
+) -> *mut Formatter {
+    let mut f = FormatterBuilder::new();
+
+    if let Some(compiler_ini_path) = pchar_to_str(compiler_ini) {
+        f.reserved_words(compiler_ini_path.into());
+    }
+    f.options(FormatOptions {
+        indent_width: indent_width as usize,
+        use_tabs,
+        keyword_case: keyword_case.into(),
+        space_around_operators,
+        align_const,
+    });
+    ptr_new(f.build())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn formatter_free(f: *mut Formatter) {
+    ptr_free(f)
+}
+
+/// the result must be released with [str_free]
+#[no_mangle]
+pub unsafe extern "C" fn formatter_format(
+    f: *mut Formatter,
+    input: PChar,
+    out: *mut PChar,
+) -> bool {
+    boolclosure! {{
+        let f = f.as_ref()?;
+        let input = pchar_to_string(input)?;
+        *out = std::ffi::CString::new(f.format(&input)).ok()?.into_raw();
+        Some(())
+    }}
+}
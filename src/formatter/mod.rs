@@ -0,0 +1,609 @@
+use std::path::PathBuf;
+
+use crate::{
+    dictionary::{config, ffi::CaseFormat, DictNumByString},
+    parser::{
+        cst::{CstElement, CstNode, CstToken, Trivia, TriviaKind},
+        function_signature, parse_lossless, FunctionCC, Span, SyntaxKind,
+    },
+    preprocessor::scopes::Scopes,
+    utils::compiler_const::{
+        TOKEN_CASE, TOKEN_CONST, TOKEN_DEFAULT, TOKEN_ELSE, TOKEN_END, TOKEN_EXPORT, TOKEN_FOR,
+        TOKEN_FUNCTION, TOKEN_HEX, TOKEN_IF, TOKEN_SWITCH, TOKEN_THEN, TOKEN_WHILE,
+    },
+};
+
+mod ffi;
+
+type FileName = PathBuf;
+
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    /// spaces per indentation level, ignored when `use_tabs` is set
+    pub indent_width: usize,
+    pub use_tabs: bool,
+    /// casing of the reserved words from compiler.ini (`if`, `end`, `int`, etc)
+    pub keyword_case: CaseFormat,
+    /// `a = b + 1` when set, `a=b+1` otherwise
+    pub space_around_operators: bool,
+    /// pad the names in `const ... end` blocks so that the `=` signs line up
+    pub align_const: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            indent_width: 4,
+            use_tabs: false,
+            keyword_case: CaseFormat::LowerCase,
+            space_around_operators: true,
+            align_const: true,
+        }
+    }
+}
+
+pub struct Formatter {
+    reserved_words: DictNumByString,
+    options: FormatOptions,
+}
+
+#[derive(Debug)]
+pub struct FormatterBuilder {
+    reserved_words: DictNumByString,
+    options: FormatOptions,
+}
+
+impl Default for FormatterBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FormatterBuilder {
+    pub fn new() -> Self {
+        Self {
+            reserved_words: DictNumByString::new(
+                config::ConfigBuilder::new()
+                    .set_case_format(CaseFormat::LowerCase)
+                    .build(),
+            ),
+            options: FormatOptions::default(),
+        }
+    }
+
+    pub fn reserved_words(&mut self, reserved_words: FileName) -> &mut Self {
+        self.reserved_words.load_file(&reserved_words);
+        self
+    }
+
+    pub fn options(&mut self, options: FormatOptions) -> &mut Self {
+        self.options = options;
+        self
+    }
+
+    pub fn build(&mut self) -> Formatter {
+        Formatter {
+            reserved_words: self.reserved_words.clone(),
+            options: self.options.clone(),
+        }
+    }
+}
+
+/// token or trivia of the lossless tree, tokens keep the kind of the node they belong to
+enum Leaf<'a> {
+    Token(&'a CstToken, &'a SyntaxKind),
+    Trivia(&'a Trivia),
+}
+
+impl Leaf<'_> {
+    fn text(&self) -> &str {
+        match self {
+            Leaf::Token(token, _) => &token.text,
+            Leaf::Trivia(trivia) => &trivia.text,
+        }
+    }
+}
+
+impl Formatter {
+    /// re-indents the blocks and normalizes the spacing and keyword casing,
+    /// comments and lines the parser could not recognize are kept as is
+    pub fn format(&self, source: &str) -> String {
+        let (mut cst, errors) = parse_lossless(source);
+        self.space_operators(&mut cst);
+
+        let mut leaves = vec![];
+        flatten(&cst, &mut leaves);
+
+        let mut scopes = Scopes::new();
+        let mut lines: Vec<(String, &str)> = vec![];
+        let mut const_block: Option<(usize, usize)> = None; // first line, depth
+        let source_lines = source.split('\n').collect::<Vec<_>>();
+        let mut line_number = 0; // block comments can span several lines of the source
+
+        for (line_index, (line, newline)) in split_lines(&leaves).into_iter().enumerate() {
+            let first_line = line_number;
+            line_number += line
+                .iter()
+                .map(|leaf| leaf.text().matches('\n').count())
+                .sum::<usize>()
+                + 1;
+            // ParseError lines are 1-based
+            let has_error = errors
+                .iter()
+                .any(|e| (first_line + 1..=line_number).contains(&(e.line as usize)));
+            if has_error {
+                // a lone `\r` is a line break for the lexer, but not for ParseError
+                let raw = match source_lines.get(first_line..line_number) {
+                    Some(raw) => raw.join("\n"),
+                    None => line.iter().map(Leaf::text).collect(),
+                };
+                let raw = raw.strip_suffix('\r').unwrap_or(&raw);
+                lines.push((raw.to_string(), newline));
+            }
+
+            let text = self.format_line(line);
+            let words = self.keywords(line);
+            let mut level = scopes.depth();
+            match words.first() {
+                Some(&TOKEN_END) => {
+                    let scope = scopes.get_current_scope();
+                    if scope.is_in_block() {
+                        scope.close_block();
+                    } else if !scope.is_root() {
+                        scopes.exit_scope(line_index);
+                    }
+                    level = scopes.depth();
+                    if let Some((start, depth)) = const_block {
+                        if depth == level {
+                            if self.options.align_const {
+                                align_const(&mut lines[start..]);
+                            }
+                            const_block = None;
+                        }
+                    }
+                }
+                Some(&TOKEN_THEN) | Some(&TOKEN_ELSE) | Some(&TOKEN_CASE)
+                | Some(&TOKEN_DEFAULT) => {
+                    // these lines belong to the enclosing statement
+                    level = level.saturating_sub(1);
+                }
+                Some(&TOKEN_IF) | Some(&TOKEN_FOR) | Some(&TOKEN_WHILE) | Some(&TOKEN_SWITCH)
+                | Some(&TOKEN_HEX) => {
+                    scopes.get_current_scope().add_block();
+                }
+                Some(&TOKEN_CONST) if words.len() == 1 && const_block.is_none() => {
+                    scopes.get_current_scope().add_block();
+                    const_block = Some((lines.len() + 1, level));
+                }
+                Some(&TOKEN_FUNCTION) | Some(&TOKEN_EXPORT) => {
+                    let is_function =
+                        words[0] == TOKEN_FUNCTION || words.get(1) == Some(&TOKEN_FUNCTION);
+                    if is_function && has_body(line) {
+                        scopes.enter_scope(line_index);
+                    }
+                }
+                _ => {}
+            }
+
+            if has_error {
+                continue;
+            }
+            let text = if text.is_empty() {
+                text
+            } else {
+                self.indent(level) + &text
+            };
+            lines.push((text, newline));
+        }
+
+        lines
+            .into_iter()
+            .map(|(text, newline)| text + newline)
+            .collect()
+    }
+
+    fn indent(&self, level: usize) -> String {
+        if self.options.use_tabs {
+            "\t".repeat(level)
+        } else {
+            " ".repeat(level * self.options.indent_width)
+        }
+    }
+
+    /// sets the whitespace around the operators of binary expressions
+    /// and the `=` of initializers (`const A = 1`, `int x = 1`)
+    fn space_operators(&self, node: &mut CstNode) {
+        for child in node.children.iter_mut() {
+            if let CstElement::Node(child) = child {
+                self.space_operators(child);
+            }
+        }
+        let is_operator: fn(&CstElement) -> bool = match node.kind {
+            SyntaxKind::BinaryExpr => {
+                |child| matches!(child, CstElement::Token(CstToken { kind: Some(kind), .. }) if is_binary_operator(kind))
+            }
+            SyntaxKind::ConstInitialization | SyntaxKind::VariableInitialization => {
+                |child| matches!(child, CstElement::Token(CstToken { kind: None, text }) if text == "=")
+            }
+            _ => return,
+        };
+        let Some(i) = node.children.iter().position(is_operator) else {
+            return;
+        };
+        let space = self.options.space_around_operators;
+
+        // keep `a - -1` apart
+        let after = match node.children.get(i + 1) {
+            Some(CstElement::Trivia(Trivia {
+                kind: TriviaKind::Whitespace,
+                ..
+            })) => node.children.get(i + 2),
+            other => other,
+        };
+        let space_after = space
+            || node.children[i].to_string().ends_with('-')
+                && after.is_some_and(|e| e.to_string().starts_with('-'));
+        respace(&mut node.children, i + 1, space_after);
+
+        if i > 0 {
+            let before = match node.children[i - 1] {
+                CstElement::Trivia(Trivia {
+                    kind: TriviaKind::Whitespace,
+                    ..
+                }) => i - 1,
+                _ => i,
+            };
+            respace(&mut node.children, before, space);
+        }
+    }
+
+    /// line text without the indentation: whitespace between the tokens collapsed,
+    /// reserved words in the configured case
+    fn format_line(&self, line: &[Leaf]) -> String {
+        let is_whitespace = |leaf: &&Leaf| {
+            matches!(
+                leaf,
+                Leaf::Trivia(Trivia {
+                    kind: TriviaKind::Whitespace,
+                    ..
+                })
+            )
+        };
+        let start = line
+            .iter()
+            .position(|l| !is_whitespace(&l))
+            .unwrap_or(line.len());
+        let end = line
+            .iter()
+            .rposition(|l| !is_whitespace(&l))
+            .map_or(start, |i| i + 1);
+        let line = &line[start..end];
+
+        let mut text = String::new();
+        let mut quote: Option<&str> = None; // inside of a string the parser could not recognize
+        for (i, leaf) in line.iter().enumerate() {
+            match leaf {
+                Leaf::Token(token, _) if quote.is_some() => {
+                    if quote == Some(token.text.as_str()) {
+                        quote = None;
+                    }
+                    text.push_str(&token.text);
+                }
+                Leaf::Trivia(trivia) if quote.is_some() => text.push_str(&trivia.text),
+                Leaf::Token(token, _)
+                    if token.kind.is_none() && ["'", "\""].contains(&token.text.as_str()) =>
+                {
+                    quote = Some(&token.text);
+                    text.push_str(&token.text);
+                }
+                Leaf::Token(token, parent) => {
+                    if self.is_reserved(token, parent) {
+                        text.push_str(&self.recase(&token.text));
+                    } else {
+                        text.push_str(&token.text);
+                    }
+                }
+                Leaf::Trivia(trivia) => match trivia.kind {
+                    // keep the spacing before trailing comments
+                    TriviaKind::Whitespace
+                        if matches!(
+                            line.get(i + 1),
+                            Some(Leaf::Trivia(Trivia {
+                                kind: TriviaKind::LineComment | TriviaKind::BlockComment,
+                                ..
+                            }))
+                        ) =>
+                    {
+                        text.push_str(&trivia.text)
+                    }
+                    TriviaKind::Whitespace => text.push(' '),
+                    _ => text.push_str(&trivia.text),
+                },
+            }
+        }
+        text
+    }
+
+    /// ids of the reserved words of the line, zero for other tokens
+    fn keywords(&self, line: &[Leaf]) -> Vec<i32> {
+        code(line)
+            .iter()
+            .map(|token| {
+                self.reserved_words
+                    .map
+                    .get(&token.text.to_ascii_lowercase())
+                    .copied()
+                    .unwrap_or(0)
+            })
+            .collect()
+    }
+
+    fn is_reserved(&self, token: &CstToken, parent: &SyntaxKind) -> bool {
+        let is_keyword = match &token.kind {
+            None => true,
+            Some(kind) if kind.is_keyword() => true,
+            // built-in routines and types
+            Some(SyntaxKind::Identifier) => matches!(
+                parent,
+                SyntaxKind::CommandCall
+                    | SyntaxKind::FunctionSignature
+                    | SyntaxKind::FunctionParameter
+            ),
+            _ => false,
+        };
+        is_keyword
+            && self
+                .reserved_words
+                .map
+                .contains_key(&token.text.to_ascii_lowercase())
+    }
+
+    fn recase(&self, word: &str) -> String {
+        match self.options.keyword_case {
+            CaseFormat::UpperCase => word.to_ascii_uppercase(),
+            CaseFormat::LowerCase => word.to_ascii_lowercase(),
+            CaseFormat::NoFormat => word.to_string(),
+        }
+    }
+}
+
+fn flatten<'a>(node: &'a CstNode, out: &mut Vec<Leaf<'a>>) {
+    for child in &node.children {
+        match child {
+            CstElement::Node(child) => flatten(child, out),
+            CstElement::Token(token) => out.push(Leaf::Token(token, &node.kind)),
+            CstElement::Trivia(trivia) => out.push(Leaf::Trivia(trivia)),
+        }
+    }
+}
+
+/// splits the leaves by line breaks, a block comment spanning several lines stays on the line it starts
+fn split_lines<'a, 'b>(leaves: &'b [Leaf<'a>]) -> Vec<(&'b [Leaf<'a>], &'a str)> {
+    let mut lines = vec![];
+    let mut start = 0;
+    for (i, leaf) in leaves.iter().enumerate() {
+        if let Leaf::Trivia(Trivia {
+            kind: TriviaKind::Newline,
+            text,
+        }) = leaf
+        {
+            lines.push((&leaves[start..i], text.as_str()));
+            start = i + 1;
+        }
+    }
+    lines.push((&leaves[start..], ""));
+    lines
+}
+
+/// tokens of the line, without comments
+fn code<'a>(line: &[Leaf<'a>]) -> Vec<&'a CstToken> {
+    line.iter()
+        .filter_map(|leaf| match leaf {
+            Leaf::Token(token, _) => Some(*token),
+            Leaf::Trivia(_) => None,
+        })
+        .collect()
+}
+
+/// whether the `function` line opens a body closed with `end` (foreign functions don't have one)
+fn has_body(line: &[Leaf]) -> bool {
+    let text = line
+        .iter()
+        .map(|leaf| match leaf {
+            Leaf::Token(token, _) => token.text.as_str(),
+            Leaf::Trivia(Trivia {
+                kind: TriviaKind::Whitespace,
+                text,
+            }) => text.as_str(),
+            Leaf::Trivia(_) => " ",
+        })
+        .collect::<String>();
+    let text = text.trim_start();
+    let text = match text.find(|c: char| c.is_whitespace()) {
+        Some(i) if !text[..i].eq_ignore_ascii_case("function") => text[i..].trim_start(),
+        _ => text,
+    };
+    function_signature(Span::from(text)).map_or(true, |(_, f)| f.cc == FunctionCC::Local)
+}
+
+/// sets the whitespace at `index` to a single space or removes it,
+/// line breaks and comments next to the operator are left in place
+fn respace(children: &mut Vec<CstElement>, index: usize, space: bool) {
+    let single = || {
+        CstElement::Trivia(Trivia {
+            kind: TriviaKind::Whitespace,
+            text: String::from(" "),
+        })
+    };
+    match children.get(index) {
+        Some(CstElement::Trivia(Trivia {
+            kind: TriviaKind::Whitespace,
+            ..
+        })) => {
+            if space {
+                children[index] = single();
+            } else {
+                children.remove(index);
+            }
+        }
+        Some(CstElement::Trivia(_)) | None => {}
+        Some(_) => {
+            if space {
+                children.insert(index, single());
+            }
+        }
+    }
+}
+
+/// lines up the `=` signs of the constants
+fn align_const(lines: &mut [(String, &str)]) {
+    let name = |line: &str| {
+        let code = line.trim_start();
+        if code.starts_with("//") || code.starts_with("/*") || code.starts_with('{') {
+            return None;
+        }
+        line.find('=').map(|i| line[..i].trim_end().chars().count())
+    };
+    let Some(width) = lines.iter().filter_map(|(line, _)| name(line)).max() else {
+        return;
+    };
+    for (line, _) in lines.iter_mut() {
+        if name(line).is_none() {
+            continue;
+        }
+        let i = line.find('=').unwrap();
+        let sep = if line[..i].ends_with(' ') { " " } else { "" };
+        *line = format!("{:width$}{sep}{}", line[..i].trim_end(), &line[i..]);
+    }
+}
+
+fn is_binary_operator(kind: &SyntaxKind) -> bool {
+    matches!(
+        kind,
+        SyntaxKind::OperatorBitwiseAnd
+            | SyntaxKind::OperatorBitwiseOr
+            | SyntaxKind::OperatorBitwiseXor
+            | SyntaxKind::OperatorBitwiseMod
+            | SyntaxKind::OperatorBitwiseShr
+            | SyntaxKind::OperatorBitwiseShl
+            | SyntaxKind::OperatorPlus
+            | SyntaxKind::OperatorMinus
+            | SyntaxKind::OperatorMul
+            | SyntaxKind::OperatorDiv
+            | SyntaxKind::OperatorEqual
+            | SyntaxKind::OperatorEqualEqual
+            | SyntaxKind::OperatorLessGreater
+            | SyntaxKind::OperatorBitwiseNotEqual
+            | SyntaxKind::OperatorBitwiseAndEqual
+            | SyntaxKind::OperatorBitwiseOrEqual
+            | SyntaxKind::OperatorBitwiseXorEqual
+            | SyntaxKind::OperatorBitwiseModEqual
+            | SyntaxKind::OperatorBitwiseShrEqual
+            | SyntaxKind::OperatorBitwiseShlEqual
+            | SyntaxKind::OperatorPlusEqual
+            | SyntaxKind::OperatorMinusEqual
+            | SyntaxKind::OperatorMulEqual
+            | SyntaxKind::OperatorDivEqual
+            | SyntaxKind::OperatorGreater
+            | SyntaxKind::OperatorGreaterEqual
+            | SyntaxKind::OperatorLess
+            | SyntaxKind::OperatorLessEqual
+            | SyntaxKind::OperatorTimedAdditionEqual
+            | SyntaxKind::OperatorTimedSubtractionEqual
+            | SyntaxKind::OperatorCastEqual
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn formatter(options: FormatOptions) -> Formatter {
+        FormatterBuilder::new()
+            .reserved_words("src/preprocessor/test/compiler.ini".into())
+            .options(options)
+            .build()
+    }
+
+    #[test]
+    fn test_indent() {
+        let f = formatter(FormatOptions::default());
+        assert_eq!(
+            f.format("IF 0@>1\nTHEN\nwait 0\n  ELSE\n while   $a<10 // loop\n$a+=1\n   END\nEnd\n"),
+            "if 0@ > 1\nthen\n    wait 0\nelse\n    while $a < 10 // loop\n        $a += 1\n    end\nend\n"
+        );
+        assert_eq!(
+            f.format("function foo(a: INT): Int\r\nswitch a\r\ncase 1\r\nreturn 1\r\ndefault\r\nreturn 0\r\nend\r\nend\r\n"),
+            "function foo(a: int): int\r\n    switch a\r\n    case 1\r\n        return 1\r\n    default\r\n        return 0\r\n    end\r\nend\r\n"
+        );
+        // foreign functions have no body
+        assert_eq!(
+            f.format("function bar<cdecl, 0x400000>(x: int)\nwait 0"),
+            "function bar<cdecl, 0x400000>(x: int)\nwait 0"
+        );
+        assert_eq!(
+            f.format("if and\n0@ > 1\n   1@ > 1\nthen\n\nend"),
+            "if and\n    0@ > 1\n    1@ > 1\nthen\n\nend"
+        );
+    }
+
+    #[test]
+    fn test_options() {
+        let f = formatter(FormatOptions {
+            use_tabs: true,
+            keyword_case: CaseFormat::UpperCase,
+            space_around_operators: false,
+            ..Default::default()
+        });
+        assert_eq!(
+            f.format("while 0@ > -1\n0@ -= 1\n  0@ = 1@ - -2\nend"),
+            "WHILE 0@>-1\n\t0@-=1\n\t0@=1@- -2\nEND"
+        );
+        assert_eq!(f.format("int x = 1 + 2"), "INT x=1+2");
+
+        let f = formatter(FormatOptions {
+            keyword_case: CaseFormat::NoFormat,
+            ..Default::default()
+        });
+        assert_eq!(f.format("If 0@>1\nEnd"), "If 0@ > 1\nEnd");
+    }
+
+    #[test]
+    fn test_const() {
+        let f = formatter(FormatOptions::default());
+        assert_eq!(
+            f.format("CONST\nMax = 10\n// comment\nMaxSpeed   =   2.5\nend\nconst a = 1, bb = 2"),
+            "const\n    Max      = 10\n    // comment\n    MaxSpeed = 2.5\nend\nconst a = 1, bb = 2"
+        );
+
+        let f = formatter(FormatOptions {
+            align_const: false,
+            ..Default::default()
+        });
+        assert_eq!(
+            f.format("const\nMax = 10\nMaxSpeed = 2.5\nend"),
+            "const\n    Max = 10\n    MaxSpeed = 2.5\nend"
+        );
+        assert_eq!(
+            f.format("const\nA=1\nB=A*2\nend\nint x=A+1, y[2]\nfloat z  =1.5"),
+            "const\n    A = 1\n    B = A * 2\nend\nint x = A + 1, y[2]\nfloat z = 1.5"
+        );
+    }
+
+    #[test]
+    fn test_keep() {
+        let f = formatter(FormatOptions::default());
+        // comments, strings and unknown lines stay as is
+        assert_eq!(
+            f.format("/* IF\n  END */\nwait  0   // END  If\n??? 'if  x'\ns$a = 'IF  x'\n{$INCLUDE x.txt}"),
+            "/* IF\n  END */\nwait 0   // END  If\n??? 'if  x'\ns$a = 'IF  x'\n{$INCLUDE x.txt}"
+        );
+        // member names and variables are not keywords
+        assert_eq!(f.format("Car.Break(0@)"), "Car.Break(0@)");
+        // lines with syntax errors are not touched
+        assert_eq!(
+            f.format("WAIT  0\n  0@ =  1  +   )\n/* multi-line\n*/ ???  x\nwait  0"),
+            "WAIT 0\n  0@ =  1  +   )\n/* multi-line\n*/ ???  x\nwait 0"
+        );
+    }
+}
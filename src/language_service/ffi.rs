@@ -89,6 +89,41 @@ pub unsafe extern "C" fn language_service_client_connect_in_memory(
     }}
 }
 
+/// `roots` is a list of directories separated by `;`, searched in the given order
+#[no_mangle]
+pub unsafe extern "C" fn language_service_set_search_roots(
+    server: *mut LanguageServer,
+    roots: PChar,
+) -> bool {
+    boolclosure! {{
+        let roots = pchar_to_str(roots)?
+            .split(';')
+            .filter(|root| !root.is_empty())
+            .map(Into::into)
+            .collect();
+        server.as_mut()?.set_search_roots(roots);
+        Some(())
+    }}
+}
+
+/// `defines` is a list of symbols for {$IFDEF} separated by `;`
+#[no_mangle]
+pub unsafe extern "C" fn language_service_set_defines(
+    server: *mut LanguageServer,
+    defines: PChar,
+) -> bool {
+    boolclosure! {{
+        let defines = pchar_to_str(defines)?
+            .split(';')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(String::from)
+            .collect();
+        server.as_mut()?.set_defines(defines);
+        Some(())
+    }}
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn language_service_client_notify_on_change(
     server: *mut LanguageServer,
@@ -189,8 +224,9 @@ pub unsafe extern "C" fn language_service_format_function_signature(
                     type_token.to_string()
                 };
 
+                let dots = if param.is_variadic { "..." } else { "" };
                 match name_token {
-                    Some(name) => format!("\"{}: {}\"", name, type_token),
+                    Some(name) => format!("\"{}{}: {}\"", dots, name, type_token),
                     None => format!("\"{}\"", type_token),
                 }
             })
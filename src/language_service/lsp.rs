@@ -0,0 +1,694 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+use serde_json::{json, Value};
+
+use super::ffi::Source;
+use super::scanner;
+use super::server::CACHE_FILE_SYMBOLS;
+use super::symbol_table::{SymbolInfoMap, SymbolTable, SymbolType};
+use crate::dictionary::{config, ffi::CaseFormat, DictNumByString};
+use crate::namespaces::namespaces::Namespaces;
+use crate::preprocessor::conditions::Conditions;
+
+const ERROR_METHOD_NOT_FOUND: i32 = -32601;
+const ERROR_INVALID_REQUEST: i32 = -32600;
+const ERROR_PARSE: i32 = -32700;
+
+struct Document {
+    text: String,
+    symbols: SymbolTable,
+}
+
+/// Language Server Protocol frontend over stdio, shares the scanner and symbol tables with the IDE
+pub struct LspServer {
+    reserved_words: DictNumByString,
+    implicit_includes: Vec<String>,
+    class_names: Vec<String>,
+    defines: Vec<String>,
+    search_roots: Vec<PathBuf>,
+    documents: HashMap</*uri*/ String, Document>,
+    shutdown: bool,
+}
+
+impl Default for LspServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LspServer {
+    pub fn new() -> Self {
+        Self {
+            reserved_words: DictNumByString::new(
+                config::ConfigBuilder::new()
+                    .set_case_format(CaseFormat::LowerCase)
+                    .build(),
+            ),
+            implicit_includes: vec![],
+            class_names: vec![],
+            defines: vec![],
+            search_roots: vec![],
+            documents: HashMap::new(),
+            shutdown: false,
+        }
+    }
+
+    /// processes messages until the client sends `exit`
+    pub fn run(
+        &mut self,
+        input: &mut impl BufRead,
+        output: &mut impl Write,
+    ) -> std::io::Result<()> {
+        while let Some(body) = read_message(input)? {
+            let message = match serde_json::from_slice::<Value>(&body) {
+                Ok(message) => message,
+                Err(e) => {
+                    // the id is unknown, the client can't match the error with a request
+                    let response = json!({
+                        "jsonrpc": "2.0",
+                        "id": Value::Null,
+                        "error": { "code": ERROR_PARSE, "message": e.to_string() }
+                    });
+                    write_message(output, &response)?;
+                    continue;
+                }
+            };
+            if message["method"] == "exit" {
+                break;
+            }
+            if let Some(response) = self.handle(&message) {
+                write_message(output, &response)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// returns the response for requests, notifications have none
+    fn handle(&mut self, message: &Value) -> Option<Value> {
+        let id = message.get("id").cloned();
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+
+        let result = match method {
+            "initialize" => Ok(self.initialize(params)),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            _ if self.shutdown => Err((ERROR_INVALID_REQUEST, "server is shut down")),
+            "textDocument/didOpen" => {
+                let document = &params["textDocument"];
+                self.update(&document["uri"], &document["text"]);
+                return None;
+            }
+            "textDocument/didChange" => {
+                // full synchronization, the last change has the whole text
+                let changes = params["contentChanges"].as_array()?;
+                self.update(&params["textDocument"]["uri"], &changes.last()?["text"]);
+                return None;
+            }
+            "textDocument/didSave" => {
+                let path = uri_to_path(params["textDocument"]["uri"].as_str()?)?;
+                CACHE_FILE_SYMBOLS.lock().unwrap().remove(&path);
+                return None;
+            }
+            "textDocument/didClose" => {
+                self.documents
+                    .remove(params["textDocument"]["uri"].as_str()?);
+                return None;
+            }
+            "textDocument/completion" => Ok(self.completion(params)),
+            "textDocument/hover" => Ok(self.hover(params)),
+            "textDocument/definition" => Ok(self.definition(params)),
+            "textDocument/documentSymbol" => Ok(self.document_symbols(params)),
+            _ => Err((ERROR_METHOD_NOT_FOUND, "method not found")),
+        };
+
+        // notifications don't have an id
+        let id = id?;
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message }
+            }),
+        })
+    }
+
+    /// initializationOptions: `{ "compilerIni": path, "library": path, "includes": [path], "defines": [name], "searchRoots": [path] }`
+    fn initialize(&mut self, params: &Value) -> Value {
+        let options = &params["initializationOptions"];
+        if let Some(compiler_ini) = options["compilerIni"].as_str() {
+            self.reserved_words.load_file(compiler_ini);
+        }
+        if let Some(library) = options["library"].as_str() {
+            let mut ns = Namespaces::new();
+            ns.load_library(library);
+            let class_names = ns
+                .commands
+                .values()
+                .filter_map(|command| command.class.as_ref())
+                .map(|class| class.to_ascii_lowercase())
+                .collect::<HashSet<_>>();
+            self.class_names = class_names.into_iter().collect();
+        }
+        if let Some(includes) = options["includes"].as_array() {
+            self.implicit_includes = includes
+                .iter()
+                .filter_map(|include| include.as_str().map(String::from))
+                .collect();
+        }
+
+        if let Some(defines) = options["defines"].as_array() {
+            self.defines = defines
+                .iter()
+                .filter_map(|define| define.as_str().map(String::from))
+                .collect();
+        }
+
+        if let Some(roots) = options["searchRoots"].as_array() {
+            self.search_roots = roots
+                .iter()
+                .filter_map(|root| root.as_str().map(PathBuf::from))
+                .collect();
+        }
+
+        json!({
+            "capabilities": {
+                "textDocumentSync": { "openClose": true, "change": 1, "save": true },
+                "completionProvider": {},
+                "hoverProvider": true,
+                "definitionProvider": true,
+                "documentSymbolProvider": true
+            },
+            "serverInfo": { "name": "sanny-builder", "version": env!("CARGO_PKG_VERSION") }
+        })
+    }
+
+    fn update(&mut self, uri: &Value, text: &Value) -> Option<()> {
+        let uri = uri.as_str()?;
+        let text = text.as_str()?;
+        let source = match uri_to_path(uri) {
+            Some(path) => Source::File(path),
+            None => Source::Memory,
+        };
+
+        let mut symbols = SymbolTable::new();
+        scanner::scan_document(
+            text,
+            &self.reserved_words,
+            &self.implicit_includes,
+            &source,
+            &self.class_names,
+            &mut symbols,
+            &mut HashSet::new(),
+            &mut vec![(0, 0)],
+            &mut Conditions::new(&self.defines),
+            &self.search_roots,
+        );
+        self.documents.insert(
+            String::from(uri),
+            Document {
+                text: String::from(text),
+                symbols,
+            },
+        );
+        Some(())
+    }
+
+    fn completion(&self, params: &Value) -> Value {
+        let Some((document, line, _)) = self.position(params) else {
+            return Value::Null;
+        };
+        let items = document
+            .symbols
+            .symbols
+            .values()
+            .filter_map(|symbols| symbols.iter().find(|s| s.is_visible_at(line)))
+            .map(|symbol| {
+                json!({
+                    "label": symbol.name_no_format,
+                    "kind": completion_kind(symbol._type),
+                    "detail": signature(symbol),
+                    "documentation": symbol.annotation,
+                })
+            })
+            .collect::<Vec<_>>();
+        Value::Array(items)
+    }
+
+    fn hover(&self, params: &Value) -> Value {
+        let Some(symbol) = self.find_symbol(params) else {
+            return Value::Null;
+        };
+        let mut contents = format!("```\n{}\n```", signature(symbol));
+        if let Some(annotation) = &symbol.annotation {
+            contents.push_str("\n\n");
+            contents.push_str(annotation);
+        }
+        json!({ "contents": { "kind": "markdown", "value": contents } })
+    }
+
+    fn definition(&self, params: &Value) -> Value {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        match self.find_symbol(params) {
+            Some(symbol) => self.location(uri, symbol).unwrap_or(Value::Null),
+            None => Value::Null,
+        }
+    }
+
+    fn document_symbols(&self, params: &Value) -> Value {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let Some(document) = self.documents.get(uri) else {
+            return Value::Null;
+        };
+        let path = uri_to_path(uri);
+
+        let mut items = document
+            .symbols
+            .symbols
+            .values()
+            .flatten()
+            // symbols declared in this document, not in the included files
+            .filter(|symbol| symbol.location.as_ref().map(|l| &l.file_name) == Some(&path))
+            .filter_map(|symbol| {
+                Some(json!({
+                    "name": symbol.name_no_format,
+                    "kind": symbol_kind(symbol._type),
+                    "detail": signature(symbol),
+                    "location": self.location(uri, symbol)?,
+                }))
+            })
+            .collect::<Vec<_>>();
+        // in the order of declaration
+        items.sort_by_key(|item| {
+            let start = &item["location"]["range"]["start"];
+            (start["line"].as_u64(), start["character"].as_u64())
+        });
+        Value::Array(items)
+    }
+
+    /// the document, the line and the character of the request's position (in UTF-16 code units)
+    fn position(&self, params: &Value) -> Option<(&Document, usize, usize)> {
+        let document = self
+            .documents
+            .get(params["textDocument"]["uri"].as_str()?)?;
+        let position = &params["position"];
+        Some((
+            document,
+            position["line"].as_u64()? as usize,
+            position["character"].as_u64()? as usize,
+        ))
+    }
+
+    fn find_symbol(&self, params: &Value) -> Option<&SymbolInfoMap> {
+        let (document, line, character) = self.position(params)?;
+        let text = document.text.lines().nth(line)?;
+        let word = word_at(text, char_index(text, character))?;
+        document
+            .symbols
+            .symbols
+            .get(&word.to_ascii_lowercase())?
+            .iter()
+            .find(|symbol| symbol.is_visible_at(line))
+    }
+
+    fn location(&self, uri: &str, symbol: &SymbolInfoMap) -> Option<Value> {
+        let location = symbol.location.as_ref()?;
+        let (uri, text) = match &location.file_name {
+            Some(path) if uri_to_path(uri).as_ref() != Some(path) => {
+                (path_to_uri(path), std::fs::read_to_string(path).ok()?)
+            }
+            _ => (String::from(uri), self.documents.get(uri)?.text.clone()),
+        };
+        let line = text.lines().nth(location.line).unwrap_or_default();
+        let start = find_word(line, &symbol.name_no_format)
+            .map(|offset| utf16_len(&line[..offset]))
+            .unwrap_or(0);
+        let end = start + utf16_len(&symbol.name_no_format);
+        Some(json!({
+            "uri": uri,
+            "range": {
+                "start": { "line": location.line, "character": start },
+                "end": { "line": location.line, "character": end },
+            }
+        }))
+    }
+}
+
+/// short description of the symbol in the source syntax
+fn signature(symbol: &SymbolInfoMap) -> String {
+    let name = &symbol.name_no_format;
+    let value = symbol.value.as_deref().unwrap_or_default();
+    match symbol._type {
+        SymbolType::Function => format!("function {name}{value}"),
+        SymbolType::Var => format!("{value} {name}"),
+        SymbolType::Number | SymbolType::String => format!("const {name} = {value}"),
+        SymbolType::Label => format!(":{name}"),
+        SymbolType::ModelName => format!("model {name}"),
+    }
+}
+
+/// CompletionItemKind
+fn completion_kind(_type: SymbolType) -> u8 {
+    match _type {
+        SymbolType::Function => 3,
+        SymbolType::Var => 6,
+        SymbolType::ModelName => 12,
+        SymbolType::Label => 18,
+        SymbolType::Number | SymbolType::String => 21,
+    }
+}
+
+/// SymbolKind
+fn symbol_kind(_type: SymbolType) -> u8 {
+    match _type {
+        SymbolType::Function => 12,
+        SymbolType::Var => 13,
+        SymbolType::Number | SymbolType::String | SymbolType::ModelName => 14,
+        SymbolType::Label => 20,
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '$' || c == '@'
+}
+
+/// byte offset of the first occurrence of the word, case-insensitive
+fn find_word(line: &str, word: &str) -> Option<usize> {
+    let line_lower = line.to_ascii_lowercase();
+    let word = word.to_ascii_lowercase();
+    line_lower.match_indices(&word).map(|(i, _)| i).find(|&i| {
+        let before = line_lower[..i].chars().next_back();
+        let after = line_lower[i + word.len()..].chars().next();
+        !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+    })
+}
+
+/// LSP counts characters in UTF-16 code units
+fn utf16_len(s: &str) -> usize {
+    s.encode_utf16().count()
+}
+
+/// index of the char at the given UTF-16 offset in the line
+fn char_index(line: &str, character: usize) -> usize {
+    let mut offset = 0;
+    for (index, c) in line.chars().enumerate() {
+        if offset >= character {
+            return index;
+        }
+        offset += c.len_utf16();
+    }
+    line.chars().count()
+}
+
+/// `character` is the index of a char in the line
+fn word_at(line: &str, character: usize) -> Option<&str> {
+    let chars = line.char_indices().collect::<Vec<_>>();
+    let is_word_at = |i: usize| chars.get(i).is_some_and(|(_, c)| is_word_char(*c));
+
+    // the cursor can be right after the word
+    let index = if is_word_at(character) {
+        character
+    } else {
+        character.checked_sub(1).filter(|&i| is_word_at(i))?
+    };
+    let start = (0..=index).rev().take_while(|&i| is_word_at(i)).last()?;
+    let end = (index..chars.len()).take_while(|&i| is_word_at(i)).last()?;
+    let end_offset = chars.get(end + 1).map_or(line.len(), |(offset, _)| *offset);
+    Some(&line[chars[start].0..end_offset])
+}
+
+/// `file:///C:/dev/main.txt` -> `C:/dev/main.txt`
+fn uri_to_path(uri: &str) -> Option<String> {
+    let path = uri.strip_prefix("file://")?;
+    let bytes = path.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = path.get(i + 1..i + 3);
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(b)) => {
+                decoded.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    let path = String::from_utf8(decoded).ok()?;
+    // drop the leading slash before a drive letter
+    match path.as_bytes() {
+        [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => Some(String::from(&path[1..])),
+        _ => Some(path),
+    }
+}
+
+fn path_to_uri(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut uri = String::from(if path.starts_with('/') {
+        "file://"
+    } else {
+        "file:///"
+    });
+    for b in path.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' | b':' => {
+                uri.push(b as char)
+            }
+            _ => uri.push_str(&format!("%{b:02X}")),
+        }
+    }
+    uri
+}
+
+/// returns the body of the next message
+fn read_message(input: &mut impl BufRead) -> std::io::Result<Option<Vec<u8>>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let Some(length) = length else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "missing Content-Length header",
+        ));
+    };
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> std::io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(id: i32, method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+    }
+
+    fn notification(method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "method": method, "params": params })
+    }
+
+    fn run(messages: Vec<Value>) -> Vec<Value> {
+        let mut input = vec![];
+        for message in messages {
+            write_message(&mut input, &message).unwrap();
+        }
+        run_input(input)
+    }
+
+    fn run_input(input: Vec<u8>) -> Vec<Value> {
+        let mut output = vec![];
+        LspServer::new()
+            .run(&mut input.as_slice(), &mut output)
+            .unwrap();
+
+        let mut output = output.as_slice();
+        let mut responses = vec![];
+        while let Some(body) = read_message(&mut output).unwrap() {
+            responses.push(serde_json::from_slice(&body).unwrap());
+        }
+        responses
+    }
+
+    #[test]
+    fn test_requests() {
+        let uri = "untitled:1";
+        let text = "const MAX = 10\nint x\n/// adds two numbers\nfunction add(a: int, b: int): int\nend\nx = MAX\n";
+        let at = |line: u32, character: u32| json!({ "textDocument": { "uri": uri }, "position": { "line": line, "character": character } });
+
+        let responses = run(vec![
+            request(
+                1,
+                "initialize",
+                json!({ "initializationOptions": { "compilerIni": "src/preprocessor/test/compiler.ini" } }),
+            ),
+            notification(
+                "textDocument/didOpen",
+                json!({ "textDocument": { "uri": uri, "languageId": "sb", "version": 1, "text": text } }),
+            ),
+            request(2, "textDocument/hover", at(5, 5)),
+            request(3, "textDocument/hover", at(3, 10)),
+            request(4, "textDocument/definition", at(5, 0)),
+            request(5, "textDocument/completion", at(5, 0)),
+            request(
+                6,
+                "textDocument/documentSymbol",
+                json!({ "textDocument": { "uri": uri } }),
+            ),
+            request(7, "unknown/method", json!({})),
+            request(8, "shutdown", Value::Null),
+            notification("exit", Value::Null),
+        ]);
+
+        assert_eq!(responses.len(), 8);
+        assert_eq!(
+            responses[0]["result"]["capabilities"]["hoverProvider"],
+            true
+        );
+        assert_eq!(
+            responses[1]["result"]["contents"]["value"],
+            "```\nconst MAX = 10\n```"
+        );
+        assert_eq!(
+            responses[2]["result"]["contents"]["value"],
+            "```\nfunction add(a: int, b: int): int\n```\n\nadds two numbers"
+        );
+        assert_eq!(
+            responses[3]["result"],
+            json!({
+                "uri": uri,
+                "range": {
+                    "start": { "line": 1, "character": 4 },
+                    "end": { "line": 1, "character": 5 }
+                }
+            })
+        );
+
+        let mut labels = responses[4]["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        labels.sort();
+        // function parameters are not visible outside of the function
+        assert_eq!(labels, vec!["MAX", "add", "x"]);
+
+        let names = responses[5]["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["MAX", "x", "add", "a", "b"]);
+
+        assert_eq!(responses[6]["error"]["code"], ERROR_METHOD_NOT_FOUND);
+        assert_eq!(responses[7]["result"], Value::Null);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let mut input = b"Content-Length: 8\r\n\r\n{\"id\": 1".to_vec();
+        write_message(&mut input, &request(2, "shutdown", Value::Null)).unwrap();
+        write_message(&mut input, &notification("exit", Value::Null)).unwrap();
+
+        // the server keeps running after the broken message
+        let responses = run_input(input);
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["id"], Value::Null);
+        assert_eq!(responses[0]["error"]["code"], ERROR_PARSE);
+        assert_eq!(responses[1]["id"], 2);
+        assert_eq!(responses[1]["result"], Value::Null);
+    }
+
+    #[test]
+    fn test_utf16_positions() {
+        let uri = "untitled:1";
+        // 😀 takes two UTF-16 code units
+        let text = "/*😀*/ int x\n'😀' x\n";
+        let at = |line: u32, character: u32| json!({ "textDocument": { "uri": uri }, "position": { "line": line, "character": character } });
+
+        let responses = run(vec![
+            request(
+                1,
+                "initialize",
+                json!({ "initializationOptions": { "compilerIni": "src/preprocessor/test/compiler.ini" } }),
+            ),
+            notification(
+                "textDocument/didOpen",
+                json!({ "textDocument": { "uri": uri, "languageId": "sb", "version": 1, "text": text } }),
+            ),
+            request(2, "textDocument/definition", at(1, 6)),
+        ]);
+        assert_eq!(
+            responses[1]["result"],
+            json!({
+                "uri": uri,
+                "range": {
+                    "start": { "line": 0, "character": 11 },
+                    "end": { "line": 0, "character": 12 }
+                }
+            })
+        );
+        assert_eq!(char_index("'😀' x", 5), 4);
+        assert_eq!(char_index("'😀' x", 6), 5);
+    }
+
+    #[test]
+    fn test_uri() {
+        assert_eq!(
+            uri_to_path("file:///C:/dev/my%20scripts/main.txt"),
+            Some(String::from("C:/dev/my scripts/main.txt"))
+        );
+        assert_eq!(
+            uri_to_path("file:///home/user/main.txt"),
+            Some(String::from("/home/user/main.txt"))
+        );
+        assert_eq!(uri_to_path("untitled:1"), None);
+        assert_eq!(
+            path_to_uri("C:\\dev\\my scripts\\main.txt"),
+            "file:///C:/dev/my%20scripts/main.txt"
+        );
+        assert_eq!(
+            path_to_uri("/home/user/main.txt"),
+            "file:///home/user/main.txt"
+        );
+    }
+
+    #[test]
+    fn test_word_at() {
+        assert_eq!(word_at("x = MAX", 0), Some("x"));
+        assert_eq!(word_at("x = MAX", 1), Some("x"));
+        assert_eq!(word_at("x = MAX", 5), Some("MAX"));
+        assert_eq!(word_at("x = MAX", 7), Some("MAX"));
+        assert_eq!(word_at("x = MAX", 2), None);
+        assert_eq!(word_at("$car = 0@", 8), Some("0@"));
+        assert_eq!(find_word("function add(a: int)", "a"), Some(13));
+    }
+}
@@ -1,5 +1,41 @@
 mod ffi;
-mod scanner;
+pub mod lsp;
+pub(crate) mod scanner;
 mod server;
-mod symbol_table;
+pub mod symbol_table;
 mod watcher;
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use crate::dictionary::DictNumByString;
+use crate::preprocessor::conditions::Conditions;
+use symbol_table::SymbolTable;
+
+/// builds the symbol table of a file outside of the IDE: scans the file, its includes and implicit includes
+///
+/// `defines` are the symbols set for {$IFDEF}, `search_roots` are the directories to look up relative includes in
+pub fn scan_file(
+    file_name: &str,
+    reserved_words: &DictNumByString,
+    implicit_includes: &Vec<String>,
+    class_names: &Vec<String>,
+    defines: &Vec<String>,
+    search_roots: &[PathBuf],
+) -> Option<SymbolTable> {
+    let text = String::from_utf8_lossy(&std::fs::read(file_name).ok()?).to_string();
+    let mut table = SymbolTable::new();
+    scanner::scan_document(
+        &text,
+        reserved_words,
+        implicit_includes,
+        &ffi::Source::File(String::from(file_name)),
+        class_names,
+        &mut table,
+        &mut HashSet::new(),
+        &mut vec![(0, 0)],
+        &mut Conditions::new(defines),
+        search_roots,
+    );
+    Some(table)
+}
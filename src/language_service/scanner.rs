@@ -1,14 +1,17 @@
 use super::ffi::Source;
-use super::symbol_table::{SymbolInfoMap, SymbolTable, SymbolType};
+use super::symbol_table::{SymbolInfoMap, SymbolLocation, SymbolTable, SymbolType};
 use crate::dictionary::DictNumByString;
 use crate::language_service::server::CACHE_FILE_SYMBOLS;
 use crate::parser::FunctionSignature;
+use crate::preprocessor::conditions::Conditions;
 use crate::utils::compiler_const::*;
+use crate::utils::path::find_path;
 use crate::utils::visibility_zone::VisibilityZone;
+use crate::v4::eval::evaluate_str;
 use crate::v4::helpers::token_str;
 use std::collections::HashSet;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 fn file_walk(
     file_name: &str,
@@ -21,6 +24,8 @@ fn file_walk(
         /* scope start line*/ u32,
     )>,
     line_number: Option<usize>,
+    conditions: &mut Conditions,
+    search_roots: &[PathBuf],
 ) {
     // ignore cyclic paths
     if !visited.insert(file_name.into()) {
@@ -50,6 +55,8 @@ fn file_walk(
         scope_stack,
         &mut local_table,
         line_number,
+        conditions,
+        search_roots,
     );
 
     // use found symbols and cache them
@@ -60,28 +67,16 @@ fn file_walk(
         .insert(file_name.into(), local_table);
 }
 
-fn resolve_path(p: &str, parent_file: &Option<String>) -> Option<String> {
-    let path = Path::new(p);
-
-    if path.is_absolute() {
-        return Some(p.to_string());
-    }
-
-    match parent_file {
-        Some(x) => {
-            // todo:
-            // If the file path is relative, the compiler scans directories in the following order to find the file:
-            // 1. directory of the file with the directive
-            // 2. data folder for the current edit mode
-            // 3. Sanny Builder root directory
-            // 4. the game directory
-            let dir_name = Path::new(&x).parent()?;
-            let abs_name = dir_name.join(path);
-
-            Some(String::from(abs_name.to_str()?))
+/// if the file is not found anywhere, the path next to the parent file is used, so it can be watched for changes
+fn resolve_path(p: &str, parent_file: &Option<String>, search_roots: &[PathBuf]) -> Option<String> {
+    let path = match find_path(p, parent_file.as_ref().map(Path::new), search_roots) {
+        Ok(path) => path,
+        Err(e) => {
+            log::debug!("{e}");
+            e.tried.into_iter().next()?
         }
-        None => None,
-    }
+    };
+    Some(String::from(path.to_str()?))
 }
 
 /// read the source code and extract all constants and variables
@@ -96,6 +91,8 @@ pub fn scan_document<'a>(
     table: &mut SymbolTable,
     visited: &mut HashSet<String>,
     scope_stack: &mut Vec<(u32, u32)>,
+    conditions: &mut Conditions,
+    search_roots: &[PathBuf],
 ) {
     for file_name in implicit_includes {
         file_walk(
@@ -106,6 +103,8 @@ pub fn scan_document<'a>(
             table,
             scope_stack,
             Some(0),
+            conditions,
+            search_roots,
         );
     }
 
@@ -118,6 +117,8 @@ pub fn scan_document<'a>(
         scope_stack,
         table,
         None, // line number to be determined as we parse the source code
+        conditions,
+        search_roots,
     );
 }
 
@@ -130,6 +131,8 @@ pub fn scan_text<'a>(
     scope_stack: &mut Vec<(u32, u32)>,
     table: &mut SymbolTable,
     line_number: Option<usize>,
+    conditions: &mut Conditions,
+    search_roots: &[PathBuf],
 ) {
     let mut inside_const = false;
     let file_name = match source {
@@ -163,6 +166,21 @@ pub fn scan_text<'a>(
         }
 
         let first_lower = first.to_ascii_lowercase();
+
+        match conditions.directive_line(&format!("{first} {rest}")) {
+            Ok(false) => {}
+            Ok(true) => continue,
+            Err(e) => {
+                log::debug!("{e}");
+                continue;
+            }
+        }
+        if !conditions.is_active() {
+            // symbols in the disabled part of {$IFDEF} are not visible
+            next_annotation = None;
+            continue;
+        }
+
         let token_id = reserved_words.map.get(&first_lower);
 
         // reset annotation if this line is not a function
@@ -177,6 +195,10 @@ pub fn scan_text<'a>(
             if the file is an implicit include (constants.txt), then all its symbols have a line number of 0
         */
         let line_number = line_number.unwrap_or(_index);
+        table.current_location = Some(SymbolLocation {
+            file_name: file_name.clone(),
+            line: _index,
+        });
         let stack_id = scope_stack.len() as u32;
 
         let mut process_function_signature = |line: &str, signature: &FunctionSignature| {
@@ -242,7 +264,7 @@ pub fn scan_text<'a>(
                         rest.as_str()
                     };
 
-                    let Some(path) = resolve_path(include_path, &file_name) else {
+                    let Some(path) = resolve_path(include_path, &file_name, search_roots) else {
                         continue;
                     };
 
@@ -254,6 +276,8 @@ pub fn scan_text<'a>(
                         table,
                         scope_stack,
                         Some(line_number),
+                        conditions,
+                        search_roots,
                     );
                 }
                 TOKEN_CONST => {
@@ -433,6 +457,7 @@ fn register_function(
         value: Some(function_params_and_return_types(line, signature)),
         name_no_format: token_str(&line, &signature.name).to_string(),
         annotation,
+        location: table.current_location.clone(),
     };
     register_symbol(table, map);
 }
@@ -477,6 +502,7 @@ fn register_const(
         value,
         name_no_format: name.to_string(),
         annotation,
+        location: table.current_location.clone(),
     };
     register_symbol(table, map);
 }
@@ -583,16 +609,24 @@ pub fn process_const_declaration(
     // }
 
     let Some(value) = tokens.next() else { return };
-    let value = value.trim();
+    let mut value = String::from(value.trim());
     let value_lower = value.to_ascii_lowercase();
-    let Some(_type) = get_type(value_lower.as_str()).or_else(|| {
-        table.symbols.get(value_lower.as_str()).and_then(|symbols| {
-            symbols
-                .iter()
-                .find(|symbol| symbol.stack_id == stack_id)
-                .map(|symbol| symbol._type)
+    let Some(_type) = get_type(value_lower.as_str())
+        .or_else(|| {
+            table.symbols.get(value_lower.as_str()).and_then(|symbols| {
+                symbols
+                    .iter()
+                    .find(|symbol| symbol.stack_id == stack_id)
+                    .map(|symbol| symbol._type)
+            })
+        })
+        .or_else(|| {
+            // constant expression, e.g. WIDTH * 2 + 1
+            let folded = fold_const_value(&value, table, stack_id)?;
+            value = folded;
+            Some(SymbolType::Number)
         })
-    }) else {
+    else {
         return;
     };
 
@@ -602,15 +636,25 @@ pub fn process_const_declaration(
         line_number + 1
     );
 
-    register_const(
-        table,
-        line_number,
-        stack_id,
-        name,
-        Some(String::from(value)),
-        _type,
-        None,
-    );
+    register_const(table, line_number, stack_id, name, Some(value), _type, None);
+}
+
+fn fold_const_value(value: &str, table: &SymbolTable, stack_id: u32) -> Option<String> {
+    let lookup = |name: &str| {
+        table.symbols.get(name).and_then(|symbols| {
+            symbols
+                .iter()
+                .find(|symbol| symbol.stack_id == stack_id && symbol._type == SymbolType::Number)
+                .and_then(|symbol| symbol.value.clone())
+        })
+    };
+    match evaluate_str(value, &lookup) {
+        Ok(v) => Some(v.to_string()),
+        Err(e) => {
+            log::debug!("Can't evaluate constant expression {}: {}", value, e);
+            None
+        }
+    }
 }
 
 pub fn process_var_declaration(
@@ -738,8 +782,9 @@ fn function_params_and_return_types(line: &str, signature: &FunctionSignature) -
             } else {
                 type_token.to_string()
             };
+            let dots = if param.is_variadic { "..." } else { "" };
             match name_token {
-                Some(name) => format!("{}: {}", name, type_token),
+                Some(name) => format!("{}{}: {}", dots, name, type_token),
                 None => format!("{}", type_token),
             }
         })
@@ -755,6 +800,8 @@ fn function_params_and_return_types(line: &str, signature: &FunctionSignature) -
 
     if return_types.is_empty() {
         format!("({params})")
+    } else if signature.has_optional_return() {
+        format!("({params}): optional {return_types}")
     } else {
         format!("({params}): {return_types}")
     }
@@ -766,8 +813,11 @@ mod tests {
 
     #[test]
     fn test1() {
-        let p = resolve_path("2.txt", &Some(String::from("C:/dev/1.txt"))).unwrap();
+        let p = resolve_path("2.txt", &Some(String::from("C:/dev/1.txt")), &[]).unwrap();
+        #[cfg(windows)]
         assert_eq!(p, String::from("C:/dev\\2.txt"));
+        #[cfg(not(windows))]
+        assert_eq!(p, String::from("C:/dev/2.txt"));
     }
 
     #[test]
@@ -862,4 +912,45 @@ mod tests {
         let s = strip_comments(s, &mut inside_comment, &mut inside_comment2);
         assert_eq!(s, ("test".to_string(), "".to_string()));
     }
+
+    #[test]
+    fn test_const_expression() {
+        let mut table = SymbolTable::new();
+        process_const_declaration("WIDTH = 10", &mut table, 1, 0);
+        process_const_declaration("MAX = WIDTH * 2 + 1", &mut table, 2, 0);
+        process_const_declaration("BAD = WIDTH + 1.0", &mut table, 3, 0);
+
+        let max = &table.symbols.get("max").unwrap()[0];
+        assert_eq!(max._type, SymbolType::Number);
+        assert_eq!(max.value, Some(String::from("21")));
+        // type mismatch, not registered
+        assert!(table.symbols.get("bad").is_none());
+    }
+
+    #[test]
+    fn test_conditions() {
+        let mut reserved_words = DictNumByString::new(
+            crate::dictionary::config::ConfigBuilder::new()
+                .set_case_format(crate::dictionary::ffi::CaseFormat::LowerCase)
+                .build(),
+        );
+        reserved_words.load_file("src/preprocessor/test/compiler.ini");
+
+        let mut table = SymbolTable::new();
+        scan_text(
+            "{$IFDEF DEBUG}\nconst a = 1\n{$ELSE}\nconst b = 2\n{$ENDIF}\n{$IFNDEF SA}\nconst c = 3\n{$ENDIF}",
+            &reserved_words,
+            &vec![],
+            &Source::Memory,
+            &mut HashSet::new(),
+            &mut vec![(0, 0)],
+            &mut table,
+            None,
+            &mut Conditions::new(["debug"]),
+            &[],
+        );
+        assert!(table.symbols.get("a").is_some());
+        assert!(table.symbols.get("b").is_none());
+        assert!(table.symbols.get("c").is_some());
+    }
 }
@@ -9,6 +9,7 @@ use super::{
 use crate::{
     dictionary::{config, ffi::CaseFormat, DictNumByString},
     namespaces::namespaces::Namespaces,
+    preprocessor::conditions::Conditions,
 };
 use std::{
     collections::{HashMap, HashSet},
@@ -37,6 +38,8 @@ lazy_static! {
     static ref IMPLICIT_INCLUDES: Mutex<HashMap<EditorHandle, Vec<String>>> =
         Mutex::new(HashMap::new());
     static ref CLASS_NAMES: Mutex<HashMap<EditorHandle, Vec<String>>> = Mutex::new(HashMap::new());
+    static ref SEARCH_ROOTS: Mutex<Vec<PathBuf>> = Mutex::new(vec![]);
+    static ref DEFINES: Mutex<Vec<String>> = Mutex::new(vec![]);
     pub static ref CACHE_FILE_SYMBOLS: Mutex<HashMap</*file name*/String, SymbolTable>> =
         Mutex::new(HashMap::new());
 }
@@ -88,6 +91,23 @@ impl LanguageServer {
         status_change(handle, Status::PendingScan);
     }
 
+    /// directories to look up relative include paths in, after the directory of the including file
+    pub fn set_search_roots(&mut self, roots: Vec<PathBuf>) {
+        log::debug!("Include search roots: {:?}", roots);
+        *SEARCH_ROOTS.lock().unwrap() = roots;
+    }
+
+    /// symbols for {$IFDEF} and {$IFNDEF}, the same the preprocessor is given when compiling
+    pub fn set_defines(&mut self, defines: Vec<String>) {
+        log::debug!("Defines: {:?}", defines);
+        *DEFINES.lock().unwrap() = defines;
+
+        // symbols in {$IFDEF} blocks may appear or disappear
+        for &handle in SOURCE_MAP.lock().unwrap().keys() {
+            status_change(handle, Status::PendingScan);
+        }
+    }
+
     pub fn disconnect(&mut self, handle: EditorHandle) {
         log::debug!("Client {} disconnected", handle);
         SYMBOL_TABLES.lock().unwrap().remove(&handle);
@@ -259,6 +279,8 @@ impl LanguageServer {
         let classes = CLASS_NAMES.lock().unwrap();
         let mut symbol_table = SYMBOL_TABLES.lock().unwrap();
         let implicit_includes = IMPLICIT_INCLUDES.lock().unwrap();
+        let search_roots = SEARCH_ROOTS.lock().unwrap();
+        let defines = DEFINES.lock().unwrap();
 
         if let Some(source) = sources.get(&handle) {
             log::debug!("Reading source {:?} to build document tree", source);
@@ -280,6 +302,8 @@ impl LanguageServer {
                 &mut table,
                 &mut visited,
                 &mut scope_stack,
+                &mut Conditions::new(defines.iter()),
+                &search_roots,
             );
             LanguageServer::update_watchers(&visited, handle);
 
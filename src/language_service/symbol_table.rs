@@ -14,6 +14,13 @@ pub enum SymbolType {
     Function = 5,
 }
 
+/// where the symbol is declared
+#[derive(Clone, Debug, PartialEq)]
+pub struct SymbolLocation {
+    pub file_name: Option<String>, // None for in-memory sources
+    pub line: usize,               // 0-based
+}
+
 #[derive(Clone, Debug)]
 pub struct SymbolInfoMap {
     pub zones: Vec<VisibilityZone>,
@@ -22,6 +29,7 @@ pub struct SymbolInfoMap {
     pub value: Option<String>,  // value of the symbol (for literals)
     pub name_no_format: String, // used for autocomplete
     pub annotation: Option<String>,
+    pub location: Option<SymbolLocation>,
 }
 
 impl SymbolInfoMap {
@@ -38,12 +46,14 @@ impl SymbolInfoMap {
 
 pub struct SymbolTable {
     pub symbols: HashMap</*symbol name (lowercase)*/ String, Vec<SymbolInfoMap>>,
+    pub(crate) current_location: Option<SymbolLocation>, // line being scanned, assigned to new symbols
 }
 
 impl SymbolTable {
     pub fn new() -> Self {
         Self {
             symbols: HashMap::new(),
+            current_location: None,
         }
     }
 
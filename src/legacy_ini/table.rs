@@ -49,7 +49,7 @@ pub enum ParamType {
     Float = 9,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Game {
     GTA3,
     VC,
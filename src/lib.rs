@@ -1,11 +1,10 @@
-use ctor::ctor;
-use simplelog::*;
-
 #[macro_use]
 extern crate lazy_static;
 #[macro_use]
 pub mod common_ffi;
+pub mod decompiler;
 pub mod dictionary;
+pub mod formatter;
 pub mod language_service;
 pub mod legacy_ini;
 pub mod namespaces;
@@ -17,10 +16,14 @@ pub mod v4;
 pub mod source_map;
 pub mod preprocessor;
 pub mod sanny_update;
+pub mod scm;
 pub mod ide;
 
-#[ctor]
+#[cfg(feature = "file-log")]
+#[ctor::ctor]
 fn main() {
+    use simplelog::*;
+
     let config = ConfigBuilder::new()
         .set_level_padding(LevelPadding::Off)
         .set_time_to_local(true)
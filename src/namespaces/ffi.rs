@@ -642,4 +642,53 @@ mod tests {
         let value = f.get_enum_value_by_name("String", "X");
         assert_eq!(value.unwrap(), &EnumMemberValue::Text(String::from("X")));
     }
+
+    #[test]
+    fn test_operators() {
+        use crate::namespaces::{OperandKind::*, Operator, ValueType::*};
+
+        let mut f = Namespaces::new();
+        let content = f.load_library("src/namespaces/test/operators.json");
+        assert!(content.is_some());
+
+        let op = |operator, operands: &[_]| f.get_opcode_by_operator(operator, operands).copied();
+        assert_eq!(
+            op(Operator::Assignment, &[GlobalVar(None), Literal(Int)]),
+            Some(0x0004)
+        );
+        assert_eq!(
+            op(Operator::Assignment, &[LocalVar(None), Literal(Float)]),
+            Some(0x0007)
+        );
+        assert_eq!(
+            op(
+                Operator::Addition,
+                &[GlobalVar(Some(Float)), Literal(Float)]
+            ),
+            Some(0x0009)
+        );
+        assert_eq!(
+            op(
+                Operator::Addition,
+                &[AnyVar(Some(Int)), Literal(Int), AnyVar(None)]
+            ),
+            Some(0x0A8E)
+        );
+        assert_eq!(
+            op(Operator::IsGreaterThan, &[Literal(Int), GlobalVar(None)]),
+            Some(0x001A)
+        );
+
+        // type, scope or number of operands don't match
+        assert_eq!(
+            op(Operator::Addition, &[GlobalVar(Some(Int)), Literal(Float)]),
+            None
+        );
+        assert_eq!(
+            op(Operator::IsEqualTo, &[LocalVar(None), Literal(Int)]),
+            None
+        );
+        assert_eq!(op(Operator::Not, &[AnyVar(None), AnyVar(None)]), None);
+        assert_eq!(op(Operator::Mod, &[AnyVar(None)]), None);
+    }
 }
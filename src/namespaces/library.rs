@@ -57,7 +57,7 @@ pub enum Version {
     _10DE,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Operator {
     Assignment,
     Addition,
@@ -79,6 +79,26 @@ pub enum Operator {
     ShiftRight,
 }
 
+/// numeric type of an operand
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ValueType {
+    Int,
+    Float,
+}
+
+/// operand of an expression, matched against command parameters to find the command for an operator.
+/// the type of a variable is unknown unless it is declared
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum OperandKind {
+    /// `$var`, `&10`
+    GlobalVar(Option<ValueType>),
+    /// `0@`
+    LocalVar(Option<ValueType>),
+    /// variable of either scope, e.g. a temporary
+    AnyVar(Option<ValueType>),
+    /// `1`, `1.0`
+    Literal(ValueType),
+}
 
 impl<'de> Deserialize<'de> for CommandParamSource {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
@@ -130,6 +150,52 @@ pub struct CommandParam {
     pub r#type: String,
 }
 
+impl CommandParam {
+    /// whether an operand of this kind can be passed to the parameter
+    pub fn accepts(&self, kind: &OperandKind) -> bool {
+        let (source_matches, _type) = match kind {
+            OperandKind::GlobalVar(t) => (
+                matches!(
+                    self.source,
+                    CommandParamSource::Any
+                        | CommandParamSource::AnyVar
+                        | CommandParamSource::AnyVarGlobal
+                ),
+                t,
+            ),
+            OperandKind::LocalVar(t) => (
+                matches!(
+                    self.source,
+                    CommandParamSource::Any
+                        | CommandParamSource::AnyVar
+                        | CommandParamSource::AnyVarLocal
+                ),
+                t,
+            ),
+            OperandKind::AnyVar(t) => (
+                !matches!(
+                    self.source,
+                    CommandParamSource::Literal | CommandParamSource::Pointer
+                ),
+                t,
+            ),
+            OperandKind::Literal(t) => (
+                matches!(
+                    self.source,
+                    CommandParamSource::Any | CommandParamSource::Literal
+                ),
+                &Some(*t),
+            ),
+        };
+        let type_matches = match (self.r#type.to_ascii_lowercase().as_str(), _type) {
+            ("any", _) | (_, None) => true,
+            ("int", Some(ValueType::Int)) | ("float", Some(ValueType::Float)) => true,
+            _ => false,
+        };
+        source_matches && type_matches
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Command {
     #[serde(default, deserialize_with = "convert_to_number")]
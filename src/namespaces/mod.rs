@@ -10,6 +10,8 @@ pub use library::CommandParam;
 pub use library::CommandParamType;
 pub use library::CommandParamSource;
 pub use library::Operator;
+pub use library::OperandKind;
+pub use library::ValueType;
 pub use library::Library;
 pub use library::Attr;
 pub use namespaces::OpId;
\ No newline at end of file
@@ -9,7 +9,7 @@ use crate::dictionary::{
 
 use super::{
     library::{Command, Library},
-    CommandParamType,
+    CommandParamType, OperandKind, Operator,
 };
 
 /**
@@ -35,6 +35,7 @@ pub struct Namespaces {
         HashMap</*member_name*/ String, /*opcodes index*/ usize>,
     >,
     map_op_by_command_name: HashMap</*command_name*/ String, OpId>,
+    map_op_by_operator: HashMap<Operator, Vec<OpId>>,
     pub map_enum: HashMap</*enum_name*/ String, HashMap</*member_name*/ String, EnumMember>>,
     library_version: CString,
 }
@@ -135,6 +136,7 @@ impl Namespaces {
             map_op_by_id: HashMap::new(),
             map_op_by_name: HashMap::new(),
             map_op_by_command_name: HashMap::new(),
+            map_op_by_operator: HashMap::new(),
             map_enum: HashMap::new(),
             library_version: CString::new("").unwrap(),
         }
@@ -744,6 +746,13 @@ impl Namespaces {
                     .insert(command.id, CString::new(command.short_desc.clone()).ok()?);
                 self.map_op_by_command_name
                     .insert(command.name.to_ascii_lowercase(), command.id);
+                if let Some(operator) = command.operator {
+                    let ids = self.map_op_by_operator.entry(operator).or_default();
+                    if !ids.contains(&command.id) {
+                        ids.push(command.id);
+                        ids.sort();
+                    }
+                }
                 self.commands.insert(command.id, command);
             }
         }
@@ -751,6 +760,27 @@ impl Namespaces {
         Some(())
     }
 
+    /// finds the command tagged with the operator in the library whose parameters accept the operands.
+    /// operands are listed in the order of the command outputs followed by the inputs, e.g. `[dest, a, b]` for `dest = a + b`.
+    /// if several commands match, the one with the lowest id is returned
+    pub fn get_opcode_by_operator(
+        &self,
+        operator: Operator,
+        operands: &[OperandKind],
+    ) -> Option<&OpId> {
+        self.map_op_by_operator.get(&operator)?.iter().find(|id| {
+            self.commands.get(id).is_some_and(|command| {
+                command.input.len() + command.output.len() == operands.len()
+                    && command
+                        .output
+                        .iter()
+                        .chain(command.input.iter())
+                        .zip(operands)
+                        .all(|(param, kind)| param.accepts(kind))
+            })
+        })
+    }
+
     pub fn get_command_snippet_line<'a>(&self, id: OpId) -> Option<CString> {
         let command = self.commands.get(&id)?;
         let mut snippet = super::snippet::command_to_snippet_line(command, false);
@@ -766,6 +796,10 @@ impl Namespaces {
             .get(&command_name.to_ascii_lowercase())
     }
 
+    pub fn get_command(&self, id: OpId) -> Option<&Command> {
+        self.commands.get(&id)
+    }
+
     pub fn is_condition<'a>(&self, id: OpId) -> Option<bool> {
         self.commands.get(&id).map(|c| c.attrs.is_condition)
     }
@@ -1,11 +1,19 @@
+use nom::branch::alt;
+use nom::character::complete::char;
+use nom::combinator::cut;
 use nom::combinator::opt;
+use nom::combinator::peek;
 use nom::combinator::{consumed, map};
-use nom::sequence::tuple;
+use nom::multi::{many0, many1};
+use nom::sequence::{pair, preceded, terminated, tuple};
+use nom_locate::position;
 
 use crate::parser::helpers::ws;
 use crate::parser::interface::*;
+use crate::parser::literal;
 use crate::parser::operator;
 use crate::parser::unary::unary;
+use crate::parser::variable;
 
 fn map_binary(span: Span, left: Node, op: Option<(Token, Node)>) -> Node {
     match op {
@@ -19,54 +27,126 @@ fn map_binary(span: Span, left: Node, op: Option<(Token, Node)>) -> Node {
     }
 }
 
+/// folds `a op b op c` into `(a op b) op c`, each expression spanning from `a` to its right operand
+fn fold_binary(start: Span, left: Node, ops: Vec<(Token, Node, Span)>) -> Node {
+    ops.into_iter().fold(left, |left, (operator, right, end)| {
+        Node::Binary(BinaryExpr {
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+            token: Token {
+                start: start.location_offset() + 1,
+                len: end.location_offset() - start.location_offset(),
+                syntax_kind: SyntaxKind::BinaryExpr,
+            },
+        })
+    })
+}
+
+/// right-hand operand of a binary operator; if the line ends right after the operator
+/// the input can't be read any other way, so the error is reported as is
+fn operand<'a, F>(mut f: F) -> impl FnMut(Span<'a>) -> R<'a, Node>
+where
+    F: FnMut(Span<'a>) -> R<'a, Node>,
+{
+    move |s: Span<'a>| match f(s) {
+        Err(nom::Err::Error(e)) if s.fragment().lines().next().unwrap_or("").trim().is_empty() => {
+            Err(nom::Err::Failure(e))
+        }
+        r => r,
+    }
+}
+
 pub fn assignment(s: Span) -> R<Node> {
     map(
         consumed(tuple((
-            equality,
-            opt(tuple((ws(operator::assignment), equality))),
+            alt((targets, equality)),
+            // anything after the assignment operator must be a valid expression
+            opt(tuple((ws(operator::assignment), cut(equality)))),
         ))),
         |(span, (left, op))| map_binary(span, left, op),
     )(s)
 }
 
-fn equality(s: Span) -> R<Node> {
+// 0@, 1@ = MyFunc()
+fn targets(s: Span) -> R<Node> {
     map(
-        consumed(tuple((
+        consumed(terminated(
+            pair(target, many1(preceded(ws(char(',')), target))),
+            peek(ws(operator::assignment)),
+        )),
+        |(span, (first, rest))| {
+            Node::Tuple(TupleExpr {
+                items: std::iter::once(first).chain(rest).collect(),
+                token: Token::from(span, SyntaxKind::TupleExpr),
+            })
+        },
+    )(s)
+}
+
+fn target(s: Span) -> R<Node> {
+    alt((
+        map(variable::variable, Node::Variable),
+        map(literal::identifier, Node::Literal),
+    ))(s)
+}
+
+pub fn equality(s: Span) -> R<Node> {
+    map(
+        tuple((
+            position,
             bitwise,
-            opt(tuple((ws(operator::equality), bitwise))),
-        ))),
-        |(span, (left, op))| map_binary(span, left, op),
+            many0(tuple((ws(operator::equality), operand(bitwise), position))),
+        )),
+        |(start, left, ops)| fold_binary(start, left, ops),
     )(s)
 }
 
 fn bitwise(s: Span) -> R<Node> {
     map(
-        consumed(tuple((
+        tuple((
+            position,
             comparison,
-            opt(tuple((ws(operator::bitwise), comparison))),
-        ))),
-        |(span, (left, op))| map_binary(span, left, op),
+            many0(tuple((
+                ws(operator::bitwise),
+                operand(comparison),
+                position,
+            ))),
+        )),
+        |(start, left, ops)| fold_binary(start, left, ops),
     )(s)
 }
 
 fn comparison(s: Span) -> R<Node> {
     map(
-        consumed(tuple((term, opt(tuple((ws(operator::comparison), term)))))),
-        |(span, (left, op))| map_binary(span, left, op),
+        tuple((
+            position,
+            term,
+            many0(tuple((ws(operator::comparison), operand(term), position))),
+        )),
+        |(start, left, ops)| fold_binary(start, left, ops),
     )(s)
 }
 
 fn term(s: Span) -> R<Node> {
     map(
-        consumed(tuple((factor, opt(tuple((ws(operator::add_sub), factor)))))),
-        |(span, (left, op))| map_binary(span, left, op),
+        tuple((
+            position,
+            factor,
+            many0(tuple((ws(operator::add_sub), operand(factor), position))),
+        )),
+        |(start, left, ops)| fold_binary(start, left, ops),
     )(s)
 }
 
 fn factor(s: Span) -> R<Node> {
     map(
-        consumed(tuple((unary, opt(tuple((ws(operator::mul_div), unary)))))),
-        |(span, (left, op))| map_binary(span, left, op),
+        tuple((
+            position,
+            unary,
+            many0(tuple((ws(operator::mul_div), operand(unary), position))),
+        )),
+        |(start, left, ops)| fold_binary(start, left, ops),
     )(s)
 }
 
@@ -290,4 +370,50 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_chained() {
+        let (_, ast) = parse("0@ = 1 + 2 - 3@ * 4").unwrap();
+        let Node::Binary(e) = &ast.body[0] else {
+            panic!("expected binary expression");
+        };
+        // (1 + 2) - (3@ * 4)
+        let Node::Binary(right) = e.right.as_ref() else {
+            panic!("expected binary expression");
+        };
+        assert_eq!(right.get_operator(), &SyntaxKind::OperatorMinus);
+        assert_eq!((right.token.start, right.token.len), (6, 14));
+
+        let Node::Binary(sum) = right.left.as_ref() else {
+            panic!("expected binary expression");
+        };
+        assert_eq!(sum.get_operator(), &SyntaxKind::OperatorPlus);
+        assert_eq!((sum.token.start, sum.token.len), (6, 5));
+
+        let Node::Binary(product) = right.right.as_ref() else {
+            panic!("expected binary expression");
+        };
+        assert_eq!(product.get_operator(), &SyntaxKind::OperatorMul);
+        assert_eq!((product.token.start, product.token.len), (14, 6));
+    }
+
+    #[test]
+    fn test_targets() {
+        let (_, ast) = parse("0@, $b = MyFunc(2@, 3.0)").unwrap();
+        let Node::Binary(e) = &ast.body[0] else {
+            panic!("expected binary expression");
+        };
+        let Node::Tuple(targets) = e.left.as_ref() else {
+            panic!("expected tuple");
+        };
+        assert_eq!(targets.items.len(), 2);
+        assert_eq!((targets.token.start, targets.token.len), (1, 6));
+        let Node::Call(call) = e.right.as_ref() else {
+            panic!("expected call");
+        };
+        assert_eq!(call.arguments.len(), 2);
+
+        // a list of variables is only valid before the assignment
+        assert!(parse("0@, 1@").is_err());
+    }
 }
@@ -0,0 +1,562 @@
+use crate::parser::interface::*;
+
+/// lossless syntax tree built on top of [AST]: the text between the AST tokens is kept
+/// as trivia (whitespace, line breaks, comments) and plain tokens (keywords, punctuation),
+/// so printing the tree gives back the source byte for byte
+#[derive(Debug, PartialEq, Clone)]
+pub struct CstNode {
+    pub kind: SyntaxKind,
+    pub children: Vec<CstElement>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum CstElement {
+    Node(CstNode),
+    Token(CstToken),
+    Trivia(Trivia),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CstToken {
+    /// none for punctuation and text the parser could not recognize
+    pub kind: Option<SyntaxKind>,
+    /// original text, including the casing
+    pub text: String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum TriviaKind {
+    Whitespace,
+    Newline,
+    /// `// comment`
+    LineComment,
+    /// `/* comment */` or `{ comment }`
+    BlockComment,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+}
+
+impl CstNode {
+    /// tokens of the tree in the source order, without trivia
+    pub fn tokens(&self) -> Vec<&CstToken> {
+        let mut tokens = vec![];
+        for child in &self.children {
+            match child {
+                CstElement::Node(node) => tokens.extend(node.tokens()),
+                CstElement::Token(token) => tokens.push(token),
+                CstElement::Trivia(_) => {}
+            }
+        }
+        tokens
+    }
+
+    /// same as [CstNode::tokens], the text of the tokens can be changed (e.g. to rename a variable)
+    pub fn tokens_mut(&mut self) -> Vec<&mut CstToken> {
+        let mut tokens = vec![];
+        for child in &mut self.children {
+            match child {
+                CstElement::Node(node) => tokens.extend(node.tokens_mut()),
+                CstElement::Token(token) => tokens.push(token),
+                CstElement::Trivia(_) => {}
+            }
+        }
+        tokens
+    }
+}
+
+impl std::fmt::Display for CstNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.children
+            .iter()
+            .try_for_each(|child| write!(f, "{child}"))
+    }
+}
+
+impl std::fmt::Display for CstElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CstElement::Node(node) => write!(f, "{node}"),
+            CstElement::Token(token) => write!(f, "{}", token.text),
+            CstElement::Trivia(trivia) => write!(f, "{}", trivia.text),
+        }
+    }
+}
+
+/// builds the lossless tree for the source parsed into `ast`
+pub fn from_ast(ast: &AST, s: &str) -> CstNode {
+    let parts = ast.body.iter().map(Part::Node).collect();
+    build(SyntaxKind::SourceFile, 0, s.len(), parts, s)
+}
+
+enum Part<'a> {
+    Node(&'a Node),
+    Variable(&'a Variable),
+    Token(&'a Token),
+    Group(SyntaxKind, &'a Token, Vec<Part<'a>>),
+}
+
+impl<'a> Part<'a> {
+    /// kind, own token and children of a part, none for leaf tokens
+    fn expand(&self) -> Option<(SyntaxKind, &'a Token, Vec<Part<'a>>)> {
+        match self {
+            Part::Node(node) => expand_node(node),
+            Part::Variable(var) => expand_variable(var),
+            Part::Token(_) => None,
+            Part::Group(kind, token, parts) => Some((
+                kind.clone(),
+                *token,
+                parts.iter().map(Part::reborrow).collect(),
+            )),
+        }
+    }
+
+    fn reborrow(&self) -> Part<'a> {
+        match self {
+            Part::Node(node) => Part::Node(node),
+            Part::Variable(var) => Part::Variable(var),
+            Part::Token(token) => Part::Token(token),
+            Part::Group(kind, token, parts) => Part::Group(
+                kind.clone(),
+                token,
+                parts.iter().map(Part::reborrow).collect(),
+            ),
+        }
+    }
+
+    fn leaf(&self) -> Option<&'a Token> {
+        match self {
+            Part::Token(token) => Some(token),
+            Part::Node(Node::Literal(token)) => Some(token),
+            Part::Node(Node::String(s)) => Some(&s.token),
+            Part::Variable(Variable::Pop(token) | Variable::Push(token)) => Some(token),
+            _ => None,
+        }
+    }
+
+    /// byte range covering the part and all its children
+    fn range(&self) -> (usize, usize) {
+        if let Some(token) = self.leaf() {
+            return token_range(token);
+        }
+        let Some((_, token, parts)) = self.expand() else {
+            return (0, 0);
+        };
+        parts
+            .iter()
+            .map(Part::range)
+            .filter(|(start, end)| start < end)
+            .fold(token_range(token), |(start, end), (s, e)| {
+                (start.min(s), end.max(e))
+            })
+    }
+}
+
+fn token_range(token: &Token) -> (usize, usize) {
+    let start = token.start.saturating_sub(1);
+    (start, start + token.len)
+}
+
+fn expand_node(node: &Node) -> Option<(SyntaxKind, &Token, Vec<Part<'_>>)> {
+    Some(match node {
+        Node::Literal(_) | Node::String(_) => return None,
+        Node::Variable(var) => return expand_variable(var),
+        Node::Binary(e) => (
+            SyntaxKind::BinaryExpr,
+            &e.token,
+            vec![
+                Part::Node(&e.left),
+                Part::Token(&e.operator),
+                Part::Node(&e.right),
+            ],
+        ),
+        Node::Unary(e) => (
+            SyntaxKind::UnaryPrefixExpr,
+            &e.token,
+            vec![Part::Token(&e.operator), Part::Node(&e.operand)],
+        ),
+        Node::ConstDeclaration(c) => (
+            SyntaxKind::ConstDeclaration,
+            &c.token,
+            c.items
+                .iter()
+                .map(|item| {
+                    Part::Group(
+                        SyntaxKind::ConstInitialization,
+                        &item.token,
+                        vec![Part::Token(&item.name), Part::Node(&item.value)],
+                    )
+                })
+                .collect(),
+        ),
+        Node::FunctionSignature(f) => return expand_signature(f),
+        Node::FunctionDeclaration(f) => {
+            let mut parts = vec![signature(&f.signature)];
+            parts.extend(nodes(&f.body));
+            (SyntaxKind::FunctionDeclaration, &f.token, parts)
+        }
+        Node::VariableDeclaration(v) => (
+            SyntaxKind::VariableDeclaration,
+            &v.token,
+            v.items
+                .iter()
+                .map(|item| {
+                    let mut parts = vec![Part::Token(&item.name)];
+                    parts.extend(item.size.iter().map(Part::Token));
+                    parts.extend(item.value.iter().map(|v| Part::Node(v)));
+                    Part::Group(SyntaxKind::VariableInitialization, &item.token, parts)
+                })
+                .collect(),
+        ),
+        Node::Label(l) => (
+            SyntaxKind::LabelDeclaration,
+            &l.token,
+            vec![Part::Token(&l.name)],
+        ),
+        Node::CommandCall(c) => {
+            let mut parts: Vec<_> = c
+                .opcode
+                .iter()
+                .chain(c.name.iter())
+                .map(Part::Token)
+                .collect();
+            parts.extend(nodes(&c.arguments));
+            (SyntaxKind::CommandCall, &c.token, parts)
+        }
+        Node::If(i) => {
+            let mut parts = vec![conditions(&i.conditions)];
+            parts.extend(nodes(&i.body));
+            parts.extend(i.else_body.iter().flat_map(|body| nodes(body)));
+            (SyntaxKind::IfStatement, &i.token, parts)
+        }
+        Node::While(w) => {
+            let mut parts = vec![conditions(&w.conditions)];
+            parts.extend(nodes(&w.body));
+            (SyntaxKind::WhileStatement, &w.token, parts)
+        }
+        Node::For(f) => {
+            let mut parts = vec![Part::Node(&f.var), Part::Node(&f.start), Part::Node(&f.end)];
+            parts.extend(f.step.iter().map(|step| Part::Node(step)));
+            parts.extend(nodes(&f.body));
+            (SyntaxKind::ForStatement, &f.token, parts)
+        }
+        Node::Switch(s) => {
+            let mut parts = vec![Part::Node(&s.subject)];
+            parts.extend(s.cases.iter().map(|case| {
+                let mut parts = nodes(&case.values);
+                parts.extend(nodes(&case.body));
+                Part::Group(SyntaxKind::SwitchCase, &case.token, parts)
+            }));
+            parts.extend(s.default.iter().flat_map(|body| nodes(body)));
+            (SyntaxKind::SwitchStatement, &s.token, parts)
+        }
+        Node::Member(m) => (
+            SyntaxKind::MemberExpr,
+            &m.token,
+            vec![Part::Node(&m.object), Part::Token(&m.member)],
+        ),
+        Node::Call(c) => {
+            let mut parts = vec![Part::Node(&c.callee)];
+            parts.extend(nodes(&c.arguments));
+            (SyntaxKind::CallExpr, &c.token, parts)
+        }
+        Node::Tuple(t) => (SyntaxKind::TupleExpr, &t.token, nodes(&t.items)),
+    })
+}
+
+fn nodes(nodes: &[Node]) -> Vec<Part<'_>> {
+    nodes.iter().map(Part::Node).collect()
+}
+
+fn expand_variable(var: &Variable) -> Option<(SyntaxKind, &Token, Vec<Part<'_>>)> {
+    Some(match var {
+        Variable::Global(v) | Variable::Local(v) | Variable::Adma(v) => (
+            v.token.syntax_kind.clone(),
+            &v.token,
+            vec![Part::Token(&v.name)],
+        ),
+        Variable::Indexed(v) => (
+            SyntaxKind::IndexedVariable,
+            &v.token,
+            vec![Part::Variable(&v.var), Part::Node(&v.index)],
+        ),
+        Variable::ArrayElement(v) => (
+            SyntaxKind::ArrayElementSCR,
+            &v.token,
+            vec![
+                Part::Variable(&v.array_var),
+                Part::Variable(&v.index_var),
+                Part::Token(&v.len),
+            ],
+        ),
+        Variable::Pop(_) | Variable::Push(_) => return None,
+    })
+}
+
+fn signature(f: &FunctionSignature) -> Part<'_> {
+    let (kind, token, parts) = expand_signature(f).unwrap();
+    Part::Group(kind, token, parts)
+}
+
+fn expand_signature(f: &FunctionSignature) -> Option<(SyntaxKind, &Token, Vec<Part<'_>>)> {
+    let mut parts = vec![Part::Token(&f.name)];
+    parts.extend(f.address.iter().map(Part::Token));
+    parts.extend(f.parameters.iter().map(|p| {
+        let mut parts: Vec<_> = p.name.iter().map(Part::Token).collect();
+        parts.push(Part::Token(&p._type));
+        parts.extend(p.size.iter().map(Part::Token));
+        Part::Group(SyntaxKind::FunctionParameter, &p.token, parts)
+    }));
+    parts.extend(f.return_types.iter().map(|t| Part::Token(&t._type)));
+    Some((SyntaxKind::FunctionSignature, &f.token, parts))
+}
+
+fn conditions(c: &Conditions) -> Part<'_> {
+    let mut parts: Vec<_> = c.operator.iter().map(Part::Token).collect();
+    parts.extend(c.items.iter().map(|item| {
+        let mut parts: Vec<_> = item.not.iter().map(Part::Token).collect();
+        parts.push(Part::Node(&item.expr));
+        Part::Group(SyntaxKind::Condition, &item.token, parts)
+    }));
+    Part::Group(SyntaxKind::Conditions, &c.token, parts)
+}
+
+/// fills the range with the parts, the text between them is split into trivia and plain tokens.
+/// parts overlapping the previous one or outside of the range stay in the text
+fn build(kind: SyntaxKind, start: usize, end: usize, parts: Vec<Part>, s: &str) -> CstNode {
+    let mut parts = parts
+        .into_iter()
+        .map(|part| (part.range(), part))
+        .filter(|((a, b), _)| a < b)
+        .collect::<Vec<_>>();
+    parts.sort_by_key(|((a, _), _)| *a);
+
+    let mut children = vec![];
+    let mut cursor = start;
+    for ((a, b), part) in parts {
+        if a < cursor || b > end {
+            continue;
+        }
+        lex(&s[cursor..a], &mut children);
+        children.push(match part.leaf() {
+            Some(token) => {
+                let text = &s[a..b];
+                // `return 0` and the parameter types are parsed as identifiers
+                let kind = match (&token.syntax_kind, &kind) {
+                    (
+                        SyntaxKind::Identifier,
+                        SyntaxKind::CommandCall
+                        | SyntaxKind::FunctionSignature
+                        | SyntaxKind::FunctionParameter,
+                    ) => keyword(text).unwrap_or(SyntaxKind::Identifier),
+                    (syntax_kind, _) => syntax_kind.clone(),
+                };
+                CstElement::Token(CstToken {
+                    kind: Some(kind),
+                    text: String::from(text),
+                })
+            }
+            None => {
+                let (kind, _, parts) = part.expand().unwrap();
+                CstElement::Node(build(kind, a, b, parts, s))
+            }
+        });
+        cursor = b;
+    }
+    lex(&s[cursor..end], &mut children);
+    CstNode { kind, children }
+}
+
+/// splits the text between the AST tokens into whitespace, line breaks, comments, keywords and punctuation
+fn lex(mut s: &str, out: &mut Vec<CstElement>) {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let trivia = |kind, text: &str| {
+        CstElement::Trivia(Trivia {
+            kind,
+            text: String::from(text),
+        })
+    };
+
+    while let Some(c) = s.chars().next() {
+        let len = if s.starts_with("\r\n") {
+            out.push(trivia(TriviaKind::Newline, "\r\n"));
+            2
+        } else if c == '\n' || c == '\r' {
+            out.push(trivia(TriviaKind::Newline, &s[..1]));
+            1
+        } else if c.is_whitespace() {
+            let len = s
+                .find(|c: char| !c.is_whitespace() || c == '\n' || c == '\r')
+                .unwrap_or(s.len());
+            out.push(trivia(TriviaKind::Whitespace, &s[..len]));
+            len
+        } else if s.starts_with("//") {
+            let len = s.find(['\r', '\n']).unwrap_or(s.len());
+            out.push(trivia(TriviaKind::LineComment, &s[..len]));
+            len
+        } else if s.starts_with("/*") {
+            let len = s.find("*/").map_or(s.len(), |i| i + 2);
+            out.push(trivia(TriviaKind::BlockComment, &s[..len]));
+            len
+        } else if c == '{' && !s.starts_with("{$") {
+            let len = s.find('}').map_or(s.len(), |i| i + 1);
+            out.push(trivia(TriviaKind::BlockComment, &s[..len]));
+            len
+        } else if is_word(c) {
+            let len = s.find(|c| !is_word(c)).unwrap_or(s.len());
+            let text = &s[..len];
+            out.push(CstElement::Token(CstToken {
+                kind: keyword(text),
+                text: String::from(text),
+            }));
+            len
+        } else {
+            let len = c.len_utf8();
+            out.push(CstElement::Token(CstToken {
+                kind: None,
+                text: String::from(&s[..len]),
+            }));
+            len
+        };
+        s = &s[len..];
+    }
+}
+
+/// reserved words of the language, including the statement keywords and the built-in types
+fn keyword(text: &str) -> Option<SyntaxKind> {
+    Some(match text.to_ascii_lowercase().as_str() {
+        "and" => SyntaxKind::KeywordAnd,
+        "or" => SyntaxKind::KeywordOr,
+        "not" => SyntaxKind::KeywordNot,
+        "end" => SyntaxKind::KeywordEnd,
+        "then" => SyntaxKind::KeywordThen,
+        "if" => SyntaxKind::KeywordIf,
+        "else" => SyntaxKind::KeywordElse,
+        "while" => SyntaxKind::KeywordWhile,
+        "for" => SyntaxKind::KeywordFor,
+        "to" => SyntaxKind::KeywordTo,
+        "downto" => SyntaxKind::KeywordDownto,
+        "step" => SyntaxKind::KeywordStep,
+        "switch" => SyntaxKind::KeywordSwitch,
+        "case" => SyntaxKind::KeywordCase,
+        "default" => SyntaxKind::KeywordDefault,
+        "function" => SyntaxKind::KeywordFunction,
+        "return" => SyntaxKind::KeywordReturn,
+        "break" => SyntaxKind::KeywordBreak,
+        "continue" => SyntaxKind::KeywordContinue,
+        "const" => SyntaxKind::KeywordConst,
+        "define" => SyntaxKind::KeywordDefine,
+        "int" => SyntaxKind::KeywordInt,
+        "float" => SyntaxKind::KeywordFloat,
+        "string" => SyntaxKind::KeywordString,
+        "longstring" => SyntaxKind::KeywordLongString,
+        "optional" => SyntaxKind::KeywordOptional,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_lossless;
+
+    #[test]
+    fn test_round_trip() {
+        let s = "  // header\r\nCONST Max = 10 {inline} END\r\n\
+            int X, y[2] = 1\n\
+            IF 0@ > Max AND not $Var == 1 THEN /* block */\n\
+            \tWait 0 // wait\n\
+            ELSE\n    x[1] = 0@(1@,2i) + -1.5\nEND\n\
+            function Foo(a: int, ...rest: int): optional int\n  return 1\nend\n\
+            /* multi-line\n comment */\n\
+            ??? unparsed line\n\
+            :Label\n\
+            s$a = 'text' // tail";
+        let (cst, errors) = parse_lossless(s);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(cst.to_string(), s);
+        assert_eq!(cst.kind, SyntaxKind::SourceFile);
+
+        let (cst, errors) = parse_lossless("");
+        assert!(errors.is_empty());
+        assert_eq!(cst.to_string(), "");
+    }
+
+    #[test]
+    fn test_structure() {
+        let s = "if 0@ > 1 // check\nthen\n  wait 0\nend";
+        let (cst, _) = parse_lossless(s);
+        let CstElement::Node(statement) = &cst.children[0] else {
+            panic!("expected node")
+        };
+        assert_eq!(statement.kind, SyntaxKind::IfStatement);
+        let CstElement::Node(conditions) = &statement.children[1] else {
+            panic!("expected node")
+        };
+        assert_eq!(conditions.kind, SyntaxKind::Conditions);
+        assert!(conditions.children.iter().any(|c| matches!(
+            c,
+            CstElement::Trivia(Trivia { kind: TriviaKind::LineComment, text }) if text == "// check"
+        )));
+
+        let kinds = cst
+            .tokens()
+            .iter()
+            .map(|t| (t.kind.clone(), t.text.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                (Some(SyntaxKind::KeywordIf), "if"),
+                (Some(SyntaxKind::IntegerLiteral), "0"),
+                (None, "@"),
+                (Some(SyntaxKind::OperatorGreater), ">"),
+                (Some(SyntaxKind::IntegerLiteral), "1"),
+                (Some(SyntaxKind::KeywordThen), "then"),
+                (Some(SyntaxKind::Identifier), "wait"),
+                (Some(SyntaxKind::IntegerLiteral), "0"),
+                (Some(SyntaxKind::KeywordEnd), "end"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keywords() {
+        let s = "const A = 1 end\nint x\nfor 0@ = 1 to 10 step 2\nend\n\
+            switch 0@\ncase 1\nwait 0\ndefault\nwait 1\nend\n\
+            function Foo(a: float): optional int\nreturn 1\nend";
+        let (cst, errors) = parse_lossless(s);
+        assert_eq!(errors, vec![]);
+        let keywords = cst
+            .tokens()
+            .iter()
+            .filter(|t| t.kind.as_ref().is_some_and(SyntaxKind::is_keyword))
+            .map(|t| t.text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keywords,
+            vec![
+                "const", "end", "int", "for", "to", "step", "end", "switch", "case", "default",
+                "end", "function", "float", "optional", "int", "return", "end"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rename() {
+        let s = "int Count = 0\n{ keep } count += 1 // Count\nwhile COUNT < 10\nend";
+        let (mut cst, _) = parse_lossless(s);
+        cst.tokens_mut()
+            .into_iter()
+            .filter(|t| t.kind == Some(SyntaxKind::Identifier))
+            .filter(|t| t.text.eq_ignore_ascii_case("count"))
+            .for_each(|t| t.text = String::from("total"));
+        assert_eq!(
+            cst.to_string(),
+            "int total = 0\n{ keep } total += 1 // Count\nwhile total < 10\nend"
+        );
+    }
+}
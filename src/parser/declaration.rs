@@ -3,17 +3,20 @@ use nom::bytes::complete::{tag, tag_no_case};
 use nom::character::complete::{one_of, space0, space1};
 use nom::combinator::{map, opt};
 use nom::multi::{many1, separated_list0, separated_list1};
+use nom::sequence::terminated;
 use nom::sequence::{delimited, pair, preceded, separated_pair, tuple};
 use nom::{branch::alt, combinator::consumed};
-use nom::{character::complete::multispace0, sequence::terminated};
 
-use crate::parser::expression;
+use crate::parser::binary;
 use crate::parser::helpers;
 use crate::parser::literal;
 use crate::parser::statement;
 
 pub fn declaration(s: Span) -> R<Node> {
-    terminated(alt((statement::statement, const_declaration)), multispace0)(s)
+    terminated(
+        alt((const_declaration, statement::statement)),
+        helpers::trivia,
+    )(s)
 }
 
 pub fn function_signature(s: Span) -> R<FunctionSignature> {
@@ -62,11 +65,10 @@ fn function_arguments(s: Span) -> R<Vec<FunctionParameter>> {
             separated_list0(
                 helpers::ws(tag(",")),
                 consumed(tuple((
-                    opt(delimited(
+                    opt(pair(
                         helpers::ws(opt(tag("..."))),
                         // param names are optional in define function
-                        helpers::ws(literal::identifier),
-                        helpers::ws(tag(":")),
+                        terminated(helpers::ws(literal::identifier), helpers::ws(tag(":"))),
                     )),
                     helpers::ws(literal::identifier),
                     opt(delimited(
@@ -81,7 +83,8 @@ fn function_arguments(s: Span) -> R<Vec<FunctionParameter>> {
         |args| {
             args.into_iter()
                 .map(|(span, (name, _type, size))| FunctionParameter {
-                    name,
+                    is_variadic: name.as_ref().is_some_and(|(dots, _)| dots.is_some()),
+                    name: name.map(|(_, name)| name),
                     _type,
                     size,
                     token: Token::from(span, SyntaxKind::LocalVariable),
@@ -98,12 +101,13 @@ fn function_return_types(s: Span) -> R<Vec<FunctionReturnType>> {
             opt(tag_no_case("optional")),
             separated_list1(helpers::ws(tag(",")), helpers::ws(literal::identifier)),
         )),
-        |(_, _, types)| {
+        |(_, optional, types)| {
             types
                 .into_iter()
                 .map(|_type| FunctionReturnType {
                     token: _type.clone(),
                     _type,
+                    is_optional: optional.is_some(),
                 })
                 .collect()
         },
@@ -136,7 +140,7 @@ pub fn const_declaration(s: Span) -> R<Node> {
                 many1(helpers::line(consumed(separated_pair(
                     literal::identifier,
                     helpers::ws(tag("=")),
-                    expression::expression,
+                    binary::equality,
                 )))),
                 |v: Vec<(Span, (Token, Node))>| {
                     v.into_iter()
@@ -163,7 +167,7 @@ pub fn const_declaration(s: Span) -> R<Node> {
 mod tests {
     use super::*;
 
-    // #[test]
+    #[test]
     fn test_const_declaration() {
         let (_, node) = const_declaration(Span::from(
             r#"
@@ -179,34 +183,34 @@ end"#,
                 items: vec![
                     ConstInitialization {
                         name: Token {
-                            start: 5,
+                            start: 12,
                             len: 1,
                             syntax_kind: SyntaxKind::Identifier
                         },
                         value: Box::new(Node::Literal(Token {
-                            start: 9,
+                            start: 16,
                             len: 1,
                             syntax_kind: SyntaxKind::IntegerLiteral
                         })),
                         token: Token {
-                            start: 5,
+                            start: 12,
                             len: 5,
                             syntax_kind: SyntaxKind::ConstInitialization
                         }
                     },
                     ConstInitialization {
                         name: Token {
-                            start: 5,
+                            start: 22,
                             len: 1,
                             syntax_kind: SyntaxKind::Identifier
                         },
                         value: Box::new(Node::Literal(Token {
-                            start: 9,
+                            start: 26,
                             len: 3,
                             syntax_kind: SyntaxKind::FloatLiteral
                         })),
                         token: Token {
-                            start: 5,
+                            start: 22,
                             len: 7,
                             syntax_kind: SyntaxKind::ConstInitialization
                         }
@@ -257,6 +261,7 @@ end"#,
                 },
                 parameters: vec![],
                 return_types: vec![FunctionReturnType {
+                    is_optional: false,
                     token: Token {
                         start: 15,
                         len: 6,
@@ -312,6 +317,7 @@ end"#,
                 },
                 parameters: vec![],
                 return_types: vec![FunctionReturnType {
+                    is_optional: false,
                     token: Token {
                         start: 17,
                         len: 3,
@@ -355,6 +361,7 @@ end"#,
                         syntax_kind: SyntaxKind::Identifier
                     },
                     size: None,
+                    is_variadic: false,
                     token: Token {
                         start: 14,
                         len: 6,
@@ -362,6 +369,7 @@ end"#,
                     }
                 }],
                 return_types: vec![FunctionReturnType {
+                    is_optional: false,
                     token: Token {
                         start: 23,
                         len: 3,
@@ -407,6 +415,7 @@ end"#,
                             syntax_kind: SyntaxKind::Identifier
                         },
                         size: None,
+                        is_variadic: false,
                         token: Token {
                             start: 14,
                             len: 6,
@@ -425,6 +434,7 @@ end"#,
                             syntax_kind: SyntaxKind::Identifier
                         },
                         size: None,
+                        is_variadic: false,
                         token: Token {
                             start: 22,
                             len: 9,
@@ -433,6 +443,7 @@ end"#,
                     }
                 ],
                 return_types: vec![FunctionReturnType {
+                    is_optional: false,
                     token: Token {
                         start: 34,
                         len: 3,
@@ -480,6 +491,7 @@ end"#,
                             syntax_kind: SyntaxKind::Identifier
                         },
                         size: None,
+                        is_variadic: false,
                         token: Token {
                             start: 14,
                             len: 6,
@@ -498,6 +510,7 @@ end"#,
                             syntax_kind: SyntaxKind::Identifier
                         },
                         size: None,
+                        is_variadic: false,
                         token: Token {
                             start: 22,
                             len: 9,
@@ -507,6 +520,7 @@ end"#,
                 ],
                 return_types: vec![
                     FunctionReturnType {
+                        is_optional: false,
                         token: Token {
                             start: 34,
                             len: 3,
@@ -519,6 +533,7 @@ end"#,
                         }
                     },
                     FunctionReturnType {
+                        is_optional: false,
                         token: Token {
                             start: 39,
                             len: 3,
@@ -531,6 +546,7 @@ end"#,
                         }
                     },
                     FunctionReturnType {
+                        is_optional: false,
                         token: Token {
                             start: 44,
                             len: 3,
@@ -601,4 +617,23 @@ end"#,
             }
         );
     }
+
+    #[test]
+    fn test_function_optional_variadic() {
+        let (_, node) = function_signature(Span::from(
+            "function foo(a: int, ...rest: int): optional int, float",
+        ))
+        .unwrap();
+        assert_eq!(node.parameters.len(), 2);
+        assert!(!node.parameters[0].is_variadic);
+        assert!(node.parameters[1].is_variadic);
+        assert!(node.is_variadic());
+        assert_eq!(node.return_types.len(), 2);
+        assert!(node.return_types.iter().all(|t| t.is_optional));
+        assert!(node.has_optional_return());
+
+        let (_, node) = function_signature(Span::from("function foo(a: int): int")).unwrap();
+        assert!(!node.is_variadic());
+        assert!(!node.has_optional_return());
+    }
 }
@@ -0,0 +1,124 @@
+use nom::error::ErrorKind;
+
+use crate::parser::interface::*;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+    /// 0-based offset of the failed input in the source text
+    pub offset: usize,
+    /// 1-based line number
+    pub line: u32,
+    /// 1-based column
+    pub column: usize,
+    /// token kinds that would be accepted at this location
+    pub expected: Vec<SyntaxKind>,
+    /// what was found instead (e.g. `+` or end of line)
+    pub found: String,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(input: Span, expected: Vec<SyntaxKind>) -> Self {
+        let fragment = input.fragment();
+        let found = match fragment
+            .lines()
+            .next()
+            .and_then(|l| l.split_whitespace().next())
+        {
+            Some(word) => format!("`{word}`"),
+            None if fragment.is_empty() => String::from("end of input"),
+            None => String::from("end of line"),
+        };
+        Self {
+            offset: input.location_offset(),
+            line: input.location_line(),
+            column: input.get_column(),
+            message: format_message(&found, &expected),
+            expected,
+            found,
+        }
+    }
+}
+
+fn format_message(found: &str, expected: &[SyntaxKind]) -> String {
+    if expected.is_empty() {
+        return format!("unexpected {found}");
+    }
+    let expected = expected
+        .iter()
+        .map(describe)
+        .collect::<Vec<_>>()
+        .join(" or ");
+    format!("expected {expected}, found {found}")
+}
+
+fn describe(kind: &SyntaxKind) -> &'static str {
+    match kind {
+        SyntaxKind::Identifier => "identifier",
+        SyntaxKind::IntegerLiteral => "integer",
+        SyntaxKind::FloatLiteral => "float",
+        SyntaxKind::LabelLiteral => "label",
+        SyntaxKind::StringLiteral => "string",
+        SyntaxKind::LocalVariable => "local variable",
+        SyntaxKind::GlobalVariable => "global variable",
+        SyntaxKind::KeywordEnd => "`end`",
+        SyntaxKind::KeywordThen => "`then`",
+        _ => "token",
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl<'a> nom::error::ParseError<Span<'a>> for ParseError {
+    fn from_error_kind(input: Span<'a>, _kind: ErrorKind) -> Self {
+        Self::new(input, vec![])
+    }
+
+    fn append(_input: Span<'a>, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    fn or(self, other: Self) -> Self {
+        // report the error that made it the furthest
+        if self.offset != other.offset {
+            return if self.offset > other.offset {
+                self
+            } else {
+                other
+            };
+        }
+        let mut expected = self.expected;
+        for kind in other.expected {
+            if !expected.contains(&kind) {
+                expected.push(kind);
+            }
+        }
+        Self {
+            message: format_message(&other.found, &expected),
+            expected,
+            ..other
+        }
+    }
+}
+
+/// replaces the error with the expected token kinds if the parser failed without consuming any input
+pub fn expect<'a, O, F>(
+    expected: &'static [SyntaxKind],
+    mut f: F,
+) -> impl FnMut(Span<'a>) -> R<'a, O>
+where
+    F: FnMut(Span<'a>) -> R<'a, O>,
+{
+    move |s: Span<'a>| match f(s) {
+        Err(nom::Err::Error(e)) if e.offset == s.location_offset() => {
+            Err(nom::Err::Error(ParseError::new(s, expected.to_vec())))
+        }
+        r => r,
+    }
+}
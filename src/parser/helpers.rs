@@ -1,16 +1,20 @@
+use nom::bytes::complete::tag_no_case;
 use nom::bytes::complete::take_until;
+use nom::character::complete::alphanumeric1;
 use nom::character::complete::char;
+use nom::character::complete::not_line_ending;
 use nom::character::complete::space0;
 use nom::combinator::eof;
 use nom::combinator::not;
 use nom::combinator::peek;
 use nom::combinator::value;
+use nom::multi::many0;
 use nom::sequence::delimited;
 use nom::sequence::terminated;
 use nom::sequence::tuple;
 use nom::IResult;
-use nom::{branch::alt, character::streaming::multispace1};
-use nom::{bytes::complete::tag, character::complete::multispace0};
+use nom::{branch::alt, character::complete::multispace1};
+use nom::bytes::complete::tag;
 
 use crate::parser::interface::*;
 
@@ -36,12 +40,37 @@ where
     F: FnMut(Span<'a>) -> IResult<Span<'a>, O, E>,
 {
     delimited(
-        alt((inline_comment, value((), multispace0))),
+        trivia,
         inner,
         alt((inline_comment, value((), alt((eof, multispace1))))),
     )
 }
 
+/** any amount of whitespace, line breaks and comments */
+pub fn trivia<'a, E: nom::error::ParseError<Span<'a>>>(s: Span<'a>) -> IResult<Span<'a>, (), E> {
+    value(
+        (),
+        many0(alt((inline_comment, line_comment, value((), multispace1)))),
+    )(s)
+}
+
+/** line comment */
+pub fn line_comment<'a, E: nom::error::ParseError<Span<'a>>>(
+    s: Span<'a>,
+) -> IResult<Span<'a>, (), E> {
+    value((), tuple((tag("//"), not_line_ending)))(s)
+}
+
+/** case-insensitive keyword not followed by an identifier character */
+pub fn keyword<'a, E: nom::error::ParseError<Span<'a>>>(
+    k: &'static str,
+) -> impl FnMut(Span<'a>) -> IResult<Span<'a>, Span<'a>, E> {
+    terminated(
+        tag_no_case(k),
+        not(peek(alt((alphanumeric1, tag("_"))))),
+    )
+}
+
 /** inline comment */
 pub fn inline_comment<'a, E: nom::error::ParseError<Span<'a>>>(
     s: Span<'a>,
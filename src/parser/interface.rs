@@ -1,11 +1,14 @@
 use nom::IResult;
 use nom_locate::LocatedSpan;
+
+use crate::parser::error::ParseError;
 #[derive(Debug, PartialEq, Clone)]
 pub enum SyntaxKind {
     Identifier,
     IntegerLiteral,
     FloatLiteral,
     LabelLiteral,
+    StringLiteral,
     ArrayElementSCR,
     IndexedVariable,
     LocalVariable,
@@ -16,6 +19,52 @@ pub enum SyntaxKind {
     ConstDeclaration,
     ConstInitialization,
     FunctionSignature,
+    FunctionDeclaration,
+    VariableDeclaration,
+    VariableInitialization,
+    LabelDeclaration,
+    CommandCall,
+    OpcodeLiteral,
+    Condition,
+    Conditions,
+    IfStatement,
+    WhileStatement,
+    ForStatement,
+    SwitchStatement,
+    SwitchCase,
+    MemberExpr,
+    CallExpr,
+    TupleExpr,
+    FunctionParameter,
+    /// root of the lossless syntax tree
+    SourceFile,
+
+    KeywordAnd,        // and
+    KeywordOr,         // or
+    KeywordNot,        // not
+    KeywordEnd,        // end
+    KeywordThen,       // then
+    KeywordIf,         // if
+    KeywordElse,       // else
+    KeywordWhile,      // while
+    KeywordFor,        // for
+    KeywordTo,         // to
+    KeywordDownto,     // downto
+    KeywordStep,       // step
+    KeywordSwitch,     // switch
+    KeywordCase,       // case
+    KeywordDefault,    // default
+    KeywordFunction,   // function
+    KeywordReturn,     // return
+    KeywordBreak,      // break
+    KeywordContinue,   // continue
+    KeywordConst,      // const
+    KeywordDefine,     // define
+    KeywordInt,        // int
+    KeywordFloat,      // float
+    KeywordString,     // string
+    KeywordLongString, // longstring
+    KeywordOptional,   // optional
 
     OperatorBitwiseNot,            // ~
     OperatorBitwiseAnd,            // &
@@ -51,6 +100,40 @@ pub enum SyntaxKind {
     OperatorCastEqual,             // =#
 }
 
+impl SyntaxKind {
+    pub fn is_keyword(&self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::KeywordAnd
+                | SyntaxKind::KeywordOr
+                | SyntaxKind::KeywordNot
+                | SyntaxKind::KeywordEnd
+                | SyntaxKind::KeywordThen
+                | SyntaxKind::KeywordIf
+                | SyntaxKind::KeywordElse
+                | SyntaxKind::KeywordWhile
+                | SyntaxKind::KeywordFor
+                | SyntaxKind::KeywordTo
+                | SyntaxKind::KeywordDownto
+                | SyntaxKind::KeywordStep
+                | SyntaxKind::KeywordSwitch
+                | SyntaxKind::KeywordCase
+                | SyntaxKind::KeywordDefault
+                | SyntaxKind::KeywordFunction
+                | SyntaxKind::KeywordReturn
+                | SyntaxKind::KeywordBreak
+                | SyntaxKind::KeywordContinue
+                | SyntaxKind::KeywordConst
+                | SyntaxKind::KeywordDefine
+                | SyntaxKind::KeywordInt
+                | SyntaxKind::KeywordFloat
+                | SyntaxKind::KeywordString
+                | SyntaxKind::KeywordLongString
+                | SyntaxKind::KeywordOptional
+        )
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Token {
     pub syntax_kind: SyntaxKind,
@@ -59,9 +142,11 @@ pub struct Token {
 }
 
 impl Token {
+    /// start is a 1-based offset of the token in the source text. it is not the column:
+    /// statements span several lines and a column alone can't locate a token on line 2 and after
     pub fn from(s: Span, syntax_kind: SyntaxKind) -> Token {
         Self {
-            start: s.get_column(),
+            start: s.location_offset() + 1,
             len: s.len(),
             syntax_kind,
         }
@@ -72,6 +157,8 @@ impl Token {
 pub enum Node {
     /// Integer or Float literal
     Literal(Token),
+    /// `'text'` or `"text"`
+    String(StringLiteral),
     /// Global or local variable or array element
     Variable(Variable),
     /// Binary expression
@@ -80,6 +167,28 @@ pub enum Node {
     Unary(UnaryPrefixExpr),
     ConstDeclaration(ConstDeclaration),
     FunctionSignature(FunctionSignature),
+    /// `function name(...) ... end`
+    FunctionDeclaration(FunctionDeclaration),
+    /// `int x, y = 1`
+    VariableDeclaration(VariableDeclaration),
+    /// `:label`
+    Label(Label),
+    /// `wait 0`, `0001: wait 0`
+    CommandCall(CommandCall),
+    /// `if ... then ... else ... end`
+    If(IfStatement),
+    /// `while ... end`
+    While(WhileStatement),
+    /// `for 0@ = 1 to 10 step 2 ... end`
+    For(ForStatement),
+    /// `switch 0@ case 1 ... default ... end`
+    Switch(SwitchStatement),
+    /// `Actor.Health`, `$car.Angle`
+    Member(MemberExpr),
+    /// `Actor.Health($a)`, `$car.SetMaxHealth(100)`
+    Call(CallExpr),
+    /// `0@, 1@` receiving the results of a function call (`0@, 1@ = MyFunc()`)
+    Tuple(TupleExpr),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -151,6 +260,36 @@ impl BinaryExpr {
     }
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub struct StringLiteral {
+    /// text between the quotes with escape sequences replaced
+    pub value: String,
+    /// `ShortString` for `'text'`, `LongString` for `"text"`
+    pub _type: VariableType,
+    pub token: Token,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MemberExpr {
+    /// class name or variable
+    pub object: Box<Node>,
+    pub member: Token,
+    pub token: Token,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CallExpr {
+    pub callee: Box<Node>,
+    pub arguments: Vec<Node>,
+    pub token: Token,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TupleExpr {
+    pub items: Vec<Node>,
+    pub token: Token,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct ArrayElementSCR {
     pub array_var: Box<Variable>,
@@ -198,6 +337,101 @@ pub enum VariableType {
     LongString,  // v
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub struct VariableDeclaration {
+    pub _type: VariableType,
+    pub items: Vec<VariableInitialization>,
+    pub token: Token,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct VariableInitialization {
+    pub name: Token,
+    /// array size (int x[10])
+    pub size: Option<Token>,
+    pub value: Option<Box<Node>>,
+    pub token: Token,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Label {
+    /// label name without the leading colon
+    pub name: Token,
+    pub token: Token,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CommandCall {
+    /// opcode prefix without the colon (0001)
+    pub opcode: Option<Token>,
+    pub name: Option<Token>,
+    pub arguments: Vec<Node>,
+    pub token: Token,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Condition {
+    pub not: Option<Token>,
+    pub expr: Box<Node>,
+    pub token: Token,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Conditions {
+    /// `and` or `or` keyword, none for a single condition
+    pub operator: Option<Token>,
+    pub items: Vec<Condition>,
+    pub token: Token,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct IfStatement {
+    pub conditions: Conditions,
+    pub body: Vec<Node>,
+    pub else_body: Option<Vec<Node>>,
+    pub token: Token,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct WhileStatement {
+    pub conditions: Conditions,
+    pub body: Vec<Node>,
+    pub token: Token,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ForStatement {
+    pub var: Box<Node>,
+    pub start: Box<Node>,
+    pub end: Box<Node>,
+    pub step: Option<Box<Node>>,
+    pub downto: bool,
+    pub body: Vec<Node>,
+    pub token: Token,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SwitchStatement {
+    pub subject: Box<Node>,
+    pub cases: Vec<SwitchCase>,
+    pub default: Option<Vec<Node>>,
+    pub token: Token,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SwitchCase {
+    pub values: Vec<Node>,
+    pub body: Vec<Node>,
+    pub token: Token,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FunctionDeclaration {
+    pub signature: FunctionSignature,
+    pub body: Vec<Node>,
+    pub token: Token,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct AST {
     pub body: Vec<Node>,
@@ -213,6 +447,18 @@ pub struct FunctionSignature {
     pub token: Token,
 }
 
+impl FunctionSignature {
+    /// the last parameter takes the rest of the arguments
+    pub fn is_variadic(&self) -> bool {
+        self.parameters.last().is_some_and(|p| p.is_variadic)
+    }
+
+    /// the function may return no values (`: optional int`)
+    pub fn has_optional_return(&self) -> bool {
+        self.return_types.iter().any(|t| t.is_optional)
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum FunctionCC {
     Local, 
@@ -225,13 +471,17 @@ pub struct FunctionParameter {
     pub name: Option<Token>,
     pub _type: Token,
     pub size: Option<Token>,
+    /// `...name: type` takes the rest of the arguments
+    pub is_variadic: bool,
     pub token: Token,
 }
 #[derive(Debug, PartialEq, Clone)]
 pub struct FunctionReturnType {
     pub _type: Token,
+    /// `: optional int`, the function may return no values
+    pub is_optional: bool,
     pub token: Token,
 }
 
 pub type Span<'a> = LocatedSpan<&'a str>;
-pub type R<'a, T> = IResult<Span<'a>, T>;
+pub type R<'a, T> = IResult<Span<'a>, T, ParseError>;
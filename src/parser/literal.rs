@@ -1,16 +1,20 @@
+use nom::bytes::complete::is_not;
 use nom::bytes::complete::tag;
 use nom::bytes::complete::tag_no_case;
 use nom::character::complete::alpha1;
 use nom::character::complete::alphanumeric1;
 use nom::character::complete::char;
 use nom::character::complete::digit1;
+use nom::character::complete::none_of;
 use nom::character::complete::one_of;
 use nom::combinator::consumed;
 use nom::combinator::map;
+use nom::combinator::map_opt;
 use nom::combinator::opt;
 use nom::combinator::recognize;
 use nom::multi::many0;
 use nom::multi::many1;
+use nom::sequence::delimited;
 use nom::sequence::pair;
 use nom::sequence::preceded;
 use nom::sequence::tuple;
@@ -74,6 +78,88 @@ pub fn label(s: Span) -> R<Token> {
     )(s)
 }
 
+// "text" is a long string, 'text' is a short one
+pub fn string(s: Span) -> R<StringLiteral> {
+    alt((
+        quoted('"', "\\\"\r\n", VariableType::LongString),
+        quoted('\'', "\\'\r\n", VariableType::ShortString),
+    ))(s)
+}
+
+fn quoted<'a>(
+    quote: char,
+    stop: &'static str,
+    _type: VariableType,
+) -> impl FnMut(Span<'a>) -> R<'a, StringLiteral> {
+    map_opt(
+        consumed(delimited(
+            char(quote),
+            recognize(many0(alt((
+                is_not(stop),
+                recognize(pair(char('\\'), none_of("\r\n"))),
+            )))),
+            char(quote),
+        )),
+        move |(span, body): (Span, Span)| {
+            Some(StringLiteral {
+                value: unescape(body.fragment())?,
+                _type: _type.clone(),
+                token: Token::from(span, SyntaxKind::StringLiteral),
+            })
+        },
+    )
+}
+
+/// replaces escape sequences the same way the preprocessor does:
+/// `\0`, `\b`, `\t`, `\n`, `\r`, `\xNN`, any other escaped character stands for itself
+pub fn unescape(s: &str) -> Option<String> {
+    let hex = |c: u8| (c as char).to_digit(16).map(|d| d as u8);
+    let mut buf = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(c) = bytes.next() {
+        if c != b'\\' {
+            buf.push(c);
+            continue;
+        }
+        match bytes.next()? {
+            b'0' => buf.push(0),
+            b'b' => buf.push(8),
+            b't' => buf.push(9),
+            b'n' => buf.push(10),
+            b'r' => buf.push(13),
+            b'x' => {
+                let a = hex(bytes.next()?)?;
+                let b = hex(bytes.next()?)?;
+                buf.push((a << 4) + b);
+            }
+            x => buf.push(x),
+        }
+    }
+    Some(String::from_utf8_lossy(&buf).to_string())
+}
+
+/// reverse of [unescape], `quote` is the quote character of the literal
+pub fn escape(s: &str, quote: char) -> String {
+    let mut result = String::new();
+    for c in s.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            _ if c == quote => {
+                result.push('\\');
+                result.push(c);
+            }
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            _ if (c as u32) < 0x20 || (c as u32) > 0x7E => {
+                result.push_str(&format!("\\x{:02X}", c as u32))
+            }
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
 pub fn float_span(s: Span) -> R<Span> {
     alt((
         // Case one: .42
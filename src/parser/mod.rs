@@ -1,12 +1,20 @@
+use nom::character::complete::line_ending;
+use nom::character::complete::not_line_ending;
 use nom::combinator::all_consuming;
 use nom::combinator::map;
+use nom::combinator::opt;
+use nom::combinator::value;
 use nom::multi::many1;
+use nom::sequence::terminated;
+use nom::sequence::tuple;
 
+pub mod cst;
 pub mod interface;
 pub use interface::*;
 
 mod binary;
 mod declaration;
+mod error;
 mod expression;
 mod helpers;
 mod literal;
@@ -16,11 +24,74 @@ mod unary;
 mod variable;
 
 pub use declaration::{function_signature, function_arguments_and_return_types}; // used in LanguageService
+pub use error::ParseError;
+pub use literal::escape;
 
 pub fn parse(s: &str) -> R<AST> {
     all_consuming(map(many1(declaration::declaration), |body| AST { body }))(Span::from(s))
 }
 
+/// parses a single expression, e.g. the value of a constant; unlike [parse],
+/// a lone identifier is read as a name and not as a command without arguments
+pub fn parse_expression(s: &str) -> R<Node> {
+    all_consuming(expression::expression)(Span::from(s))
+}
+
+/// parses the header of an `if` or `while` statement without the body
+pub fn parse_conditions(s: &str) -> R<Conditions> {
+    all_consuming(terminated(statement::conditions_header, helpers::trivia))(Span::from(s))
+}
+
+/// parses a single string literal (`'text'` or `"text"`)
+pub fn parse_string(s: &str) -> R<StringLiteral> {
+    all_consuming(literal::string)(Span::from(s))
+}
+
+/// parses the source as far as possible, skipping to the next line after each error
+pub fn parse_with_recovery(s: &str) -> (AST, Vec<ParseError>) {
+    let mut body = vec![];
+    let mut errors: Vec<ParseError> = vec![];
+    let Ok((mut input, _)) = helpers::trivia::<ParseError>(Span::from(s)) else {
+        return (AST { body }, errors);
+    };
+
+    while !input.fragment().is_empty() {
+        match declaration::declaration(input) {
+            Ok((rest, node)) => {
+                body.push(node);
+                input = rest;
+            }
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+                // continue after the line where the error happened
+                let Ok((at_error, _)) = nom::bytes::complete::take::<_, _, ParseError>(
+                    e.offset - input.location_offset(),
+                )(input) else {
+                    break;
+                };
+                let Ok((rest, _)) = skip_line(at_error) else {
+                    break;
+                };
+                errors.push(e);
+                input = rest;
+            }
+            Err(nom::Err::Incomplete(_)) => break,
+        }
+    }
+
+    (AST { body }, errors)
+}
+
+/// same as [parse_with_recovery], but keeps whitespace, comments and the original text of the tokens,
+/// the tree prints back to the source byte for byte
+pub fn parse_lossless(s: &str) -> (cst::CstNode, Vec<ParseError>) {
+    let (ast, errors) = parse_with_recovery(s);
+    (cst::from_ast(&ast, s), errors)
+}
+
+fn skip_line(s: Span) -> R<()> {
+    value((), tuple((not_line_ending, opt(line_ending), helpers::trivia)))(s)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -116,6 +187,55 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_error() {
+        let Err(nom::Err::Failure(e)) = parse("0@ = 1 +") else {
+            panic!("expected error")
+        };
+        assert_eq!(e.line, 1);
+        assert_eq!(e.column, 9);
+        assert_eq!(e.expected.len(), 6);
+        assert_eq!(
+            e.message,
+            "expected local variable or global variable or integer or float or string or identifier, found end of input"
+        );
+
+        let Err(nom::Err::Error(e)) = parse("0@ = 1 + )") else {
+            panic!("expected error")
+        };
+        assert_eq!(e.column, 8);
+        assert_eq!(e.message, "unexpected `+`");
+
+        let Err(nom::Err::Error(e)) = parse("if 0@ > 1\n  wait 0\n") else {
+            panic!("expected error")
+        };
+        assert_eq!(e.line, 3);
+        assert_eq!(e.expected, vec![SyntaxKind::KeywordEnd]);
+        assert_eq!(e.message, "expected `end`, found end of input");
+
+        let Err(nom::Err::Failure(e)) = parse("0@ = (") else {
+            panic!("expected error")
+        };
+        assert_eq!(e.column, 6);
+        assert_eq!(e.expected.len(), 6);
+        assert!(e.message.starts_with("expected local variable or global variable"));
+    }
+
+    #[test]
+    fn test_parse_with_recovery() {
+        let (ast, errors) = parse_with_recovery("0@ = 1\n0@ = (\nwait 0\n1@ = ]\n2@ = 2");
+        assert_eq!(ast.body.len(), 3);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].line, 2);
+        assert_eq!(errors[0].column, 6);
+        assert_eq!(errors[1].line, 4);
+        assert_eq!(errors[1].column, 6);
+
+        let (ast, errors) = parse_with_recovery("// comment\n0@ = 1");
+        assert_eq!(ast.body.len(), 1);
+        assert!(errors.is_empty());
+    }
+
     #[test]
     fn test_binary() {
         let (_, ast) = parse("0b101").unwrap();
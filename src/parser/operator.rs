@@ -44,7 +44,7 @@ pub fn equality(s: Span) -> R<Token> {
 }
 
 pub fn comparison(s: Span) -> R<Token> {
-    alt((op_greater_equal, op_greater, op_less, op_less_equal))(s)
+    alt((op_greater_equal, op_greater, op_less_equal, op_less))(s)
 }
 
 pub fn add_sub(s: Span) -> R<Token> {
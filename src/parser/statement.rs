@@ -1,7 +1,763 @@
-use crate::parser::interface::*;
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::bytes::complete::take_while_m_n;
+use nom::character::complete::char;
+use nom::character::complete::multispace1;
+use nom::character::complete::space0;
+use nom::character::complete::space1;
+use nom::combinator::consumed;
+use nom::combinator::eof;
+use nom::combinator::map;
+use nom::combinator::not;
+use nom::combinator::opt;
+use nom::combinator::peek;
+use nom::combinator::success;
+use nom::combinator::value;
+use nom::combinator::verify;
+use nom::multi::many0;
+use nom::multi::many1;
+use nom::multi::separated_list1;
+use nom::sequence::delimited;
+use nom::sequence::pair;
+use nom::sequence::preceded;
+use nom::sequence::terminated;
+use nom::sequence::tuple;
 
+use crate::parser::binary;
+use crate::parser::declaration;
+use crate::parser::error::expect;
 use crate::parser::expression;
+use crate::parser::helpers;
+use crate::parser::helpers::keyword;
+use crate::parser::interface::*;
+use crate::parser::literal;
+use crate::parser::unary;
 
 pub fn statement(s: Span) -> R<Node> {
-    expression::expression(s)
+    alt((
+        if_statement,
+        while_statement,
+        for_statement,
+        switch_statement,
+        function_declaration,
+        label,
+        variable_declaration,
+        preceded(
+            not(peek(preceded(helpers::trivia, reserved))),
+            alt((
+                map(helpers::line(command_call), Node::CommandCall),
+                expression::expression,
+            )),
+        ),
+    ))(s)
+}
+
+/** keywords that can't be used as a command name */
+fn reserved(s: Span) -> R<Span> {
+    alt((
+        keyword("if"),
+        keyword("while"),
+        keyword("for"),
+        keyword("switch"),
+        keyword("function"),
+        keyword("define"),
+        keyword("const"),
+        keyword("and"),
+        keyword("or"),
+        keyword("not"),
+        block_end,
+    ))(s)
+}
+
+/** end of the current line, optionally followed by a comment */
+fn eol(s: Span) -> R<()> {
+    preceded(
+        space0,
+        alt((
+            helpers::inline_comment,
+            helpers::line_comment,
+            value((), eof),
+            value((), multispace1),
+        )),
+    )(s)
+}
+
+/** keyword starting a new line */
+fn line_keyword<'a>(k: &'static str) -> impl FnMut(Span<'a>) -> R<'a, Span<'a>> {
+    preceded(helpers::trivia, keyword(k))
+}
+
+/** keywords that close or split a block body */
+fn block_end(s: Span) -> R<Span> {
+    alt((
+        keyword("end"),
+        keyword("else"),
+        keyword("case"),
+        keyword("default"),
+        keyword("then"),
+    ))(s)
+}
+
+/** statements until the block end keyword */
+fn body(s: Span) -> R<Vec<Node>> {
+    many0(preceded(
+        not(peek(preceded(helpers::trivia, block_end))),
+        declaration::declaration,
+    ))(s)
+}
+
+fn block_end_keyword(s: Span) -> R<()> {
+    value(
+        (),
+        terminated(
+            preceded(
+                helpers::trivia,
+                expect(&[SyntaxKind::KeywordEnd], keyword("end")),
+            ),
+            eol,
+        ),
+    )(s)
+}
+
+// :label
+fn label(s: Span) -> R<Node> {
+    map(
+        helpers::line(consumed(preceded(char(':'), literal::identifier_any))),
+        |(span, name)| {
+            Node::Label(Label {
+                name,
+                token: Token::from(span, SyntaxKind::LabelDeclaration),
+            })
+        },
+    )(s)
+}
+
+// int x, y[10], z = 1
+fn variable_declaration(s: Span) -> R<Node> {
+    map(
+        helpers::line(consumed(tuple((
+            terminated(
+                alt((
+                    value(VariableType::Int, keyword("int")),
+                    value(VariableType::Float, keyword("float")),
+                    value(VariableType::ShortString, keyword("string")),
+                    value(VariableType::LongString, keyword("longstring")),
+                )),
+                space1,
+            ),
+            separated_list1(
+                helpers::ws(tag(",")),
+                consumed(tuple((
+                    literal::identifier,
+                    opt(delimited(
+                        helpers::ws(tag("[")),
+                        helpers::ws(literal::number),
+                        helpers::ws(tag("]")),
+                    )),
+                    opt(preceded(helpers::ws(tag("=")), binary::equality)),
+                ))),
+            ),
+        )))),
+        |(span, (_type, items))| {
+            Node::VariableDeclaration(VariableDeclaration {
+                _type,
+                items: items
+                    .into_iter()
+                    .map(|(span, (name, size, value))| VariableInitialization {
+                        name,
+                        size,
+                        value: value.map(Box::new),
+                        token: Token::from(span, SyntaxKind::VariableInitialization),
+                    })
+                    .collect(),
+                token: Token::from(span, SyntaxKind::VariableDeclaration),
+            })
+        },
+    )(s)
+}
+
+fn opcode(s: Span) -> R<Token> {
+    map(take_while_m_n(4, 4, |c: char| c.is_ascii_hexdigit()), |s| {
+        Token::from(s, SyntaxKind::OpcodeLiteral)
+    })(s)
+}
+
+fn argument(s: Span) -> R<Node> {
+    preceded(
+        not(peek(alt((keyword("then"), keyword("and"), keyword("or"))))),
+        unary::unary,
+    )(s)
+}
+
+/** a command without an opcode must end here, otherwise its name starts an expression */
+fn command_end(s: Span) -> R<()> {
+    peek(alt((
+        eol,
+        value(
+            (),
+            preceded(
+                space1,
+                alt((keyword("then"), keyword("and"), keyword("or"))),
+            ),
+        ),
+    )))(s)
+}
+
+// wait 0
+// 0001: wait 0
+// 0001: 0
+// terminate_this_script
+fn command_call(s: Span) -> R<CommandCall> {
+    map(
+        consumed(alt((
+            tuple((
+                map(terminated(opcode, char(':')), Some),
+                opt(preceded(space0, literal::identifier)),
+                many0(preceded(space1, argument)),
+            )),
+            tuple((
+                success(None),
+                map(literal::identifier, Some),
+                terminated(many0(preceded(space1, argument)), command_end),
+            )),
+        ))),
+        |(span, (opcode, name, arguments))| CommandCall {
+            opcode,
+            name,
+            arguments,
+            token: Token::from(span, SyntaxKind::CommandCall),
+        },
+    )(s)
+}
+
+// [not] command or comparison
+fn condition(s: Span) -> R<Condition> {
+    map(
+        consumed(pair(
+            opt(terminated(
+                map(keyword("not"), |s| Token::from(s, SyntaxKind::KeywordNot)),
+                space1,
+            )),
+            preceded(
+                not(peek(reserved)),
+                alt((map(command_call, Node::CommandCall), binary::assignment)),
+            ),
+        )),
+        |(span, (not, expr))| Condition {
+            not,
+            expr: Box::new(expr),
+            token: Token::from(span, SyntaxKind::Condition),
+        },
+    )(s)
+}
+
+fn logical_operator(s: Span) -> R<Token> {
+    alt((
+        map(keyword("and"), |s| Token::from(s, SyntaxKind::KeywordAnd)),
+        map(keyword("or"), |s| Token::from(s, SyntaxKind::KeywordOr)),
+    ))(s)
+}
+
+fn map_conditions(span: Span, operator: Option<Token>, items: Vec<Condition>) -> Conditions {
+    Conditions {
+        operator,
+        items,
+        token: Token::from(span, SyntaxKind::Conditions),
+    }
+}
+
+// if and
+//   cond1
+//   cond2
+// then
+fn conditions_multiline(s: Span) -> R<Conditions> {
+    alt((
+        map(
+            consumed(tuple((
+                helpers::ws(logical_operator),
+                eol,
+                many1(preceded(
+                    not(peek(line_keyword("then"))),
+                    helpers::line(condition),
+                )),
+                preceded(
+                    helpers::trivia,
+                    expect(&[SyntaxKind::KeywordThen], keyword("then")),
+                ),
+                eol,
+            ))),
+            |(span, (operator, _, items, _, _))| map_conditions(span, Some(operator), items),
+        ),
+        map(
+            consumed(tuple((
+                eol,
+                helpers::line(condition),
+                opt(terminated(line_keyword("then"), eol)),
+            ))),
+            |(span, (_, item, _))| map_conditions(span, None, vec![item]),
+        ),
+    ))(s)
+}
+
+// if cond1 and cond2 then
+fn conditions_inline(s: Span) -> R<Conditions> {
+    map(
+        consumed(tuple((
+            preceded(space1, condition),
+            verify(
+                many0(pair(helpers::ws(logical_operator), condition)),
+                |items: &Vec<(Token, Condition)>| {
+                    // and/or can't be mixed in one statement
+                    items
+                        .windows(2)
+                        .all(|w| w[0].0.syntax_kind == w[1].0.syntax_kind)
+                },
+            ),
+            opt(preceded(helpers::trivia, keyword("then"))),
+            eol,
+        ))),
+        |(span, (first, rest, _, _))| {
+            let operator = rest.first().map(|(op, _)| op.clone());
+            let mut items = vec![first];
+            items.extend(rest.into_iter().map(|(_, item)| item));
+            map_conditions(span, operator, items)
+        },
+    )(s)
+}
+
+fn conditions(s: Span) -> R<Conditions> {
+    alt((conditions_multiline, conditions_inline))(s)
+}
+
+// if cond1 and cond2 [then]
+// while cond1 and cond2
+pub fn conditions_header(s: Span) -> R<Conditions> {
+    preceded(
+        helpers::trivia,
+        preceded(alt((keyword("if"), keyword("while"))), conditions),
+    )(s)
+}
+
+fn if_statement(s: Span) -> R<Node> {
+    preceded(
+        helpers::trivia,
+        map(
+            consumed(tuple((
+                keyword("if"),
+                conditions,
+                body,
+                opt(preceded(terminated(line_keyword("else"), eol), body)),
+                block_end_keyword,
+            ))),
+            |(span, (_, conditions, body, else_body, _))| {
+                Node::If(IfStatement {
+                    conditions,
+                    body,
+                    else_body,
+                    token: Token::from(span, SyntaxKind::IfStatement),
+                })
+            },
+        ),
+    )(s)
+}
+
+fn while_statement(s: Span) -> R<Node> {
+    preceded(
+        helpers::trivia,
+        map(
+            consumed(tuple((
+                keyword("while"),
+                conditions,
+                body,
+                block_end_keyword,
+            ))),
+            |(span, (_, conditions, body, _))| {
+                Node::While(WhileStatement {
+                    conditions,
+                    body,
+                    token: Token::from(span, SyntaxKind::WhileStatement),
+                })
+            },
+        ),
+    )(s)
+}
+
+fn for_statement(s: Span) -> R<Node> {
+    preceded(
+        helpers::trivia,
+        map(
+            consumed(tuple((
+                terminated(keyword("for"), space1),
+                unary::unary,
+                helpers::ws(tag("=")),
+                unary::unary,
+                helpers::ws(alt((
+                    value(false, keyword("to")),
+                    value(true, keyword("downto")),
+                ))),
+                unary::unary,
+                opt(preceded(helpers::ws(keyword("step")), unary::unary)),
+                eol,
+                body,
+                block_end_keyword,
+            ))),
+            |(span, (_, var, _, start, downto, end, step, _, body, _))| {
+                Node::For(ForStatement {
+                    var: Box::new(var),
+                    start: Box::new(start),
+                    end: Box::new(end),
+                    step: step.map(Box::new),
+                    downto,
+                    body,
+                    token: Token::from(span, SyntaxKind::ForStatement),
+                })
+            },
+        ),
+    )(s)
+}
+
+fn switch_case(s: Span) -> R<SwitchCase> {
+    preceded(
+        helpers::trivia,
+        map(
+            consumed(tuple((
+                terminated(keyword("case"), space1),
+                separated_list1(helpers::ws(tag(",")), unary::unary),
+                eol,
+                body,
+            ))),
+            |(span, (_, values, _, body))| SwitchCase {
+                values,
+                body,
+                token: Token::from(span, SyntaxKind::SwitchCase),
+            },
+        ),
+    )(s)
+}
+
+fn switch_statement(s: Span) -> R<Node> {
+    preceded(
+        helpers::trivia,
+        map(
+            consumed(tuple((
+                terminated(keyword("switch"), space1),
+                unary::unary,
+                eol,
+                many0(switch_case),
+                opt(preceded(terminated(line_keyword("default"), eol), body)),
+                block_end_keyword,
+            ))),
+            |(span, (_, subject, _, cases, default, _))| {
+                Node::Switch(SwitchStatement {
+                    subject: Box::new(subject),
+                    cases,
+                    default,
+                    token: Token::from(span, SyntaxKind::SwitchStatement),
+                })
+            },
+        ),
+    )(s)
+}
+
+fn function_declaration(s: Span) -> R<Node> {
+    preceded(
+        helpers::trivia,
+        alt((
+            // forward declaration
+            map(
+                preceded(
+                    terminated(keyword("define"), space1),
+                    declaration::function_signature,
+                ),
+                Node::FunctionSignature,
+            ),
+            // foreign functions have no body
+            map(
+                verify(declaration::function_signature, |signature| {
+                    signature.cc != FunctionCC::Local
+                }),
+                Node::FunctionSignature,
+            ),
+            map(
+                consumed(pair(
+                    declaration::function_signature,
+                    terminated(body, block_end_keyword),
+                )),
+                |(span, (signature, body))| {
+                    Node::FunctionDeclaration(FunctionDeclaration {
+                        signature,
+                        body,
+                        token: Token::from(span, SyntaxKind::FunctionDeclaration),
+                    })
+                },
+            ),
+        )),
+    )(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::interface::*;
+    use crate::parser::parse;
+
+    fn text<'a>(s: &'a str, token: &Token) -> &'a str {
+        &s[token.start - 1..token.start - 1 + token.len]
+    }
+
+    #[test]
+    fn test_label() {
+        let s = ":label_1\n0@ = 1";
+        let (_, ast) = parse(s).unwrap();
+        assert_eq!(ast.body.len(), 2);
+        let Node::Label(label) = &ast.body[0] else {
+            panic!("expected label")
+        };
+        assert_eq!(text(s, &label.name), "label_1");
+        assert_eq!(text(s, &label.token), ":label_1");
+        assert!(matches!(ast.body[1], Node::Binary(_)));
+    }
+
+    #[test]
+    fn test_variable_declaration() {
+        let s = "int x, y[10], z = 5\nfloat f = -1.0";
+        let (_, ast) = parse(s).unwrap();
+        let Node::VariableDeclaration(decl) = &ast.body[0] else {
+            panic!("expected declaration")
+        };
+        assert_eq!(decl._type, VariableType::Int);
+        assert_eq!(decl.items.len(), 3);
+        assert_eq!(text(s, &decl.items[0].name), "x");
+        assert_eq!(text(s, decl.items[1].size.as_ref().unwrap()), "10");
+        assert!(decl.items[2].value.is_some());
+
+        let Node::VariableDeclaration(decl) = &ast.body[1] else {
+            panic!("expected declaration")
+        };
+        assert_eq!(decl._type, VariableType::Float);
+        assert!(matches!(
+            decl.items[0].value.as_deref(),
+            Some(Node::Unary(_))
+        ));
+
+        // not a declaration
+        let (_, ast) = parse("integer = 1").unwrap();
+        assert!(matches!(ast.body[0], Node::Binary(_)));
+    }
+
+    #[test]
+    fn test_command_call() {
+        let s = "wait 0\n0001: wait 100\n0002: @label\nset_char_health $player -10 1@(2@,3i)";
+        let (_, ast) = parse(s).unwrap();
+        assert_eq!(ast.body.len(), 4);
+
+        let Node::CommandCall(call) = &ast.body[0] else {
+            panic!("expected command")
+        };
+        assert_eq!(text(s, call.name.as_ref().unwrap()), "wait");
+        assert!(call.opcode.is_none());
+        assert_eq!(call.arguments.len(), 1);
+
+        let Node::CommandCall(call) = &ast.body[1] else {
+            panic!("expected command")
+        };
+        assert_eq!(text(s, call.opcode.as_ref().unwrap()), "0001");
+        assert_eq!(text(s, call.name.as_ref().unwrap()), "wait");
+
+        let Node::CommandCall(call) = &ast.body[2] else {
+            panic!("expected command")
+        };
+        assert!(call.name.is_none());
+        assert!(matches!(
+            &call.arguments[0],
+            Node::Literal(Token {
+                syntax_kind: SyntaxKind::LabelLiteral,
+                ..
+            })
+        ));
+
+        let Node::CommandCall(call) = &ast.body[3] else {
+            panic!("expected command")
+        };
+        assert_eq!(call.arguments.len(), 3);
+        assert!(matches!(&call.arguments[1], Node::Unary(_)));
+        assert!(matches!(
+            &call.arguments[2],
+            Node::Variable(Variable::ArrayElement(_))
+        ));
+
+        // binary expressions are not commands
+        let (_, ast) = parse("x - 1").unwrap();
+        assert!(matches!(ast.body[0], Node::Binary(_)));
+
+        let s = "terminate_this_script\nif is_player_playing $player and player_made_progress\nend";
+        let (_, ast) = parse(s).unwrap();
+        let Node::CommandCall(call) = &ast.body[0] else {
+            panic!("expected command")
+        };
+        assert_eq!(
+            text(s, call.name.as_ref().unwrap()),
+            "terminate_this_script"
+        );
+        assert!(call.arguments.is_empty());
+        let Node::If(stmt) = &ast.body[1] else {
+            panic!("expected if")
+        };
+        let Node::CommandCall(call) = stmt.conditions.items[1].expr.as_ref() else {
+            panic!("expected command")
+        };
+        assert!(call.arguments.is_empty());
+
+        // neither are calls and comparisons
+        let (_, ast) = parse("MyFunc()").unwrap();
+        assert!(!matches!(ast.body[0], Node::CommandCall(_)));
+        let (_, ast) = parse("if x == 1\nend").unwrap();
+        let Node::If(stmt) = &ast.body[0] else {
+            panic!("expected if")
+        };
+        assert!(matches!(
+            stmt.conditions.items[0].expr.as_ref(),
+            Node::Binary(_)
+        ));
+    }
+
+    #[test]
+    fn test_if_inline() {
+        let s = "if 0@ > 1 and not is_char_dead $p then\n  wait 0\nelse\n  0@ = 1\nend";
+        let (_, ast) = parse(s).unwrap();
+        assert_eq!(ast.body.len(), 1);
+        let Node::If(stmt) = &ast.body[0] else {
+            panic!("expected if")
+        };
+        assert_eq!(
+            stmt.conditions.operator.as_ref().unwrap().syntax_kind,
+            SyntaxKind::KeywordAnd
+        );
+        assert_eq!(stmt.conditions.items.len(), 2);
+        assert!(stmt.conditions.items[0].not.is_none());
+        assert!(stmt.conditions.items[1].not.is_some());
+        assert!(matches!(
+            stmt.conditions.items[1].expr.as_ref(),
+            Node::CommandCall(_)
+        ));
+        assert_eq!(stmt.body.len(), 1);
+        assert_eq!(stmt.else_body.as_ref().unwrap().len(), 1);
+        assert_eq!(text(s, &stmt.token), s);
+
+        // and/or can't be mixed
+        assert!(parse("if 0@ > 1 and 1@ > 1 or 2@ > 1\nend").is_err());
+    }
+
+    #[test]
+    fn test_if_multiline() {
+        let s = r#"
+if or
+    0@ == 1 // comment
+    1@ <= 2
+then
+    wait 0
+end
+
+if
+    0@ <> 1
+    1@ = 2
+end"#;
+        let (_, ast) = parse(s).unwrap();
+        assert_eq!(ast.body.len(), 2);
+        let Node::If(stmt) = &ast.body[0] else {
+            panic!("expected if")
+        };
+        assert_eq!(
+            stmt.conditions.operator.as_ref().unwrap().syntax_kind,
+            SyntaxKind::KeywordOr
+        );
+        assert_eq!(stmt.conditions.items.len(), 2);
+        assert_eq!(stmt.body.len(), 1);
+        assert!(stmt.else_body.is_none());
+
+        let Node::If(stmt) = &ast.body[1] else {
+            panic!("expected if")
+        };
+        assert!(stmt.conditions.operator.is_none());
+        assert_eq!(stmt.conditions.items.len(), 1);
+        assert_eq!(stmt.body.len(), 1);
+    }
+
+    #[test]
+    fn test_while_for() {
+        let s = r#"
+while true
+    for 0@ = 10 downto 1 step 2
+        wait 0
+    end
+end"#;
+        let (_, ast) = parse(s).unwrap();
+        let Node::While(stmt) = &ast.body[0] else {
+            panic!("expected while")
+        };
+        assert_eq!(stmt.body.len(), 1);
+        let Node::For(stmt) = &stmt.body[0] else {
+            panic!("expected for")
+        };
+        assert!(stmt.downto);
+        assert!(stmt.step.is_some());
+        assert_eq!(text(s, as_literal(&stmt.start)), "10");
+        assert_eq!(text(s, as_literal(&stmt.end)), "1");
+        assert_eq!(stmt.body.len(), 1);
+    }
+
+    fn as_literal(node: &Node) -> &Token {
+        match node {
+            Node::Literal(t) => t,
+            _ => panic!("expected literal"),
+        }
+    }
+
+    #[test]
+    fn test_switch() {
+        let s = r#"
+switch 0@
+case 1, 2
+    wait 0
+    wait 1
+case 3
+default
+    wait 2
+end"#;
+        let (_, ast) = parse(s).unwrap();
+        let Node::Switch(stmt) = &ast.body[0] else {
+            panic!("expected switch")
+        };
+        assert_eq!(stmt.cases.len(), 2);
+        assert_eq!(stmt.cases[0].values.len(), 2);
+        assert_eq!(stmt.cases[0].body.len(), 2);
+        assert_eq!(stmt.cases[1].body.len(), 0);
+        assert_eq!(stmt.default.as_ref().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_function() {
+        let s = r#"
+function foo(a: int): int
+    int x = a
+    if x > 0
+    then
+        return
+    end
+end
+function bar<cdecl, 0x400000>(a: int)
+define function baz(x: float)
+"#;
+        let (_, ast) = parse(s).unwrap();
+        assert_eq!(ast.body.len(), 3);
+        let Node::FunctionDeclaration(f) = &ast.body[0] else {
+            panic!("expected function")
+        };
+        assert_eq!(text(s, &f.signature.name), "foo");
+        assert_eq!(f.body.len(), 2);
+        assert!(matches!(ast.body[1], Node::FunctionSignature(_)));
+        assert!(matches!(ast.body[2], Node::FunctionSignature(_)));
+
+        // unterminated block
+        assert!(parse("function foo\nwait 0").is_err());
+    }
 }
@@ -1,8 +1,19 @@
 use nom::branch::alt;
+use nom::character::complete::char;
+use nom::character::complete::space0;
 use nom::combinator::consumed;
 use nom::combinator::map;
+use nom::multi::many0;
+use nom::multi::separated_list0;
+use nom::sequence::delimited;
+use nom::sequence::pair;
+use nom::sequence::preceded;
 use nom::sequence::tuple;
+use nom_locate::position;
 
+use crate::parser::binary;
+use crate::parser::error::expect;
+use crate::parser::helpers::ws;
 use crate::parser::interface::*;
 use crate::parser::literal;
 use crate::parser::operator;
@@ -20,14 +31,86 @@ pub fn unary(s: Span) -> R<Node> {
                 })
             },
         ),
-        alt((
-            map(variable::variable, |v| Node::Variable(v)),
-            map(literal::number, |n| Node::Literal(n)),
-            map(literal::identifier, |n| Node::Literal(n)),
-        )),
+        expect(
+            &[
+                SyntaxKind::LocalVariable,
+                SyntaxKind::GlobalVariable,
+                SyntaxKind::IntegerLiteral,
+                SyntaxKind::FloatLiteral,
+                SyntaxKind::StringLiteral,
+                SyntaxKind::Identifier,
+            ],
+            alt((
+                postfix(map(variable::variable, |v| Node::Variable(v))),
+                map(literal::number, |n| Node::Literal(n)),
+                map(literal::string, Node::String),
+                postfix(map(literal::identifier, |n| Node::Literal(n))),
+            )),
+        ),
     ))(s)
 }
 
+enum Postfix {
+    Member(Token),
+    Call(Vec<Node>),
+}
+
+// .member
+fn member(s: Span) -> R<Postfix> {
+    map(preceded(char('.'), literal::identifier), Postfix::Member)(s)
+}
+
+// (arg1, arg2)
+fn call(s: Span) -> R<Postfix> {
+    map(
+        delimited(
+            pair(char('('), space0),
+            separated_list0(ws(char(',')), binary::equality),
+            pair(space0, char(')')),
+        ),
+        Postfix::Call,
+    )(s)
+}
+
+/// member access and calls, e.g. `Actor.Health($a)`, each expression spanning from the object to its end
+fn postfix<'a>(
+    primary: impl FnMut(Span<'a>) -> R<'a, Node>,
+) -> impl FnMut(Span<'a>) -> R<'a, Node> {
+    map(
+        tuple((
+            position,
+            primary,
+            many0(pair(alt((member, call)), position)),
+        )),
+        |(start, primary, ops)| {
+            ops.into_iter().fold(primary, |node, (op, end)| {
+                let len = end.location_offset() - start.location_offset();
+                let start = start.location_offset() + 1;
+                match op {
+                    Postfix::Member(member) => Node::Member(MemberExpr {
+                        object: Box::new(node),
+                        member,
+                        token: Token {
+                            start,
+                            len,
+                            syntax_kind: SyntaxKind::MemberExpr,
+                        },
+                    }),
+                    Postfix::Call(arguments) => Node::Call(CallExpr {
+                        callee: Box::new(node),
+                        arguments,
+                        token: Token {
+                            start,
+                            len,
+                            syntax_kind: SyntaxKind::CallExpr,
+                        },
+                    }),
+                }
+            })
+        },
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -166,4 +249,103 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_member_call() {
+        let text =
+            |s: &str, token: &Token| String::from(&s[token.start - 1..token.start - 1 + token.len]);
+
+        let s = "$hp = Actor.Health($a)";
+        let (_, ast) = parse(s).unwrap();
+        let Node::Binary(e) = &ast.body[0] else {
+            panic!("expected binary")
+        };
+        let Node::Call(call) = e.right.as_ref() else {
+            panic!("expected call")
+        };
+        assert_eq!(text(s, &call.token), "Actor.Health($a)");
+        assert_eq!(call.arguments.len(), 1);
+        let Node::Member(member) = call.callee.as_ref() else {
+            panic!("expected member")
+        };
+        assert_eq!(text(s, &member.token), "Actor.Health");
+        assert_eq!(text(s, &member.member), "Health");
+        assert!(
+            matches!(member.object.as_ref(), Node::Literal(t) if t.syntax_kind == SyntaxKind::Identifier)
+        );
+
+        let s = "$a.Health += 1";
+        let (_, ast) = parse(s).unwrap();
+        let Node::Binary(e) = &ast.body[0] else {
+            panic!("expected binary")
+        };
+        let Node::Member(member) = e.left.as_ref() else {
+            panic!("expected member")
+        };
+        assert!(matches!(
+            member.object.as_ref(),
+            Node::Variable(Variable::Global(_))
+        ));
+
+        let s = "Car.Create(0@, 400, 1.0, -2.0, x + 1)";
+        let (_, ast) = parse(s).unwrap();
+        let Node::Call(call) = &ast.body[0] else {
+            panic!("expected call")
+        };
+        assert_eq!(call.arguments.len(), 5);
+        assert!(matches!(call.arguments[4], Node::Binary(_)));
+        assert_eq!(text(s, &call.token), s);
+
+        let (_, ast) = parse("Player.IsPlaying( )").unwrap();
+        assert!(matches!(&ast.body[0], Node::Call(call) if call.arguments.is_empty()));
+
+        // arrays and numbers are not calls
+        let (_, ast) = parse("x(1@,1i)").unwrap();
+        assert!(matches!(
+            ast.body[0],
+            Node::Variable(Variable::ArrayElement(_))
+        ));
+        let (_, ast) = parse("0@ = 1.5").unwrap();
+        assert!(
+            matches!(&ast.body[0], Node::Binary(e) if matches!(e.right.as_ref(), Node::Literal(_)))
+        );
+    }
+
+    #[test]
+    fn test_string() {
+        let (_, ast) = parse(r#"s$a = 'te\'xt'"#).unwrap();
+        let Node::Binary(e) = &ast.body[0] else {
+            panic!("expected binary")
+        };
+        assert_eq!(
+            e.right.as_ref(),
+            &Node::String(StringLiteral {
+                value: String::from("te'xt"),
+                _type: VariableType::ShortString,
+                token: Token {
+                    syntax_kind: SyntaxKind::StringLiteral,
+                    start: 7,
+                    len: 8
+                }
+            })
+        );
+
+        // same escape sequences as in the preprocessor
+        let (_, ast) = parse(r#""a\tb\x41\\\"'""#).unwrap();
+        assert!(matches!(
+            &ast.body[0],
+            Node::String(s) if s.value == "a\tbA\\\"'" && s._type == VariableType::LongString
+        ));
+
+        let (_, ast) = parse("print_help 'text' 0@").unwrap();
+        assert!(matches!(
+            &ast.body[0],
+            Node::CommandCall(c) if matches!(c.arguments[0], Node::String(_))
+        ));
+
+        // unterminated strings and bad escape sequences
+        assert!(parse("'text").is_err());
+        assert!(parse("\"te\nxt\"").is_err());
+        assert!(parse(r"'\xZZ'").is_err());
+    }
 }
@@ -0,0 +1,131 @@
+use anyhow::{bail, Result};
+use std::collections::HashSet;
+
+/// conditional compilation directives, recognized by name so they work with any compiler.ini
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Directive {
+    Define,
+    Undef,
+    IfDef,
+    IfNDef,
+    Else,
+    EndIf,
+}
+
+impl Directive {
+    /// `name` is the directive with the opening `{$`, e.g. `{$IFDEF`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "{$define" => Some(Directive::Define),
+            "{$undef" => Some(Directive::Undef),
+            "{$ifdef" => Some(Directive::IfDef),
+            "{$ifndef" => Some(Directive::IfNDef),
+            "{$else" => Some(Directive::Else),
+            "{$endif" => Some(Directive::EndIf),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Branch {
+    /// the code around the {$IFDEF} block is compiled
+    parent_active: bool,
+    /// the current part of the block is compiled
+    active: bool,
+    has_else: bool,
+}
+
+/// symbols set with {$DEFINE} and the stack of open {$IFDEF} blocks
+#[derive(Default, Debug, Clone)]
+pub struct Conditions {
+    defines: HashSet<String>,
+    branches: Vec<Branch>,
+}
+
+impl Conditions {
+    pub fn new<S: AsRef<str>>(defines: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            defines: defines
+                .into_iter()
+                .map(|name| name.as_ref().to_ascii_lowercase())
+                .collect(),
+            branches: vec![],
+        }
+    }
+
+    /// false for lines in a disabled part of an {$IFDEF} block
+    pub fn is_active(&self) -> bool {
+        !self.branches.last().is_some_and(|branch| !branch.active)
+    }
+
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.defines.contains(&name.to_ascii_lowercase())
+    }
+
+    /// number of open {$IFDEF} blocks
+    pub fn depth(&self) -> usize {
+        self.branches.len()
+    }
+
+    /// handles a conditional directive with the given argument
+    pub fn directive(&mut self, directive: Directive, argument: &str) -> Result<()> {
+        let name = argument.trim();
+        match directive {
+            Directive::Define | Directive::Undef | Directive::IfDef | Directive::IfNDef
+                if name.is_empty() =>
+            {
+                bail!("Missing symbol name in the directive")
+            }
+            Directive::Define => {
+                if self.is_active() {
+                    self.defines.insert(name.to_ascii_lowercase());
+                }
+            }
+            Directive::Undef => {
+                if self.is_active() {
+                    self.defines.remove(&name.to_ascii_lowercase());
+                }
+            }
+            Directive::IfDef | Directive::IfNDef => {
+                let parent_active = self.is_active();
+                let is_defined = self.is_defined(name);
+                self.branches.push(Branch {
+                    parent_active,
+                    active: parent_active && is_defined == (directive == Directive::IfDef),
+                    has_else: false,
+                });
+            }
+            Directive::Else => {
+                let Some(branch) = self.branches.last_mut() else {
+                    bail!("{{$ELSE}} without {{$IFDEF}}")
+                };
+                if branch.has_else {
+                    bail!("Duplicate {{$ELSE}} in the {{$IFDEF}} block")
+                }
+                branch.has_else = true;
+                branch.active = branch.parent_active && !branch.active;
+            }
+            Directive::EndIf => {
+                if self.branches.pop().is_none() {
+                    bail!("{{$ENDIF}} without {{$IFDEF}}")
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// handles a line that starts with a conditional directive (`{$IFDEF NAME}`), returns false for other lines
+    pub fn directive_line(&mut self, line: &str) -> Result<bool> {
+        let line = line.trim_start();
+        if !line.starts_with("{$") {
+            return Ok(false);
+        }
+        let body = line.split('}').next().unwrap_or_default();
+        let (name, argument) = body.split_once(' ').unwrap_or((body, ""));
+        match Directive::from_name(name) {
+            Some(directive) => self.directive(directive, argument).map(|_| true),
+            None => Ok(false),
+        }
+    }
+}
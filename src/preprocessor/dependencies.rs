@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Edge {
+    /// the including file, empty for the in-memory source
+    pub from: PathBuf,
+    pub to: PathBuf,
+    /// 0-based line of the include directive, none for implicit includes
+    pub line: Option<usize>,
+}
+
+/// which file includes which
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DependencyGraph {
+    /// every file in the graph in the order they were found
+    pub files: Vec<PathBuf>,
+    pub edges: Vec<Edge>,
+}
+
+impl DependencyGraph {
+    pub fn clear(&mut self) {
+        self.files.clear();
+        self.edges.clear();
+    }
+
+    pub fn add_file(&mut self, file: &Path) {
+        if !self.files.iter().any(|x| x == file) {
+            self.files.push(file.to_path_buf());
+        }
+    }
+
+    pub fn add_edge(&mut self, edge: Edge) {
+        self.add_file(&edge.from);
+        self.add_file(&edge.to);
+        if !self.edges.contains(&edge) {
+            self.edges.push(edge);
+        }
+    }
+
+    /// adds the graph of another script, e.g. to keep all scripts of the project in one manifest
+    pub fn merge(&mut self, other: &DependencyGraph) {
+        for file in &other.files {
+            self.add_file(file);
+        }
+        for edge in &other.edges {
+            self.add_edge(edge.clone());
+        }
+    }
+
+    /// files that are not included by any other file
+    pub fn roots(&self) -> Vec<&Path> {
+        self.files
+            .iter()
+            .filter(|file| !self.edges.iter().any(|edge| &edge.to == *file))
+            .map(PathBuf::as_path)
+            .collect()
+    }
+
+    /// files included by `file`, directly or through other includes
+    pub fn dependencies(&self, file: &Path) -> Vec<&Path> {
+        let mut found: Vec<&Path> = vec![];
+        let mut visited = HashSet::from([file]);
+        let mut queue = vec![file];
+        while let Some(current) = queue.pop() {
+            for edge in self.edges.iter().filter(|edge| edge.from == current) {
+                if visited.insert(&edge.to) {
+                    found.push(&edge.to);
+                    queue.push(&edge.to);
+                }
+            }
+        }
+        found
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// Graphviz digraph, edges are labeled with 1-based lines of the include directives
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph includes {\n");
+        for file in &self.files {
+            dot += &format!("    {};\n", dot_id(file));
+        }
+        for edge in &self.edges {
+            dot += &format!("    {} -> {}", dot_id(&edge.from), dot_id(&edge.to));
+            if let Some(line) = edge.line {
+                dot += &format!(" [label=\"{}\"]", line + 1);
+            }
+            dot += ";\n";
+        }
+        dot += "}\n";
+        dot
+    }
+}
+
+fn dot_id(file: &Path) -> String {
+    let name = file.to_string_lossy();
+    let name = if name.is_empty() { "<memory>" } else { &name };
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// content hashes of the files in a dependency graph
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// FNV-1a hash of the file content in hex, files that can't be read are not listed
+    pub hashes: BTreeMap<PathBuf, String>,
+}
+
+impl Manifest {
+    pub fn new(graph: &DependencyGraph) -> Self {
+        let hashes = graph
+            .files
+            .iter()
+            .filter_map(|file| {
+                let content = std::fs::read(file).ok()?;
+                Some((file.clone(), format!("{:016x}", fnv1a(&content))))
+            })
+            .collect();
+        Self { hashes }
+    }
+
+    pub fn from_json(json: &str) -> Option<Self> {
+        serde_json::from_str(json).ok()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// top-level scripts of the graph that need recompiling because they or any of their includes
+    /// changed since the `previous` manifest was made
+    pub fn changed_scripts<'a>(
+        &self,
+        previous: &Manifest,
+        graph: &'a DependencyGraph,
+    ) -> Vec<&'a Path> {
+        let is_changed = |file: &Path| {
+            let hash = self.hashes.get(file);
+            hash.is_none() || hash != previous.hashes.get(file)
+        };
+        graph
+            .roots()
+            .into_iter()
+            .filter(|root| is_changed(root) || graph.dependencies(root).into_iter().any(is_changed))
+            .collect()
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
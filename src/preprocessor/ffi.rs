@@ -1,6 +1,9 @@
 use std::ffi::CString;
 
-use super::{PreProcessorBuilder, Preprocessor};
+use super::{
+    conditions::Directive, dependencies::Manifest, diagnostics::Severity, PreProcessorBuilder,
+    Preprocessor,
+};
 use crate::common_ffi::*;
 
 #[no_mangle]
@@ -16,6 +19,28 @@ pub extern "C" fn preprocessor_new(include: PChar, compiler_ini: PChar) -> *mut
     ptr_new(p.build())
 }
 
+/// adds a directory to look up relative include paths in, must be called before parsing
+#[no_mangle]
+pub unsafe extern "C" fn preprocessor_add_search_root(p: *mut Preprocessor, path: PChar) -> bool {
+    boolclosure! {{
+        let p = p.as_mut()?;
+        let path = pchar_to_str(path)?;
+        p.search_roots.push(path.into());
+        Some(())
+    }}
+}
+
+/// sets a symbol for {$IFDEF} and {$IFNDEF}, must be called before parsing
+#[no_mangle]
+pub unsafe extern "C" fn preprocessor_define(p: *mut Preprocessor, name: PChar) -> bool {
+    boolclosure! {{
+        let p = p.as_mut()?;
+        let name = pchar_to_str(name)?;
+        p.conditions.directive(Directive::Define, name).ok()?;
+        Some(())
+    }}
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn preprocessor_free(p: *mut Preprocessor) {
     ptr_free(p)
@@ -30,46 +55,132 @@ pub unsafe extern "C" fn preprocessor_parse_file(p: *mut Preprocessor, file: PCh
     }}
 }
 
-// #[no_mangle]
-// pub unsafe extern "C" fn preprocessor_get_line(
-//     p: *mut Preprocessor,
-//     line_index: u32,
-//     out_line: *mut PChar,
-// ) -> bool {
-//     boolclosure! {{
-//         let p = p.as_mut()?;
-//         *out_line = p.get_line(line_index as usize)?.as_ptr();
-//         Some(())
-//     }}
-// }
-
-// #[no_mangle]
-// pub unsafe extern "C" fn preprocessor_get_line_count(
-//     p: *mut Preprocessor,
-//     out_count: *mut u32,
-// ) -> bool {
-//     boolclosure! {{
-//         let p = p.as_mut()?;
-//         *out_count = p.get_line_count() as u32;
-//         Some(())
-//     }}
-// }
-
-// #[no_mangle]
-// pub unsafe extern "C" fn preprocessor_translate_line(
-//     p: *mut Preprocessor,
-//     line_index: u32,
-//     out_index: *mut u32,
-//     out_filename: *mut PChar,
-// ) -> bool {
-//     boolclosure! {{
-//         let p = p.as_mut()?;
-//         let (index, filename) = p.translate_line(line_index as usize)?;
-//         *out_index = index as u32;
-//         *out_filename = CString::new(filename.to_str()?).ok()?.into_raw();
-//         Some(())
-//     }}
-// }
+/// the line is owned by the preprocessor and valid until the next parse
+#[no_mangle]
+pub unsafe extern "C" fn preprocessor_get_line(
+    p: *mut Preprocessor,
+    line_index: u32,
+    out_line: *mut PChar,
+) -> bool {
+    boolclosure! {{
+        let p = p.as_mut()?;
+        *out_line = p.get_line(line_index as usize)?.as_ptr();
+        Some(())
+    }}
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn preprocessor_get_line_count(
+    p: *mut Preprocessor,
+    out_count: *mut u32,
+) -> bool {
+    boolclosure! {{
+        let p = p.as_mut()?;
+        *out_count = p.get_line_count() as u32;
+        Some(())
+    }}
+}
+
+/// `out_filename` is empty for the in-memory source and must be released with [str_free]
+#[no_mangle]
+pub unsafe extern "C" fn preprocessor_translate_line(
+    p: *mut Preprocessor,
+    line_index: u32,
+    out_index: *mut u32,
+    out_filename: *mut PChar,
+) -> bool {
+    boolclosure! {{
+        let p = p.as_mut()?;
+        let (index, filename) = p.translate_line(line_index as usize)?;
+        *out_index = index as u32;
+        *out_filename = CString::new(filename.to_str()?).ok()?.into_raw();
+        Some(())
+    }}
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn preprocessor_get_diagnostic_count(
+    p: *mut Preprocessor,
+    out_count: *mut u32,
+) -> bool {
+    boolclosure! {{
+        let p = p.as_mut()?;
+        *out_count = p.diagnostics.len() as u32;
+        Some(())
+    }}
+}
+
+/// `out_line` and `out_column` are 0-based, `out_file_name` is empty for the in-memory source.
+/// `out_file_name` and `out_message` must be released with [str_free]
+#[no_mangle]
+pub unsafe extern "C" fn preprocessor_get_diagnostic(
+    p: *mut Preprocessor,
+    index: u32,
+    out_severity: *mut Severity,
+    out_file_name: *mut PChar,
+    out_line: *mut u32,
+    out_column: *mut u32,
+    out_message: *mut PChar,
+) -> bool {
+    boolclosure! {{
+        let p = p.as_mut()?;
+        let d = p.diagnostics.get(index as usize)?;
+        let file_name = CString::new(d.file_name.to_str()?).ok()?;
+        let message = CString::new(d.message.as_str()).ok()?;
+        *out_severity = d.severity;
+        *out_line = d.line as u32;
+        *out_column = d.column as u32;
+        *out_file_name = file_name.into_raw();
+        *out_message = message.into_raw();
+        Some(())
+    }}
+}
+
+/// include graph of the last parsed source, `format` is 0 for JSON or 1 for DOT.
+/// The result must be released with [str_free]
+#[no_mangle]
+pub unsafe extern "C" fn preprocessor_get_dependencies(
+    p: *mut Preprocessor,
+    format: u8,
+    out: *mut PChar,
+) -> bool {
+    boolclosure! {{
+        let p = p.as_mut()?;
+        let text = match format {
+            0 => p.dependencies.to_json(),
+            1 => p.dependencies.to_dot(),
+            _ => return None,
+        };
+        *out = CString::new(text).ok()?.into_raw();
+        Some(())
+    }}
+}
+
+/// content hashes of the files of the last parsed source as JSON, the result must be released with [str_free]
+#[no_mangle]
+pub unsafe extern "C" fn preprocessor_get_manifest(p: *mut Preprocessor, out: *mut PChar) -> bool {
+    boolclosure! {{
+        let p = p.as_mut()?;
+        *out = CString::new(Manifest::new(&p.dependencies).to_json()).ok()?.into_raw();
+        Some(())
+    }}
+}
+
+/// `out_changed` is set if the source or any of its includes changed since the `previous` manifest was made
+#[no_mangle]
+pub unsafe extern "C" fn preprocessor_is_changed(
+    p: *mut Preprocessor,
+    previous: PChar,
+    out_changed: *mut bool,
+) -> bool {
+    boolclosure! {{
+        let p = p.as_mut()?;
+        let previous = Manifest::from_json(pchar_to_str(previous)?)?;
+        let current = Manifest::new(&p.dependencies);
+        *out_changed = !current.changed_scripts(&previous, &p.dependencies).is_empty();
+        Some(())
+    }}
+}
 
 #[no_mangle]
 pub unsafe extern "C" fn preprocessor_get_number_of_functions_this_scope(
@@ -98,3 +209,22 @@ pub unsafe extern "C" fn preprocessor_get_function(
         Some(())
     }}
 }
+
+/// `out_variadic` is set if the last parameter takes the rest of the arguments,
+/// `out_optional_return` is set if the function may return no values
+#[no_mangle]
+pub unsafe extern "C" fn preprocessor_get_function_flags(
+    p: *mut Preprocessor,
+    line_index: u32,
+    function_index: u32,
+    out_variadic: *mut bool,
+    out_optional_return: *mut bool,
+) -> bool {
+    boolclosure! {{
+        let p = p.as_mut()?;
+        let f = p.get_function(line_index as usize, function_index as usize)?;
+        *out_variadic = f.is_variadic;
+        *out_optional_return = f.has_optional_return;
+        Some(())
+    }}
+}
@@ -4,9 +4,12 @@ use std::{
     collections::HashSet,
     ffi::CString,
     io::{BufRead, BufReader, Read},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
+use self::conditions::Directive;
+use self::dependencies::{DependencyGraph, Edge};
+use self::diagnostics::{Diagnostic, Diagnostics, Severity};
 use self::line_parser::{TokenType, TokenVal};
 use crate::{
     dictionary::{config, ffi::CaseFormat, DictNumByString},
@@ -21,9 +24,12 @@ use crate::{
     v4::helpers::token_str,
 };
 
+pub mod conditions;
+pub mod dependencies;
+pub mod diagnostics;
 mod ffi;
 mod line_parser;
-mod scopes;
+pub(crate) mod scopes;
 
 type FileName = PathBuf;
 
@@ -37,6 +43,8 @@ pub enum SourceType {
 #[derive(Default)]
 pub struct Preprocessor {
     pub implicit_includes: HashSet<FileName>,
+    /// directories to look up relative include paths in, after the directory of the including file
+    pub search_roots: Vec<FileName>,
     pub source_type: SourceType,
     pub files: Vec<FileName>,
     pub open_files: HashSet<FileName>,
@@ -45,8 +53,17 @@ pub struct Preprocessor {
     pub current_file: isize,
     pub absolute_line_index: usize,
     pub scopes: scopes::Scopes,
+    pub conditions: conditions::Conditions,
+    /// expanded source with included files inlined, each line with its origin
+    pub lines: Vec<(CString, LineLoc)>,
+    pub diagnostics: Diagnostics,
+    /// includes found while processing the source
+    pub dependencies: DependencyGraph,
+    /// locations of the {$IFDEF} blocks without {$ENDIF} yet
+    open_conditions: Vec<LineLoc>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct LineLoc {
     file_index: isize, // -1 for memory
     line_index: usize, // 0-based
@@ -55,18 +72,22 @@ pub struct LineLoc {
 #[derive(Debug)]
 pub struct PreProcessorBuilder {
     implicit_includes: HashSet<FileName>,
+    search_roots: Vec<FileName>,
     reserved_words: DictNumByString,
+    defines: Vec<String>,
 }
 
 impl PreProcessorBuilder {
     pub fn new() -> Self {
         Self {
             implicit_includes: HashSet::new(),
+            search_roots: vec![],
             reserved_words: DictNumByString::new(
                 config::ConfigBuilder::new()
                     .set_case_format(CaseFormat::LowerCase)
                     .build(),
             ),
+            defines: vec![],
         }
     }
 
@@ -75,17 +96,31 @@ impl PreProcessorBuilder {
         self
     }
 
+    /// directories searched in the given order when an include is not found next to the including file
+    pub fn search_roots(&mut self, roots: Vec<FileName>) -> &mut Self {
+        self.search_roots.extend(roots);
+        self
+    }
+
     pub fn reserved_words(&mut self, reserved_words: FileName) -> &mut Self {
         self.reserved_words.load_file(&reserved_words);
         self
     }
 
+    /// symbols checked by {$IFDEF} and {$IFNDEF}, as if they were set with {$DEFINE}
+    pub fn defines(&mut self, defines: Vec<String>) -> &mut Self {
+        self.defines.extend(defines);
+        self
+    }
+
     pub fn build(&mut self) -> Preprocessor {
         Preprocessor {
             implicit_includes: self.implicit_includes.clone(),
+            search_roots: self.search_roots.clone(),
             parser: line_parser::DataParser::new(),
             reserved_words: self.reserved_words.clone(),
             scopes: scopes::Scopes::new(),
+            conditions: conditions::Conditions::new(&self.defines),
             ..Default::default()
         }
     }
@@ -95,14 +130,22 @@ impl Preprocessor {
     pub fn parse_file(&mut self, file_path: FileName) -> Result<()> {
         self.source_type = SourceType::File(file_path.clone());
         self.absolute_line_index = 0;
-        self.load_implicit_includes()?;
-        match self.load_file_source(&file_path) {
-            Ok(_) => {}
-            Err(e) => {
-                // todo: propagate error to compiler
-                log::error!("{e}");
-            }
+        self.lines.clear();
+        self.diagnostics.clear();
+        self.dependencies.clear();
+        let main_file = normalize_file_name(&file_path).unwrap_or(file_path.clone());
+        self.dependencies.add_file(&main_file);
+        self.load_implicit_includes(&main_file);
+        if let Err(e) = self.load_file_source(&file_path) {
+            self.diagnostics.add(Diagnostic {
+                severity: Severity::Error,
+                file_name: file_path,
+                line: 0,
+                column: 0,
+                message: e.to_string(),
+            });
         }
+        self.check_conditions();
         self.scopes.exit_scope(self.absolute_line_index);
         Ok(())
     }
@@ -110,23 +153,71 @@ impl Preprocessor {
     pub fn parse_in_memory(&mut self, source: &str) -> Result<()> {
         self.source_type = SourceType::Memory;
         self.absolute_line_index = 0;
-        self.load_implicit_includes()?;
+        self.lines.clear();
+        self.diagnostics.clear();
+        self.dependencies.clear();
+        self.dependencies.add_file(Path::new(""));
+        self.load_implicit_includes(Path::new(""));
 
         self.current_file = -1;
         let mut in_hex_block = false;
         for (line_index, line) in source.lines().enumerate() {
-            match self.process_line(line, line_index, &mut in_hex_block) {
-                Ok(_) => {}
-                Err(e) => {
-                    // bail!(e);
-                    log::error!("{e}");
-                }
-            }
+            // errors are collected in diagnostics
+            let _ = self.process_line(line, line_index, &mut in_hex_block);
         }
+        self.check_conditions();
         self.scopes.exit_scope(self.absolute_line_index);
         Ok(())
     }
 
+    fn check_conditions(&mut self) {
+        for loc in std::mem::take(&mut self.open_conditions) {
+            self.diagnostics.add(Diagnostic {
+                severity: Severity::Error,
+                file_name: self.file_name(loc.file_index).to_path_buf(),
+                line: loc.line_index,
+                column: 0,
+                message: String::from("Missing {$ENDIF}"),
+            });
+        }
+    }
+
+    /// adds a diagnostic for the line of the current file
+    fn report(&mut self, severity: Severity, line: usize, column: usize, message: String) {
+        self.diagnostics.add(Diagnostic {
+            severity,
+            file_name: self.file_name(self.current_file).to_path_buf(),
+            line,
+            column,
+            message,
+        });
+    }
+
+    /// the name is empty for the in-memory source
+    fn file_name(&self, file_index: isize) -> &Path {
+        match file_index {
+            -1 => Path::new(""),
+            x => self
+                .files
+                .get(x as usize)
+                .map_or(Path::new(""), |x| x.as_path()),
+        }
+    }
+
+    pub fn get_line(&self, index: usize) -> Option<&CString> {
+        self.lines.get(index).map(|(line, _)| line)
+    }
+
+    pub fn get_line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// 0-based line number and name of the file the output line comes from, the name is empty for the in-memory source
+    pub fn translate_line(&self, index: usize) -> Option<(usize, &Path)> {
+        let (_, loc) = self.lines.get(index)?;
+        Some((loc.line_index, self.file_name(loc.file_index)))
+    }
+
     pub fn get_number_of_functions_this_scope(&self, line_index: usize) -> usize {
         self.scopes
             .functions
@@ -143,14 +234,24 @@ impl Preprocessor {
             .nth(index)
     }
 
-    fn load_implicit_includes(&mut self) -> Result<()> {
+    fn load_implicit_includes(&mut self, main_file: &Path) {
         let includes = self.implicit_includes.clone();
         for include in includes {
+            self.dependencies.add_edge(Edge {
+                from: main_file.to_path_buf(),
+                to: normalize_file_name(&include).unwrap_or(include.clone()),
+                line: None,
+            });
             if let Err(e) = self.load_file_source(&include) {
-                return Err(e);
+                self.diagnostics.add(Diagnostic {
+                    severity: Severity::Error,
+                    file_name: include,
+                    line: 0,
+                    column: 0,
+                    message: e.to_string(),
+                });
             }
         }
-        Ok(())
     }
 
     fn load_file_source(&mut self, file_path: &FileName) -> Result<()> {
@@ -167,6 +268,7 @@ impl Preprocessor {
             }) as isize;
 
         let Ok(file) = std::fs::File::open(file_path) else {
+            self.current_file = prev_file;
             bail!("Can't open file: {:?}", file_path);
         };
         let reader = std::io::BufReader::new(file);
@@ -174,15 +276,18 @@ impl Preprocessor {
         let mut in_hex_block = false;
         while let Some((line_index, line)) = lines.next() {
             match line {
-                Ok(line) => match self.process_line(line.as_str(), line_index, &mut in_hex_block) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        // bail!(e);
-                        log::error!("{e}");
-                    }
-                },
+                Ok(line) => {
+                    // errors are collected in diagnostics
+                    let _ = self.process_line(line.as_str(), line_index, &mut in_hex_block);
+                }
                 Err(_) => {
-                    bail!("Can't read line {line_index} from file {:?}", file_path);
+                    self.report(
+                        Severity::Error,
+                        line_index,
+                        0,
+                        format!("Can't read line {line_index} from file {:?}", file_path),
+                    );
+                    break;
                 }
             }
         }
@@ -197,6 +302,30 @@ impl Preprocessor {
         line_index: usize,
         in_hex_block: &mut bool,
     ) -> Result<()> {
+        let result = self.process_tokens(line, line_index, in_hex_block);
+        if let Err(e) = &result {
+            let column = self.parser.current_loc().1;
+            self.report(Severity::Error, line_index, column, e.to_string());
+        }
+        if !matches!(result, Ok(false)) {
+            self.lines.push((
+                CString::new(line).unwrap_or_default(),
+                LineLoc {
+                    file_index: self.current_file,
+                    line_index,
+                },
+            ));
+        }
+        result.map(|_| ())
+    }
+
+    /// returns false if the line does not go to the output (e.g. includes, empty lines or disabled code)
+    fn process_tokens(
+        &mut self,
+        line: &str,
+        line_index: usize,
+        in_hex_block: &mut bool,
+    ) -> Result<bool> {
         self.parser.line(line);
         self.absolute_line_index += 1;
         let token = self.parser.get_token();
@@ -205,8 +334,36 @@ impl Preprocessor {
             TokenType::Directive if !*in_hex_block => {
                 match token.val {
                     TokenVal::Ident(s) => {
+                        if let Some(directive) = Directive::from_name(&s) {
+                            self.parser.skip_whitespace();
+                            let token = self.parser.get_until1(b"}", TokenType::Ident);
+                            if self.parser.get_token().token_type != TokenType::CloseCurly {
+                                bail!("Error parsing directive {s}")
+                            }
+                            let argument = match token.val {
+                                TokenVal::Ident(argument) => argument,
+                                _ => String::new(),
+                            };
+                            self.conditions.directive(directive, &argument)?;
+                            match directive {
+                                Directive::IfDef | Directive::IfNDef => {
+                                    self.open_conditions.push(LineLoc {
+                                        file_index: self.current_file,
+                                        line_index,
+                                    })
+                                }
+                                Directive::EndIf => {
+                                    self.open_conditions.pop();
+                                }
+                                _ => {}
+                            }
+                            return Ok(false);
+                        }
                         let token_id = self.reserved_words.map.get(&s.to_ascii_lowercase());
                         match token_id {
+                            _ if !self.conditions.is_active() => {
+                                return Ok(false); // disabled by {$IFDEF}
+                            }
                             Some(&TOKEN_INCLUDE) | Some(&TOKEN_INCLUDE_ONCE) => {
                                 self.parser.skip_whitespace();
                                 let token = self.parser.get_until1(b"}", TokenType::Ident);
@@ -229,39 +386,37 @@ impl Preprocessor {
                                                     }
                                                 };
 
-                                                let Some(path) =
-                                                    resolve_path(&include_path, current_file_name)
-                                                else {
-                                                    let loc = self.parser.current_loc();
-                                                    bail!("Error resolving path to include file at {}:{}", loc.0, loc.1);
+                                                let path = match resolve_path(
+                                                    &include_path,
+                                                    current_file_name,
+                                                    &self.search_roots,
+                                                ) {
+                                                    Ok(path) => path,
+                                                    Err(e) => bail!(e),
                                                 };
+                                                self.dependencies.add_edge(Edge {
+                                                    from: self
+                                                        .file_name(self.current_file)
+                                                        .to_path_buf(),
+                                                    to: path.clone(),
+                                                    line: Some(line_index),
+                                                });
 
                                                 if matches!(token_id, Some(&TOKEN_INCLUDE_ONCE))
                                                     && self.files.iter().any(|x| x == &path)
                                                 {
                                                     // already included
-                                                    return Ok(());
+                                                    return Ok(false);
                                                 }
 
                                                 if !self.open_files.insert(path.clone()) {
-                                                    let loc = self.parser.current_loc();
-                                                    match current_file_name {
-                                                        Some(x) => {
-                                                            bail!("Circular include detected at {:?}:{}", x, loc.1);
-                                                        }
-                                                        None => {
-                                                            // can't happen as current file is in-memory and can't include itself
-                                                            bail!("Circular include detected at {}:{}", loc.0, loc.1);
-                                                        }
-                                                    }
-                                                }
-
-                                                if let Err(e) = self.load_file_source(&path) {
-                                                    bail!(e);
+                                                    bail!("Circular include of {:?}", path);
                                                 }
 
+                                                let result = self.load_file_source(&path);
                                                 self.open_files.remove(&path);
-                                                return Ok(()); // don't add the include line to the source
+                                                result?;
+                                                return Ok(false); // don't add the include line to the source
                                             }
                                             _ => {}
                                         }
@@ -275,16 +430,24 @@ impl Preprocessor {
                                 // skip other directives
                             }
                             None => {
-                                bail!("Unknown directive: {}", s);
+                                // reported, the rest of the file is still processed
+                                self.report(
+                                    Severity::Error,
+                                    line_index,
+                                    0,
+                                    format!("Unknown directive: {s}}}"),
+                                );
                             }
                         }
                     }
                     _ => {}
                 }
             }
+            _ if !self.conditions.is_active() => {
+                return Ok(false); // disabled by {$IFDEF}
+            }
             TokenType::Unknown if !*in_hex_block => {
-                let loc = self.parser.current_loc();
-                bail!("Unknown token at line {}:{}", loc.0, loc.1);
+                bail!("Unknown token");
             }
             TokenType::Ident => {
                 match token.val {
@@ -350,11 +513,11 @@ impl Preprocessor {
                 }
             }
             TokenType::Eol => {
-                return Ok(()); // skip empty lines
+                return Ok(false); // skip empty lines
             }
             _ => {}
         }
-        return Ok(());
+        return Ok(true);
     }
 
     fn process_new_function(&mut self, line: &str) -> Result<()> {
@@ -363,12 +526,14 @@ impl Preprocessor {
         let line = Self::strip_comments(line);
         let line = line.as_str();
         let Ok((_, ref signature)) = function_signature(Span::from(line)) else {
-            let loc = self.parser.current_loc();
-            bail!("Can't parse function at {}:{}", loc.0, loc.1)
+            bail!("Can't parse function signature")
         };
 
-        self.scopes
-            .add_function(token_str(line, &signature.token).to_string());
+        self.scopes.add_function(
+            token_str(line, &signature.token).to_string(),
+            signature.is_variadic(),
+            signature.has_optional_return(),
+        );
         if signature.cc == crate::parser::FunctionCC::Local {
             self.scopes.enter_scope(self.absolute_line_index); // from 'function' line
         }
@@ -486,15 +651,15 @@ mod tests {
             .unwrap();
         assert_eq!(preprocessor.files.len(), 3);
 
-        let mut preprocessor = PreProcessorBuilder::new()
-            .reserved_words("src/preprocessor/test/compiler.ini".into())
-            .build();
-        let e = preprocessor.parse_in_memory(r#" {$include "#);
-        // todo:
-        // assert!(e.is_err());
-
-        // let e = preprocessor.parse_in_memory(r#" {$include missing.txt } "#);
-        // assert!(e.is_err());
+        let has_errors = |source: &str| {
+            let mut preprocessor = PreProcessorBuilder::new()
+                .reserved_words("src/preprocessor/test/compiler.ini".into())
+                .build();
+            preprocessor.parse_in_memory(source).unwrap();
+            preprocessor.diagnostics.has_errors()
+        };
+        assert!(has_errors(r#" {$include "#));
+        assert!(has_errors(r#" {$include missing.txt } "#));
     }
 
     #[test]
@@ -503,9 +668,44 @@ mod tests {
             .implicit_includes(vec!["src/preprocessor/test/circular1.txt".into()])
             .reserved_words("src/preprocessor/test/compiler.ini".into())
             .build();
-        let e = preprocessor.parse_file("src/preprocessor/test/circular1.txt".into());
-        // todo:
-        // assert!(e.is_err());
+        preprocessor
+            .parse_file("src/preprocessor/test/circular1.txt".into())
+            .unwrap();
+        assert!(preprocessor
+            .diagnostics
+            .iter()
+            .any(|d| d.message.starts_with("Circular include")));
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let diagnostics = |source: &str| {
+            let mut preprocessor = PreProcessorBuilder::new()
+                .reserved_words("src/preprocessor/test/compiler.ini".into())
+                .build();
+            preprocessor.parse_in_memory(source).unwrap();
+            preprocessor
+                .diagnostics
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // the disabled part of {$IFDEF A} is not checked
+        assert_eq!(
+            diagnostics("{$IFDEF A}\n{$FOO}\nfunction 1\nend\n{$ENDIF}\n{$IFNDEF B}\nnop"),
+            vec![":6:1: error: Missing {$ENDIF}"]
+        );
+        assert_eq!(
+            diagnostics("{$FOO}\n{$INCLUDE missing.txt}\nfunction 1\n^\n{$ENDIF}"),
+            vec![
+                ":1:1: error: Unknown directive: {$FOO}",
+                ":2:23: error: Can't find file missing.txt",
+                ":3:9: error: Can't parse function signature",
+                ":4:1: error: Unknown token",
+                ":5:9: error: {$ENDIF} without {$IFDEF}",
+            ]
+        );
     }
 
     #[test]
@@ -523,6 +723,25 @@ mod tests {
         assert_eq!(preprocessor.get_number_of_functions_this_scope(9), 0);
     }
 
+    #[test]
+    fn test_function_flags() {
+        let mut preprocessor = PreProcessorBuilder::new()
+            .reserved_words("src/preprocessor/test/compiler.ini".into())
+            .build();
+        preprocessor
+            .parse_in_memory(
+                "function foo(a: int, ...rest: int): optional int\nend\nfunction bar(a: int): int\nend",
+            )
+            .unwrap();
+
+        let foo = preprocessor.get_function(0, 0).unwrap();
+        assert!(foo.is_variadic);
+        assert!(foo.has_optional_return);
+        let bar = preprocessor.get_function(0, 1).unwrap();
+        assert!(!bar.is_variadic);
+        assert!(!bar.has_optional_return);
+    }
+
     #[test]
     fn test_foreign_function() {
         let mut preprocessor = PreProcessorBuilder::new()
@@ -565,6 +784,179 @@ mod tests {
         assert_eq!(preprocessor.get_number_of_functions_this_scope(50), 1);
     }
 
+    #[test]
+    fn test_search_roots() {
+        let mut preprocessor = PreProcessorBuilder::new()
+            .reserved_words("src/preprocessor/test/compiler.ini".into())
+            .search_roots(vec![
+                "src/preprocessor/test/missing".into(),
+                "src/preprocessor/test/lib".into(),
+            ])
+            .build();
+        preprocessor
+            .parse_file("src/preprocessor/test/scr_with_lib.txt".into())
+            .unwrap();
+        assert_eq!(preprocessor.files.len(), 2);
+        assert!(preprocessor.files[1].ends_with("lib/shared.txt"));
+
+        let mut preprocessor = PreProcessorBuilder::new()
+            .reserved_words("src/preprocessor/test/compiler.ini".into())
+            .search_roots(vec!["src/preprocessor/test/missing".into()])
+            .build();
+        preprocessor.current_file = -1; // in-memory source
+        let e = preprocessor
+            .process_line("{$INCLUDE shared.txt}", 0, &mut false)
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("Can't find file shared.txt, tried:"));
+        assert!(e.contains(&format!(
+            "\n  {}",
+            Path::new("src/preprocessor/test/missing/shared.txt").display()
+        )));
+    }
+
+    #[test]
+    fn test_lines() {
+        let mut preprocessor = PreProcessorBuilder::new()
+            .reserved_words("src/preprocessor/test/compiler.ini".into())
+            .search_roots(vec!["src/preprocessor/test/lib".into()])
+            .build();
+        preprocessor
+            .parse_in_memory(
+                "{$CLEO}\n\n{$INCLUDE shared.txt}\n{$IFDEF DEBUG}\nnop\n{$ENDIF}\nwait 0",
+            )
+            .unwrap();
+
+        let lines = (0..preprocessor.get_line_count())
+            .map(|i| preprocessor.get_line(i).unwrap().to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec!["{$CLEO}", "function shared_log", "end", "wait 0"]
+        );
+
+        let shared = Path::new("src/preprocessor/test/lib/shared.txt");
+        assert_eq!(preprocessor.translate_line(0), Some((0, Path::new(""))));
+        let (index, file_name) = preprocessor.translate_line(2).unwrap();
+        assert_eq!(index, 1);
+        assert!(file_name.ends_with(shared));
+        assert_eq!(preprocessor.translate_line(3), Some((6, Path::new(""))));
+        assert_eq!(preprocessor.translate_line(4), None);
+    }
+
+    #[test]
+    fn test_dependencies() {
+        use dependencies::Manifest;
+
+        let mut preprocessor = PreProcessorBuilder::new()
+            .reserved_words("src/preprocessor/test/compiler.ini".into())
+            .implicit_includes(vec!["src/preprocessor/test/const.txt".into()])
+            .search_roots(vec!["src/preprocessor/test/lib".into()])
+            .build();
+        preprocessor
+            .parse_file("src/preprocessor/test/scr_with_lib.txt".into())
+            .unwrap();
+
+        let graph = &preprocessor.dependencies;
+        let path = |p: &str| normalize_file_name(Path::new(p)).unwrap();
+        let script = path("src/preprocessor/test/scr_with_lib.txt");
+        let constants = path("src/preprocessor/test/const.txt");
+        let shared = path("src/preprocessor/test/lib/shared.txt");
+        assert_eq!(
+            graph.files,
+            vec![script.clone(), constants.clone(), shared.clone()]
+        );
+        assert_eq!(graph.edges[0].line, None);
+        assert_eq!(graph.edges[1].from, script);
+        assert_eq!(graph.edges[1].to, shared);
+        assert_eq!(graph.edges[1].line, Some(0));
+        assert_eq!(graph.roots(), vec![script.as_path()]);
+        assert_eq!(
+            graph.dependencies(&script),
+            vec![constants.as_path(), shared.as_path()]
+        );
+        assert!(graph.to_dot().contains("scr_with_lib.txt\" -> \""));
+        assert!(graph.to_dot().contains("shared.txt\" [label=\"1\"];"));
+        assert_eq!(
+            serde_json::from_str::<dependencies::DependencyGraph>(&graph.to_json()).unwrap(),
+            *graph
+        );
+
+        let manifest = Manifest::new(graph);
+        assert_eq!(manifest.hashes.len(), 3);
+        let previous = Manifest::from_json(&manifest.to_json()).unwrap();
+        assert!(manifest.changed_scripts(&previous, graph).is_empty());
+
+        // shared include was edited
+        let mut previous = previous;
+        previous.hashes.insert(shared, String::from("0"));
+        assert_eq!(
+            manifest.changed_scripts(&previous, graph),
+            vec![script.as_path()]
+        );
+    }
+
+    #[test]
+    fn test_conditions() {
+        let source = "{$DEFINE LOG}
+{$IFDEF DEBUG}
+function debug_only
+end
+{$ELSE}
+{$IFDEF LOG}
+function log
+end
+{$ENDIF}
+{$ENDIF}
+{$IFNDEF gta3}
+function not_gta3
+end
+{$ENDIF}";
+        let functions = |defines: Vec<String>| {
+            let mut preprocessor = PreProcessorBuilder::new()
+                .reserved_words("src/preprocessor/test/compiler.ini".into())
+                .defines(defines)
+                .build();
+            preprocessor.parse_in_memory(source).unwrap();
+            assert_eq!(preprocessor.conditions.depth(), 0);
+            preprocessor
+                .scopes
+                .functions
+                .iter()
+                .map(|f| f.signature.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(functions(vec![]), vec!["function log", "function not_gta3"]);
+        assert_eq!(
+            functions(vec!["Debug".into(), "GTA3".into()]),
+            vec!["function debug_only"]
+        );
+    }
+
+    #[test]
+    fn test_conditions_errors() {
+        let mut preprocessor = PreProcessorBuilder::new()
+            .reserved_words("src/preprocessor/test/compiler.ini".into())
+            .build();
+        let mut in_hex_block = false;
+        assert!(preprocessor
+            .process_line("{$ENDIF}", 0, &mut in_hex_block)
+            .is_err());
+        assert!(preprocessor
+            .process_line("{$IFDEF}", 1, &mut in_hex_block)
+            .is_err());
+        assert!(preprocessor
+            .process_line("{$IFDEF A}", 2, &mut in_hex_block)
+            .is_ok());
+        assert!(preprocessor
+            .process_line("{$ELSE}", 3, &mut in_hex_block)
+            .is_ok());
+        assert!(preprocessor
+            .process_line("{$ELSE}", 4, &mut in_hex_block)
+            .is_err());
+    }
+
     #[test]
     fn test_parse_comments() {
         let mut preprocessor = PreProcessorBuilder::new()
@@ -17,6 +17,10 @@ pub struct Scope {
 pub struct Function {
     pub zone: VisibilityZone,
     pub signature: String,
+    /// the last parameter takes the rest of the arguments (`...args: int`)
+    pub is_variadic: bool,
+    /// the function may return no values (`: optional int`)
+    pub has_optional_return: bool,
 }
 
 impl Scopes {
@@ -50,15 +54,28 @@ impl Scopes {
         self.stack.pop();
     }
 
+    /// number of functions and blocks enclosing the current line
+    pub fn depth(&self) -> usize {
+        let blocks: usize = self.stack.iter().map(|scope| scope.open_blocks).sum();
+        blocks + self.stack.len().saturating_sub(1)
+    }
+
     pub fn get_current_scope(&mut self) -> &mut Scope {
         self.stack.last_mut().unwrap()
     }
 
-    pub fn add_function(&mut self, signature: String) {
+    pub fn add_function(
+        &mut self,
+        signature: String,
+        is_variadic: bool,
+        has_optional_return: bool,
+    ) {
         let start_line = self.get_current_scope().start_line;
         self.functions.push(Function {
             // name,
             signature,
+            is_variadic,
+            has_optional_return,
             zone: VisibilityZone {
                 start: start_line,
                 end: 0,
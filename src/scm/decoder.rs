@@ -0,0 +1,216 @@
+use super::*;
+use crate::legacy_ini::Game;
+use crate::namespaces::{namespaces::Namespaces, OpId};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedInstruction {
+    /// offset of the opcode in the buffer
+    pub offset: usize,
+    /// opcode without the NOT flag
+    pub id: OpId,
+    /// the result of the condition is inverted
+    pub not: bool,
+    /// arguments in the order they are stored in the script (inputs, then outputs)
+    pub args: Vec<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodeError {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:08X}: {}", self.offset, self.message)
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+pub struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    game: Game,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(bytes: &'a [u8], game: Game) -> Self {
+        Self {
+            bytes,
+            pos: 0,
+            game,
+        }
+    }
+
+    pub fn is_eof(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    /// reads the next instruction, parameter types are taken from the command definition in the library
+    pub fn next_instruction(&mut self, ns: &Namespaces) -> Result<DecodedInstruction, DecodeError> {
+        let offset = self.pos;
+        let raw = self.u16()?;
+        let id = raw & !NOT_FLAG;
+        let Some(command) = ns.get_command(id) else {
+            return Err(self.error_at(offset, format!("unknown opcode {id:04X}")));
+        };
+
+        let mut args = vec![];
+        for param in command.input.iter().chain(command.output.iter()) {
+            match param.r#type.to_ascii_lowercase().as_str() {
+                "arguments" => {
+                    // read until the end of the argument list
+                    while self.peek()? != DataType::EndOfArgs as u8 {
+                        args.push(self.value()?);
+                    }
+                    self.pos += 1;
+                }
+                "string128" => args.push(Value::Byte128(self.fixed_string(128)?)),
+                _ => args.push(self.value()?),
+            }
+        }
+
+        Ok(DecodedInstruction {
+            offset,
+            id,
+            not: raw & NOT_FLAG != 0,
+            args,
+        })
+    }
+
+    fn value(&mut self) -> Result<Value, DecodeError> {
+        let offset = self.pos;
+        let tag = self.u8()?;
+        let Ok(data_type) = DataType::try_from(tag) else {
+            // text labels in GTA III and VC are stored without the data type
+            self.pos = offset;
+            return Ok(Value::String8(self.fixed_string(8)?));
+        };
+
+        use DataType::*;
+        Ok(match data_type {
+            Int32 => Value::Int(self.i32()?),
+            Int16 => Value::Int(self.u16()? as i16 as i32),
+            Int8 => Value::Int(self.u8()? as i8 as i32),
+            Float => Value::Float(self.float()?),
+            GlobalVar => Value::Var(self.var(true, VariableType::Unknown)?),
+            LocalVar => Value::Var(self.var(false, VariableType::Unknown)?),
+            GlobalVarString8 => Value::Var(self.var(true, VariableType::ShortString)?),
+            LocalVarString8 => Value::Var(self.var(false, VariableType::ShortString)?),
+            GlobalVarString16 => Value::Var(self.var(true, VariableType::LongString)?),
+            LocalVarString16 => Value::Var(self.var(false, VariableType::LongString)?),
+            GlobalArray | GlobalArrayString8 | GlobalArrayString16 => self.array(true)?,
+            LocalArray | LocalArrayString8 | LocalArrayString16 => self.array(false)?,
+            String8 => Value::String8(self.fixed_string(8)?),
+            String16 => Value::String16(self.fixed_string(16)?),
+            VarString => {
+                let len = self.u8()? as usize;
+                Value::VarString(self.fixed_string(len)?)
+            }
+            EndOfArgs => return Err(self.error_at(offset, "unexpected end of arguments")),
+        })
+    }
+
+    fn var(&mut self, global: bool, _type: VariableType) -> Result<ScmVariable, DecodeError> {
+        Ok(ScmVariable {
+            global,
+            offset: self.u16()?,
+            _type,
+        })
+    }
+
+    fn array(&mut self, global: bool) -> Result<Value, DecodeError> {
+        let offset = self.u16()?;
+        let index = self.u16()?;
+        let size = self.u8()?;
+        let flags = self.u8()?;
+        let _type = match flags & !ARRAY_GLOBAL_INDEX {
+            ARRAY_TYPE_INT => VariableType::Int,
+            ARRAY_TYPE_FLOAT => VariableType::Float,
+            ARRAY_TYPE_STRING8 => VariableType::ShortString,
+            ARRAY_TYPE_STRING16 => VariableType::LongString,
+            t => {
+                return Err(self.error_at(self.pos - 1, format!("unknown array type {t}")));
+            }
+        };
+        Ok(Value::Array {
+            var: ScmVariable {
+                global,
+                offset,
+                _type,
+            },
+            index: ScmVariable {
+                global: flags & ARRAY_GLOBAL_INDEX != 0,
+                offset: index,
+                _type: VariableType::Unknown,
+            },
+            size,
+        })
+    }
+
+    fn float(&mut self) -> Result<f32, DecodeError> {
+        match self.game {
+            // fixed-point number: value * 16
+            Game::GTA3 | Game::VC => Ok(self.u16()? as i16 as f32 / 16.0),
+            _ => Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap())),
+        }
+    }
+
+    /// reads a zero-padded string
+    fn fixed_string(&mut self, len: usize) -> Result<String, DecodeError> {
+        let bytes = self.take(len)?;
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        Ok(bytes[..end].iter().map(|&b| b as char).collect())
+    }
+
+    fn peek(&self) -> Result<u8, DecodeError> {
+        self.bytes
+            .get(self.pos)
+            .copied()
+            .ok_or_else(|| self.error_at(self.pos, "unexpected end of data"))
+    }
+
+    fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, DecodeError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Result<i32, DecodeError> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        let end = self.pos + len;
+        if end > self.bytes.len() {
+            return Err(self.error_at(self.pos, "unexpected end of data"));
+        }
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn error_at(&self, offset: usize, message: impl Into<String>) -> DecodeError {
+        DecodeError {
+            offset,
+            message: message.into(),
+        }
+    }
+}
+
+/// decodes all instructions in the buffer
+pub fn decode(
+    bytes: &[u8],
+    ns: &Namespaces,
+    game: Game,
+) -> Result<Vec<DecodedInstruction>, DecodeError> {
+    let mut decoder = Decoder::new(bytes, game);
+    let mut instructions = vec![];
+    while !decoder.is_eof() {
+        instructions.push(decoder.next_instruction(ns)?);
+    }
+    Ok(instructions)
+}
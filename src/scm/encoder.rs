@@ -0,0 +1,586 @@
+use super::*;
+use crate::legacy_ini::Game;
+use crate::namespaces::{Command, OpId};
+use crate::v4::instruction::{Instruction, Operand, OperandType};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncodeError {
+    pub message: String,
+}
+
+impl EncodeError {
+    fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+/// writes opcodes and parameters in the format of GTA III, VC and SA
+pub struct Encoder {
+    bytes: Vec<u8>,
+    game: Game,
+}
+
+impl Encoder {
+    pub fn new(game: Game) -> Self {
+        Self {
+            bytes: vec![],
+            game,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    pub fn write_opcode(&mut self, id: OpId, not: bool) {
+        let id = if not { id | NOT_FLAG } else { id };
+        self.bytes.extend(id.to_le_bytes());
+    }
+
+    /// terminates the list of variadic arguments
+    pub fn write_end_of_args(&mut self) {
+        self.bytes.push(DataType::EndOfArgs as u8);
+    }
+
+    /// writes a value with the data type, integers use the smallest type that fits
+    pub fn write_value(&mut self, value: &Value) -> Result<(), EncodeError> {
+        match value {
+            Value::Int(v) => {
+                if let Ok(v) = i8::try_from(*v) {
+                    self.tag(DataType::Int8);
+                    self.bytes.extend(v.to_le_bytes());
+                } else if let Ok(v) = i16::try_from(*v) {
+                    self.tag(DataType::Int16);
+                    self.bytes.extend(v.to_le_bytes());
+                } else {
+                    self.write_int32(*v);
+                }
+            }
+            Value::Float(v) => {
+                self.tag(DataType::Float);
+                self.float(*v)?;
+            }
+            Value::Var(var) => {
+                let data_type = match (var.global, &var._type) {
+                    (true, VariableType::ShortString) => DataType::GlobalVarString8,
+                    (false, VariableType::ShortString) => DataType::LocalVarString8,
+                    (true, VariableType::LongString) => DataType::GlobalVarString16,
+                    (false, VariableType::LongString) => DataType::LocalVarString16,
+                    (true, _) => DataType::GlobalVar,
+                    (false, _) => DataType::LocalVar,
+                };
+                self.sa_only(data_type)?;
+                self.tag(data_type);
+                self.bytes.extend(var.offset.to_le_bytes());
+            }
+            Value::Array { var, index, size } => {
+                let (data_type, element_type) = match (var.global, &var._type) {
+                    (true, VariableType::ShortString) => {
+                        (DataType::GlobalArrayString8, ARRAY_TYPE_STRING8)
+                    }
+                    (false, VariableType::ShortString) => {
+                        (DataType::LocalArrayString8, ARRAY_TYPE_STRING8)
+                    }
+                    (true, VariableType::LongString) => {
+                        (DataType::GlobalArrayString16, ARRAY_TYPE_STRING16)
+                    }
+                    (false, VariableType::LongString) => {
+                        (DataType::LocalArrayString16, ARRAY_TYPE_STRING16)
+                    }
+                    (true, VariableType::Float) => (DataType::GlobalArray, ARRAY_TYPE_FLOAT),
+                    (false, VariableType::Float) => (DataType::LocalArray, ARRAY_TYPE_FLOAT),
+                    (true, _) => (DataType::GlobalArray, ARRAY_TYPE_INT),
+                    (false, _) => (DataType::LocalArray, ARRAY_TYPE_INT),
+                };
+                self.sa_only(data_type)?;
+                self.tag(data_type);
+                self.bytes.extend(var.offset.to_le_bytes());
+                self.bytes.extend(index.offset.to_le_bytes());
+                self.bytes.push(*size);
+                let flags = if index.global {
+                    element_type | ARRAY_GLOBAL_INDEX
+                } else {
+                    element_type
+                };
+                self.bytes.push(flags);
+            }
+            Value::String8(s) => {
+                // text labels in GTA III and VC have no data type
+                if !matches!(self.game, Game::GTA3 | Game::VC) {
+                    self.tag(DataType::String8);
+                }
+                self.fixed_string(s, 8)?;
+            }
+            Value::String16(s) => {
+                self.sa_only(DataType::String16)?;
+                self.tag(DataType::String16);
+                self.fixed_string(s, 16)?;
+            }
+            Value::VarString(s) => {
+                self.sa_only(DataType::VarString)?;
+                let Ok(len) = u8::try_from(s.len()) else {
+                    return Err(EncodeError::new(format!(
+                        "string is too long ({} bytes), max 255",
+                        s.len()
+                    )));
+                };
+                self.tag(DataType::VarString);
+                self.bytes.push(len);
+                self.bytes.extend(s.bytes());
+            }
+            Value::Byte128(s) => self.fixed_string(s, 128)?,
+        }
+        Ok(())
+    }
+
+    /// writes the opcode and its arguments (inputs, then outputs) using the parameter types of the command
+    pub fn write_instruction(
+        &mut self,
+        id: OpId,
+        not: bool,
+        command: &Command,
+        args: &[Value],
+    ) -> Result<(), EncodeError> {
+        self.write_opcode(id, not);
+
+        let mut params = command.input.iter().chain(command.output.iter());
+        let mut variadic = false;
+        for arg in args {
+            let _type = if variadic {
+                "any"
+            } else {
+                match params.next() {
+                    Some(param) => param.r#type.as_str(),
+                    None => {
+                        return Err(EncodeError::new(format!(
+                            "too many arguments for {}",
+                            command.name
+                        )))
+                    }
+                }
+            };
+            match (_type.to_ascii_lowercase().as_str(), arg) {
+                ("arguments", arg) => {
+                    variadic = true;
+                    self.write_value(arg)?;
+                }
+                ("label", Value::Int(v)) => self.write_int32(*v),
+                ("string128", Value::String8(s) | Value::VarString(s)) => {
+                    self.write_value(&Value::Byte128(s.clone()))?
+                }
+                (_, arg) => self.write_value(arg)?,
+            }
+        }
+        // a variadic command called without extra arguments still needs the terminator
+        if !variadic {
+            variadic = params
+                .next()
+                .is_some_and(|param| param.r#type.eq_ignore_ascii_case("arguments"));
+        }
+        if variadic {
+            self.write_end_of_args();
+        }
+        Ok(())
+    }
+
+    /// labels and other values that must keep their size are written as 4-byte integers
+    pub fn write_int32(&mut self, v: i32) {
+        self.tag(DataType::Int32);
+        self.bytes.extend(v.to_le_bytes());
+    }
+
+    fn tag(&mut self, data_type: DataType) {
+        self.bytes.push(data_type as u8);
+    }
+
+    fn sa_only(&self, data_type: DataType) -> Result<(), EncodeError> {
+        let supported = match self.game {
+            Game::GTA3 | Game::VC => matches!(data_type, DataType::GlobalVar | DataType::LocalVar),
+            _ => true,
+        };
+        if supported {
+            Ok(())
+        } else {
+            Err(EncodeError::new(format!(
+                "{data_type:?} is not supported in {:?}",
+                self.game
+            )))
+        }
+    }
+
+    fn float(&mut self, v: f32) -> Result<(), EncodeError> {
+        match self.game {
+            Game::GTA3 | Game::VC => {
+                // fixed-point number: value * 16
+                let fixed = (v * 16.0).round();
+                if fixed < i16::MIN as f32 || fixed > i16::MAX as f32 {
+                    return Err(EncodeError::new(format!("float {v} is out of range")));
+                }
+                self.bytes.extend((fixed as i16).to_le_bytes());
+            }
+            _ => self.bytes.extend(v.to_le_bytes()),
+        }
+        Ok(())
+    }
+
+    /// writes a zero-padded string
+    fn fixed_string(&mut self, s: &str, len: usize) -> Result<(), EncodeError> {
+        if s.len() > len {
+            return Err(EncodeError::new(format!(
+                "string '{s}' is too long, max {len} bytes"
+            )));
+        }
+        self.bytes.extend(s.bytes());
+        self.bytes.resize(self.bytes.len() + len - s.len(), 0);
+        Ok(())
+    }
+}
+
+/// converts an operand of a v4 instruction into a value.
+/// only numbered variables are supported (`$10`, `0@`, `s$2`, `0@v`, `$10(0@,4i)`),
+/// `'text'` is a fixed 8 or 16-byte string depending on its length, `"text"` is prefixed with its length.
+/// `label` returns the offset of a label by its name
+pub fn operand_to_value<F>(operand: &Operand, label: &F) -> Result<Value, EncodeError>
+where
+    F: Fn(&str) -> Option<i32>,
+{
+    let unsupported = || EncodeError::new(format!("can't encode operand {}", operand.value));
+    match operand._type {
+        OperandType::IntLiteral => operand.as_int().map(Value::Int).ok_or_else(unsupported),
+        OperandType::FloatLiteral => operand.as_float().map(Value::Float).ok_or_else(unsupported),
+        OperandType::Label => {
+            let name = operand.value.trim_start_matches('@');
+            label(name)
+                .map(Value::Int)
+                .ok_or_else(|| EncodeError::new(format!("unknown label {name}")))
+        }
+        OperandType::Variable => parse_var(&operand.value)
+            .map(Value::Var)
+            .ok_or_else(unsupported),
+        OperandType::ArrayElement => parse_array(&operand.value).ok_or_else(unsupported),
+        OperandType::String => match operand.as_string().ok_or_else(unsupported)? {
+            (s, true) => Ok(Value::VarString(s)),
+            (s, false) if s.len() <= 8 => Ok(Value::String8(s)),
+            (s, false) => Ok(Value::String16(s)),
+        },
+    }
+}
+
+fn parse_var(s: &str) -> Option<ScmVariable> {
+    if let Some(local) = s.strip_suffix("@s") {
+        return local_var(local, VariableType::ShortString);
+    }
+    if let Some(local) = s.strip_suffix("@v") {
+        return local_var(local, VariableType::LongString);
+    }
+    if let Some(local) = s.strip_suffix('@') {
+        return local_var(local, VariableType::Unknown);
+    }
+    let (_type, global) = if let Some(g) = s.strip_prefix("s$") {
+        (VariableType::ShortString, g)
+    } else if let Some(g) = s.strip_prefix("v$") {
+        (VariableType::LongString, g)
+    } else {
+        (VariableType::Unknown, s.strip_prefix('$')?)
+    };
+    let index = global.parse::<u16>().ok()?;
+    Some(ScmVariable {
+        global: true,
+        // global variables are 4 bytes each
+        offset: index.checked_mul(4)?,
+        _type,
+    })
+}
+
+fn local_var(s: &str, _type: VariableType) -> Option<ScmVariable> {
+    Some(ScmVariable {
+        global: false,
+        offset: s.parse().ok()?,
+        _type,
+    })
+}
+
+/// `$10(0@,4i)`
+fn parse_array(s: &str) -> Option<Value> {
+    let (base, rest) = s.split_once('(')?;
+    let (index, rest) = rest.strip_suffix(')')?.split_once(',')?;
+    let (size, _type) = rest.split_at(rest.len().checked_sub(1)?);
+    let _type = match _type {
+        "i" => VariableType::Int,
+        "f" => VariableType::Float,
+        "s" => VariableType::ShortString,
+        "v" => VariableType::LongString,
+        _ => return None,
+    };
+    let var = parse_var(base)?;
+    Some(Value::Array {
+        var: ScmVariable { _type, ..var },
+        index: parse_var(index)?,
+        size: size.parse().ok()?,
+    })
+}
+
+/// encodes a v4 instruction using the parameter types of its command,
+/// opcodes without a library definition can't be encoded
+pub fn encode_instruction<F>(
+    instruction: &Instruction,
+    game: Game,
+    label: &F,
+) -> Result<Vec<u8>, EncodeError>
+where
+    F: Fn(&str) -> Option<i32>,
+{
+    let Some(command) = instruction.command else {
+        return Err(EncodeError::new(format!(
+            "unknown parameter types of opcode {:04X}",
+            instruction.id
+        )));
+    };
+    let args = instruction
+        .operands
+        .iter()
+        .map(|operand| operand_to_value(operand, label))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut encoder = Encoder::new(game);
+    encoder.write_instruction(instruction.id, instruction.not, command, &args)?;
+    Ok(encoder.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::namespaces::namespaces::Namespaces;
+    use crate::scm::decoder::decode;
+
+    #[test]
+    fn test_fixture() {
+        let mut ns = Namespaces::new();
+        ns.load_library("sa.json");
+        let bytes = std::fs::read("src/decompiler/test/sample.cs").unwrap();
+
+        // decoding and encoding the compiled script gives the same bytes
+        let mut e = Encoder::new(Game::SA);
+        for i in decode(&bytes, &ns, Game::SA).unwrap() {
+            let command = ns.get_command(i.id).unwrap();
+            e.write_instruction(i.id, i.not, command, &i.args).unwrap();
+        }
+        assert_eq!(e.into_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_int_width() {
+        let mut e = Encoder::new(Game::SA);
+        e.write_value(&Value::Int(0)).unwrap();
+        e.write_value(&Value::Int(-129)).unwrap();
+        e.write_value(&Value::Int(100000)).unwrap();
+        assert_eq!(
+            e.into_bytes(),
+            vec![0x04, 0x00, 0x05, 0x7F, 0xFF, 0x01, 0xA0, 0x86, 0x01, 0x00]
+        );
+    }
+
+    #[test]
+    fn test_strings() {
+        let mut e = Encoder::new(Game::SA);
+        e.write_value(&Value::String8(String::from("HELP1")))
+            .unwrap();
+        e.write_value(&Value::VarString(String::from("abc")))
+            .unwrap();
+        assert_eq!(
+            e.into_bytes(),
+            vec![0x09, b'H', b'E', b'L', b'P', b'1', 0, 0, 0, 0x0E, 3, b'a', b'b', b'c']
+        );
+
+        // no data type in vc
+        let mut e = Encoder::new(Game::VC);
+        e.write_value(&Value::String8(String::from("HELP1")))
+            .unwrap();
+        assert_eq!(e.into_bytes(), b"HELP1\0\0\0".to_vec());
+
+        let mut e = Encoder::new(Game::SA);
+        e.write_value(&Value::Byte128(String::from("debug")))
+            .unwrap();
+        assert_eq!(e.len(), 128);
+
+        let e = Encoder::new(Game::SA)
+            .write_value(&Value::String8(String::from("TOO_LONG_KEY")))
+            .unwrap_err();
+        assert_eq!(e.message, "string 'TOO_LONG_KEY' is too long, max 8 bytes");
+
+        let e = Encoder::new(Game::VC)
+            .write_value(&Value::VarString(String::from("abc")))
+            .unwrap_err();
+        assert_eq!(e.message, "VarString is not supported in VC");
+    }
+
+    #[test]
+    fn test_arrays_and_floats() {
+        let mut e = Encoder::new(Game::SA);
+        e.write_value(&parse_array("$10(0@,5i)").unwrap()).unwrap();
+        e.write_value(&parse_array("0@($1,3f)").unwrap()).unwrap();
+        e.write_value(&parse_array("s$2(1@,4s)").unwrap()).unwrap();
+        e.write_value(&Value::Float(1.0)).unwrap();
+        assert_eq!(
+            e.into_bytes(),
+            vec![
+                0x07, 40, 0, 0, 0, 5, 0x00, //
+                0x08, 0, 0, 4, 0, 3, 0x81, //
+                0x0C, 8, 0, 1, 0, 4, 0x02, //
+                0x06, 0x00, 0x00, 0x80, 0x3F,
+            ]
+        );
+
+        let mut e = Encoder::new(Game::VC);
+        e.write_value(&Value::Float(2.5)).unwrap();
+        assert_eq!(e.into_bytes(), vec![0x06, 0x28, 0x00]);
+    }
+
+    #[test]
+    fn test_encode_instruction() {
+        let mut ns = Namespaces::new();
+        ns.load_library("sa.json");
+        let label = |name: &str| (name == "start").then_some(-4);
+
+        let op = |_type, value: &str| Operand::new(_type, value);
+
+        // round trip through the decoder
+        let i = Instruction::new(
+            &ns,
+            0x00A5,
+            vec![
+                op(OperandType::IntLiteral, "400"),
+                op(OperandType::FloatLiteral, "1.0"),
+                op(OperandType::FloatLiteral, "-2.5"),
+                op(OperandType::FloatLiteral, "300.0"),
+                op(OperandType::Variable, "$2"),
+            ],
+        );
+        let bytes = encode_instruction(&i, Game::SA, &label).unwrap();
+        let decoded = decode(&bytes, &ns, Game::SA).unwrap();
+        assert_eq!(
+            decoded[0].args,
+            vec![
+                Value::Int(400),
+                Value::Float(1.0),
+                Value::Float(-2.5),
+                Value::Float(300.0),
+                Value::Var(ScmVariable {
+                    global: true,
+                    offset: 8,
+                    _type: VariableType::Unknown
+                })
+            ]
+        );
+
+        // labels are always 4 bytes, variadic arguments end with 0
+        let i = Instruction::new(
+            &ns,
+            0x004F,
+            vec![
+                op(OperandType::Label, "@start"),
+                op(OperandType::IntLiteral, "1"),
+                op(OperandType::Variable, "0@"),
+            ],
+        );
+        assert_eq!(
+            encode_instruction(&i, Game::SA, &label).unwrap(),
+            vec![0x4F, 0x00, 0x01, 0xFC, 0xFF, 0xFF, 0xFF, 0x04, 0x01, 0x03, 0x00, 0x00, 0x00]
+        );
+
+        // no extra arguments
+        let i = Instruction::new(&ns, 0x004F, vec![op(OperandType::Label, "@start")]);
+        let bytes = encode_instruction(&i, Game::SA, &label).unwrap();
+        assert_eq!(bytes, vec![0x4F, 0x00, 0x01, 0xFC, 0xFF, 0xFF, 0xFF, 0x00]);
+        let decoded = decode(&bytes, &ns, Game::SA).unwrap();
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0].args, vec![Value::Int(-4)]);
+
+        let i = Instruction::new(
+            &ns,
+            0x0AB1,
+            vec![
+                op(OperandType::Label, "@start"),
+                op(OperandType::IntLiteral, "0"),
+            ],
+        );
+        let bytes = encode_instruction(&i, Game::SA, &label).unwrap();
+        assert_eq!(
+            bytes,
+            vec![0xB1, 0x0A, 0x01, 0xFC, 0xFF, 0xFF, 0xFF, 0x04, 0x00, 0x00]
+        );
+        let decoded = decode(&bytes, &ns, Game::SA).unwrap();
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0].args, vec![Value::Int(-4), Value::Int(0)]);
+
+        let i = Instruction::new(&ns, 0x0002, vec![op(OperandType::Label, "@unknown")]);
+        assert_eq!(
+            encode_instruction(&i, Game::SA, &label)
+                .unwrap_err()
+                .message,
+            "unknown label unknown"
+        );
+
+        let i = Instruction::new(&ns, 0x0001, vec![op(OperandType::Variable, "$named")]);
+        assert_eq!(
+            encode_instruction(&i, Game::SA, &label)
+                .unwrap_err()
+                .message,
+            "can't encode operand $named"
+        );
+
+        let empty = Namespaces::new();
+        let i = Instruction::new(&empty, 0x0001, vec![op(OperandType::IntLiteral, "0")]);
+        assert_eq!(
+            encode_instruction(&i, Game::SA, &label)
+                .unwrap_err()
+                .message,
+            "unknown parameter types of opcode 0001"
+        );
+
+        // short strings are fixed, long strings are prefixed with the length
+        let i = Instruction::new(
+            &ns,
+            0x05A9,
+            vec![
+                op(OperandType::Variable, "s$3"),
+                op(OperandType::String, r"'A\x42C'"),
+            ],
+        );
+        let bytes = encode_instruction(&i, Game::SA, &label).unwrap();
+        let decoded = decode(&bytes, &ns, Game::SA).unwrap();
+        assert_eq!(decoded[0].args[1], Value::String8(String::from("ABC")));
+
+        let i = Instruction::new(
+            &ns,
+            0x06D1,
+            vec![
+                op(OperandType::Variable, "v$3"),
+                op(OperandType::String, "\"long text\""),
+            ],
+        );
+        let bytes = encode_instruction(&i, Game::SA, &label).unwrap();
+        let decoded = decode(&bytes, &ns, Game::SA).unwrap();
+        assert_eq!(
+            decoded[0].args[1],
+            Value::VarString(String::from("long text"))
+        );
+    }
+}
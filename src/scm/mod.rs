@@ -0,0 +1,102 @@
+pub mod decoder;
+pub mod encoder;
+
+use crate::parser::interface::VariableType;
+
+/// the data type tag preceding each parameter in the compiled script
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum DataType {
+    /// end of the variadic argument list
+    EndOfArgs = 0x00,
+    Int32 = 0x01,
+    GlobalVar = 0x02,
+    LocalVar = 0x03,
+    Int8 = 0x04,
+    Int16 = 0x05,
+    Float = 0x06,
+    GlobalArray = 0x07,
+    LocalArray = 0x08,
+    String8 = 0x09,
+    GlobalVarString8 = 0x0A,
+    LocalVarString8 = 0x0B,
+    GlobalArrayString8 = 0x0C,
+    LocalArrayString8 = 0x0D,
+    VarString = 0x0E,
+    String16 = 0x0F,
+    GlobalVarString16 = 0x10,
+    LocalVarString16 = 0x11,
+    GlobalArrayString16 = 0x12,
+    LocalArrayString16 = 0x13,
+}
+
+impl TryFrom<u8> for DataType {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        use DataType::*;
+        Ok(match value {
+            0x00 => EndOfArgs,
+            0x01 => Int32,
+            0x02 => GlobalVar,
+            0x03 => LocalVar,
+            0x04 => Int8,
+            0x05 => Int16,
+            0x06 => Float,
+            0x07 => GlobalArray,
+            0x08 => LocalArray,
+            0x09 => String8,
+            0x0A => GlobalVarString8,
+            0x0B => LocalVarString8,
+            0x0C => GlobalArrayString8,
+            0x0D => LocalArrayString8,
+            0x0E => VarString,
+            0x0F => String16,
+            0x10 => GlobalVarString16,
+            0x11 => LocalVarString16,
+            0x12 => GlobalArrayString16,
+            0x13 => LocalArrayString16,
+            _ => return Err(value),
+        })
+    }
+}
+
+/// element type stored in the flags byte of an array (SA)
+pub const ARRAY_TYPE_INT: u8 = 0;
+pub const ARRAY_TYPE_FLOAT: u8 = 1;
+pub const ARRAY_TYPE_STRING8: u8 = 2;
+pub const ARRAY_TYPE_STRING16: u8 = 3;
+/// set in the flags byte if the index variable is global
+pub const ARRAY_GLOBAL_INDEX: u8 = 0x80;
+
+/// the highest bit of the opcode inverts the result of a condition
+pub const NOT_FLAG: u16 = 0x8000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScmVariable {
+    pub global: bool,
+    /// byte offset for global variables, index for local variables
+    pub offset: u16,
+    pub _type: VariableType,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i32),
+    Float(f32),
+    Var(ScmVariable),
+    /// `var` is the first element of the array, its type is the type of the elements
+    Array {
+        var: ScmVariable,
+        index: ScmVariable,
+        size: u8,
+    },
+    /// fixed 8-byte string, e.g. a GXT key
+    String8(String),
+    /// fixed 16-byte string
+    String16(String),
+    /// string prefixed with its length
+    VarString(String),
+    /// fixed 128-byte buffer without a data type (SAVE_STRING_TO_DEBUG_FILE)
+    Byte128(String),
+}
@@ -1,13 +1,22 @@
+#[cfg(windows)]
 use winapi::um::winuser::PostMessageA;
 
-pub const WM_ONSTATUSCHANGE: u32 = winapi::um::winuser::WM_USER + 1048;
-pub const WM_ONSHOWTEXTBOX: u32 = winapi::um::winuser::WM_USER + 1049;
-pub const WM_CHANGETITLE: u32 = winapi::um::winuser::WM_USER + 1050;
-pub const WM_RESETTITLE: u32 = winapi::um::winuser::WM_USER + 1051;
-pub const WM_OPENFILE: u32 = winapi::um::winuser::WM_USER + 1052;
+/// winapi::um::winuser::WM_USER, the module is only available on Windows
+const WM_USER: u32 = 0x0400;
 
+pub const WM_ONSTATUSCHANGE: u32 = WM_USER + 1048;
+pub const WM_ONSHOWTEXTBOX: u32 = WM_USER + 1049;
+pub const WM_CHANGETITLE: u32 = WM_USER + 1050;
+pub const WM_RESETTITLE: u32 = WM_USER + 1051;
+pub const WM_OPENFILE: u32 = WM_USER + 1052;
+
+#[cfg(windows)]
 pub fn send_message(message: u32, wparam: usize, lparam: isize) {
     unsafe {
         PostMessageA(*super::GET_WORKBOOK_HANDLE as _, message, wparam, lparam);
     }
 }
+
+/// there is no IDE window to notify outside of Windows
+#[cfg(not(windows))]
+pub fn send_message(_message: u32, _wparam: usize, _lparam: isize) {}
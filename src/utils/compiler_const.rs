@@ -22,4 +22,10 @@ pub const TOKEN_FOR: i32 = 70;
 pub const TOKEN_WHILE: i32 = 71;
 pub const TOKEN_SWITCH: i32 = 75;
 
+// block parts
+pub const TOKEN_DEFAULT: i32 = 46;
+pub const TOKEN_THEN: i32 = 51;
+pub const TOKEN_ELSE: i32 = 52;
+pub const TOKEN_CASE: i32 = 76;
+
 pub const TOKEN_END: i32 = 255;
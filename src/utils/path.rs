@@ -1,34 +1,110 @@
 use std::path::{Path, PathBuf};
 
-pub fn resolve_path(p: &str, parent_file: Option<&PathBuf>) -> Option<PathBuf> {
+/// a relative path that was not found in any of the search locations
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathNotFound {
+    pub path: String,
+    /// every location that was checked, in the search order
+    pub tried: Vec<PathBuf>,
+}
+
+impl std::fmt::Display for PathNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Can't find file {}", self.path)?;
+        if !self.tried.is_empty() {
+            write!(f, ", tried:")?;
+            for path in &self.tried {
+                write!(f, "\n  {}", path.display())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for PathNotFound {}
+
+/// If the file path is relative, the directories are scanned in the following order to find the file:
+/// 1. directory of the file with the directive
+/// 2. each of the search roots, e.g. the data folder for the current edit mode, Sanny Builder root directory, the game directory
+pub fn find_path(
+    p: &str,
+    parent_file: Option<&Path>,
+    search_roots: &[PathBuf],
+) -> Result<PathBuf, PathNotFound> {
     let path = Path::new(p);
 
     if path.is_absolute() {
-        // return Some(p.to_string());
-        return normalize_file_name(path);
+        return Ok(path.to_path_buf());
     }
 
-    match parent_file {
-        Some(x) => {
-            // todo:
-            // If the file path is relative, the compiler scans directories in the following order to find the file:
-            // 1. directory of the file with the directive
-            // 2. data folder for the current edit mode
-            // 3. Sanny Builder root directory
-            // 4. the game directory
-            let dir_name = Path::new(&x).parent()?;
-            let abs_name = dir_name.join(path);
-
-            // Some(String::from(abs_name.to_str()?))
-            normalize_file_name(&abs_name)
-        }
-        None => None,
+    let tried = parent_file
+        .and_then(|x| x.parent())
+        .into_iter()
+        .chain(search_roots.iter().map(PathBuf::as_path))
+        .map(|dir_name| dir_name.join(path))
+        .collect::<Vec<_>>();
+
+    match tried.iter().find(|x| x.is_file()) {
+        Some(x) => Ok(x.clone()),
+        None => Err(PathNotFound {
+            path: String::from(p),
+            tried,
+        }),
     }
 }
 
+pub fn resolve_path(
+    p: &str,
+    parent_file: Option<&PathBuf>,
+    search_roots: &[PathBuf],
+) -> Result<PathBuf, PathNotFound> {
+    let path = find_path(p, parent_file.map(PathBuf::as_path), search_roots)?;
+    normalize_file_name(&path).ok_or(PathNotFound {
+        path: String::from(p),
+        tried: vec![path],
+    })
+}
 
-
+#[cfg(windows)]
 pub fn normalize_file_name(file_name: &Path) -> Option<PathBuf> {
     use normpath::PathExt;
     Some(file_name.normalize_virtually().ok()?.into_path_buf())
 }
+
+/// makes the path absolute and removes `.` and `..` without touching the file system, like `normalize_virtually` does on Windows
+#[cfg(not(windows))]
+pub fn normalize_file_name(file_name: &Path) -> Option<PathBuf> {
+    use std::path::Component;
+
+    let mut path = PathBuf::new();
+    for component in std::env::current_dir().ok()?.join(file_name).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                path.pop();
+            }
+            c => path.push(c),
+        }
+    }
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_normalize_file_name() {
+        let p = |s: &str| normalize_file_name(Path::new(s)).unwrap();
+        assert_eq!(
+            p("/dev/scripts/./lib/../main.txt"),
+            PathBuf::from("/dev/scripts/main.txt")
+        );
+        assert_eq!(p("/dev/../../main.txt"), PathBuf::from("/main.txt"));
+        assert_eq!(
+            p("main.txt"),
+            std::env::current_dir().unwrap().join("main.txt")
+        );
+    }
+}
@@ -0,0 +1,277 @@
+use super::helpers::token_str;
+use crate::parser::{interface::*, parse_expression};
+
+/// max depth of constants referencing other constants
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Value {
+    Int(i32),
+    Float(f32),
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Int(v) => write!(f, "{v}"),
+            Value::Float(v) => write!(f, "{}", format_float(*v)),
+        }
+    }
+}
+
+/// float literal the parser can read back: no exponent (0.0000001, not 1e-7)
+/// and always with the fractional part (100.0, not 100)
+pub fn format_float(v: f32) -> String {
+    let text = v.to_string();
+    if text.contains('.') || !v.is_finite() {
+        text
+    } else {
+        format!("{text}.0")
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum EvalError {
+    /// the expression references a variable, a label or an unknown name
+    NotConstant(String),
+    /// the result of the operation does not fit into a 32-bit integer
+    Overflow(String),
+    DivisionByZero(String),
+    /// int and float operands are mixed or the operator is not defined for floats
+    TypeMismatch(String),
+    /// the constant references itself or the chain of constants is too deep
+    Recursion(String),
+}
+
+impl std::fmt::Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalError::NotConstant(e) => write!(f, "`{e}` is not a constant expression"),
+            EvalError::Overflow(e) => write!(f, "integer overflow in `{e}`"),
+            EvalError::DivisionByZero(e) => write!(f, "division by zero in `{e}`"),
+            EvalError::TypeMismatch(e) => write!(f, "type mismatch in `{e}`"),
+            EvalError::Recursion(e) => write!(f, "recursive constant `{e}`"),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+/// parses an integer literal in decimal, hex (0x) or binary (0b) form.
+/// hex and binary literals may exceed i32::MAX (0xFFFFFFFF)
+pub fn parse_int(s: &str) -> Option<i32> {
+    let (negative, value) = match s.strip_prefix('-') {
+        Some(v) => (true, v),
+        None => (false, s),
+    };
+    let lower = value.to_ascii_lowercase();
+    let n = if let Some(hex) = lower.strip_prefix("0x") {
+        u32::from_str_radix(hex, 16).ok()? as i32 as i64
+    } else if let Some(bin) = lower.strip_prefix("0b") {
+        u32::from_str_radix(bin, 2).ok()? as i32 as i64
+    } else {
+        lower.parse::<i64>().ok()?
+    };
+    let n = if negative { -n } else { n };
+    i32::try_from(n).ok()
+}
+
+/// evaluates a constant expression at compile time.
+/// `lookup` returns the source text of a constant by its lower-case name
+pub fn evaluate<F>(node: &Node, expr: &str, lookup: &F) -> Result<Value, EvalError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    eval(node, expr, lookup, 0)
+}
+
+/// parses and evaluates the source text of a constant expression, e.g. `WIDTH * 2 + 1`
+pub fn evaluate_str<F>(expr: &str, lookup: &F) -> Result<Value, EvalError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    eval_str(expr, lookup, 0)
+}
+
+fn eval_str<F>(expr: &str, lookup: &F, depth: usize) -> Result<Value, EvalError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let not_constant = || EvalError::NotConstant(expr.trim().to_string());
+    let (_, node) = parse_expression(expr).map_err(|_| not_constant())?;
+    eval(&node, expr, lookup, depth)
+}
+
+fn eval<F>(node: &Node, expr: &str, lookup: &F, depth: usize) -> Result<Value, EvalError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let text = |token: &Token| token_str(expr, token).to_string();
+
+    match node {
+        Node::Literal(token) => match token.syntax_kind {
+            SyntaxKind::IntegerLiteral => parse_int(token_str(expr, token))
+                .map(Value::Int)
+                .ok_or_else(|| EvalError::Overflow(text(token))),
+            SyntaxKind::FloatLiteral => token_str(expr, token)
+                .parse::<f32>()
+                .map(Value::Float)
+                .map_err(|_| EvalError::NotConstant(text(token))),
+            SyntaxKind::Identifier => {
+                let name = token_str(expr, token);
+                if depth >= MAX_DEPTH {
+                    return Err(EvalError::Recursion(name.to_string()));
+                }
+                let value = lookup(&name.to_ascii_lowercase())
+                    .ok_or_else(|| EvalError::NotConstant(name.to_string()))?;
+                eval_str(&value, lookup, depth + 1)
+            }
+            _ => Err(EvalError::NotConstant(text(token))),
+        },
+        Node::Unary(e) => {
+            let value = eval(&e.operand, expr, lookup, depth)?;
+            match (e.get_operator(), value) {
+                (SyntaxKind::OperatorMinus, Value::Int(v)) => v
+                    .checked_neg()
+                    .map(Value::Int)
+                    .ok_or_else(|| EvalError::Overflow(text(&e.token))),
+                (SyntaxKind::OperatorMinus, Value::Float(v)) => Ok(Value::Float(-v)),
+                (SyntaxKind::OperatorBitwiseNot, Value::Int(v)) => Ok(Value::Int(!v)),
+                (SyntaxKind::OperatorBitwiseNot, Value::Float(_)) => {
+                    Err(EvalError::TypeMismatch(text(&e.token)))
+                }
+                _ => Err(EvalError::NotConstant(text(&e.token))),
+            }
+        }
+        Node::Binary(e) => {
+            let left = eval(&e.left, expr, lookup, depth)?;
+            let right = eval(&e.right, expr, lookup, depth)?;
+            let source = || text(&e.token);
+            match (left, right) {
+                (Value::Int(l), Value::Int(r)) => {
+                    let result = match e.get_operator() {
+                        SyntaxKind::OperatorPlus => l.checked_add(r),
+                        SyntaxKind::OperatorMinus => l.checked_sub(r),
+                        SyntaxKind::OperatorMul => l.checked_mul(r),
+                        SyntaxKind::OperatorDiv | SyntaxKind::OperatorBitwiseMod if r == 0 => {
+                            return Err(EvalError::DivisionByZero(source()));
+                        }
+                        SyntaxKind::OperatorDiv => l.checked_div(r),
+                        SyntaxKind::OperatorBitwiseMod => l.checked_rem(r),
+                        SyntaxKind::OperatorBitwiseAnd => Some(l & r),
+                        SyntaxKind::OperatorBitwiseOr => Some(l | r),
+                        SyntaxKind::OperatorBitwiseXor => Some(l ^ r),
+                        SyntaxKind::OperatorBitwiseShl => {
+                            u32::try_from(r).ok().and_then(|r| l.checked_shl(r))
+                        }
+                        SyntaxKind::OperatorBitwiseShr => {
+                            u32::try_from(r).ok().and_then(|r| l.checked_shr(r))
+                        }
+                        _ => return Err(EvalError::NotConstant(source())),
+                    };
+                    result
+                        .map(Value::Int)
+                        .ok_or_else(|| EvalError::Overflow(source()))
+                }
+                (Value::Float(l), Value::Float(r)) => {
+                    let result = match e.get_operator() {
+                        SyntaxKind::OperatorPlus => l + r,
+                        SyntaxKind::OperatorMinus => l - r,
+                        SyntaxKind::OperatorMul => l * r,
+                        SyntaxKind::OperatorDiv if r == 0.0 => {
+                            return Err(EvalError::DivisionByZero(source()));
+                        }
+                        SyntaxKind::OperatorDiv => l / r,
+                        SyntaxKind::OperatorBitwiseAnd
+                        | SyntaxKind::OperatorBitwiseOr
+                        | SyntaxKind::OperatorBitwiseXor
+                        | SyntaxKind::OperatorBitwiseMod
+                        | SyntaxKind::OperatorBitwiseShl
+                        | SyntaxKind::OperatorBitwiseShr => {
+                            return Err(EvalError::TypeMismatch(source()));
+                        }
+                        _ => return Err(EvalError::NotConstant(source())),
+                    };
+                    if !result.is_finite() {
+                        return Err(EvalError::Overflow(source()));
+                    }
+                    Ok(Value::Float(result))
+                }
+                _ => Err(EvalError::TypeMismatch(source())),
+            }
+        }
+        Node::Variable(v) => {
+            let token = match v {
+                Variable::Local(v) | Variable::Global(v) => &v.token,
+                Variable::ArrayElement(v) => &v.token,
+                Variable::Indexed(v) => &v.token,
+                Variable::Adma(v) => &v.token,
+                Variable::Pop(v) | Variable::Push(v) => v,
+            };
+            Err(EvalError::NotConstant(text(token)))
+        }
+        _ => Err(EvalError::NotConstant(expr.trim().to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate() {
+        let lookup = |name: &str| -> Option<String> {
+            match name {
+                "width" => Some("10".into()),
+                "max" => Some("WIDTH * 2 + 1".into()),
+                "half" => Some("0.5".into()),
+                "var" => Some("$var".into()),
+                "self" => Some("self + 1".into()),
+                _ => None,
+            }
+        };
+        let e = |s: &str| evaluate_str(s, &lookup);
+
+        assert_eq!(e("WIDTH * 2 + 1"), Ok(Value::Int(21)));
+        assert_eq!(e("max - 1"), Ok(Value::Int(20)));
+        assert_eq!(e("-max"), Ok(Value::Int(-21)));
+        assert_eq!(e("1 + 2 * 3 - 4 / 2"), Ok(Value::Int(5)));
+        assert_eq!(e("0xFF & 0x0F | 0x100"), Ok(Value::Int(0x10F)));
+        assert_eq!(e("1 << 4 >> 2"), Ok(Value::Int(4)));
+        assert_eq!(e("~0"), Ok(Value::Int(-1)));
+        assert_eq!(e("7 % 3 ^ 1"), Ok(Value::Int(0)));
+        assert_eq!(e("0xFFFFFFFF"), Ok(Value::Int(-1)));
+        assert_eq!(e("half * 3.0"), Ok(Value::Float(1.5)));
+        assert_eq!(e("-half"), Ok(Value::Float(-0.5)));
+
+        assert_eq!(
+            e("2147483647 + 1"),
+            Err(EvalError::Overflow("2147483647 + 1".into()))
+        );
+        assert_eq!(e("1 << 32"), Err(EvalError::Overflow("1 << 32".into())));
+        assert_eq!(
+            e("WIDTH / 0"),
+            Err(EvalError::DivisionByZero("WIDTH / 0".into()))
+        );
+        assert_eq!(
+            e("WIDTH + half"),
+            Err(EvalError::TypeMismatch("WIDTH + half".into()))
+        );
+        assert_eq!(
+            e("half & 1.0"),
+            Err(EvalError::TypeMismatch("half & 1.0".into()))
+        );
+        assert_eq!(e("var + 1"), Err(EvalError::NotConstant("$var".into())));
+        assert_eq!(e("unknown"), Err(EvalError::NotConstant("unknown".into())));
+        assert_eq!(e("self"), Err(EvalError::Recursion("self".into())));
+
+        assert_eq!(Value::Float(100.0).to_string(), "100.0");
+        assert_eq!(Value::Float(1e-7).to_string(), "0.0000001");
+        assert_eq!(Value::Float(-1e20).to_string(), "-100000000000000000000.0");
+        assert_eq!(Value::Int(-5).to_string(), "-5");
+        assert_eq!(
+            EvalError::Overflow("1 << 32".into()).to_string(),
+            "integer overflow in `1 << 32`"
+        );
+    }
+}
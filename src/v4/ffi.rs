@@ -1,7 +1,11 @@
 use crate::common_ffi::{pchar_to_string, PChar};
 use crate::dictionary::dictionary_str_by_str::DictStrByStr;
+use crate::language_service::scanner::process_var_declaration;
+use crate::language_service::symbol_table::SymbolTable;
 use crate::legacy_ini::OpcodeTable;
 use crate::namespaces::namespaces::Namespaces;
+use crate::v4::helpers::token_column;
+use crate::v4::typecheck::TypeEnv;
 
 #[no_mangle]
 pub unsafe extern "C" fn v4_try_transform(
@@ -14,8 +18,316 @@ pub unsafe extern "C" fn v4_try_transform(
 ) -> bool {
     boolclosure! {{
         let input = pchar_to_string(input)?;
-        let result = super::transform(&input, ns.as_ref()?, legacy_ini.as_ref()?, const_lookup.as_ref()?)?;
+        let ns = ns.as_ref()?;
+        check_types(&input, ns)?;
+        let result = super::transform(&input, ns, legacy_ini.as_ref()?, const_lookup.as_ref()?)?;
         *out = std::ffi::CString::new(result).unwrap().into_raw();
         Some(())
     }}
 }
+
+/// same as [v4_try_transform], `var_types` maps variable names to their declared types
+/// (`int`, `float`, class names) for the cast assignment, comparisons of variables and the class syntax
+#[no_mangle]
+pub unsafe extern "C" fn v4_try_transform_with_types(
+    input: PChar,
+    ns: *const Namespaces,
+    legacy_ini: *const OpcodeTable,
+    var_types: *const DictStrByStr,
+    const_lookup: *const DictStrByStr,
+    out: *mut PChar,
+) -> bool {
+    boolclosure! {{
+        let input = pchar_to_string(input)?;
+        let symbols = declarations(var_types.as_ref()?);
+        let env = TypeEnv {
+            ns: ns.as_ref()?,
+            symbols: &symbols,
+            line_number: 0,
+        };
+        let result = super::transform_checked(&input, &env, legacy_ini.as_ref()?, const_lookup.as_ref()?)
+            .ok()??;
+        *out = std::ffi::CString::new(result.to_string()).unwrap().into_raw();
+        Some(())
+    }}
+}
+
+/// lowers the header of `if` or `while`, instructions in `out` are separated by new lines.
+/// `var_types` is the same as in [v4_try_transform_with_types]
+#[no_mangle]
+pub unsafe extern "C" fn v4_try_transform_conditions(
+    input: PChar,
+    ns: *const Namespaces,
+    var_types: *const DictStrByStr,
+    const_lookup: *const DictStrByStr,
+    out: *mut PChar,
+) -> bool {
+    boolclosure! {{
+        let input = pchar_to_string(input)?;
+        let symbols = declarations(var_types.as_ref()?);
+        let env = TypeEnv {
+            ns: ns.as_ref()?,
+            symbols: &symbols,
+            line_number: 0,
+        };
+        super::type_check(&input, &env).is_empty().then_some(())?;
+        let result = super::transform_conditions(&input, &env, const_lookup.as_ref()?)?
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        *out = std::ffi::CString::new(result).unwrap().into_raw();
+        Some(())
+    }}
+}
+
+/// `temps` is a space-separated list of variables for intermediate results,
+/// instructions in `out` are separated by new lines
+#[no_mangle]
+pub unsafe extern "C" fn v4_try_transform_sequence(
+    input: PChar,
+    ns: *const Namespaces,
+    legacy_ini: *const OpcodeTable,
+    const_lookup: *const DictStrByStr,
+    temps: PChar,
+    out: *mut PChar,
+) -> bool {
+    boolclosure! {{
+        let input = pchar_to_string(input)?;
+        let ns = ns.as_ref()?;
+        check_types(&input, ns)?;
+        let temps = pchar_to_string(temps)?;
+        let mut temps = super::TempPool::new(temps.split_whitespace());
+        let result = super::transform_sequence(
+            &input,
+            ns,
+            legacy_ini.as_ref()?,
+            const_lookup.as_ref()?,
+            &mut temps,
+        )?
+        .iter()
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+        *out = std::ffi::CString::new(result).unwrap().into_raw();
+        Some(())
+    }}
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn v4_get_parse_error(
+    input: PChar,
+    out_line: *mut u32,
+    out_column: *mut u32,
+    out_message: *mut PChar,
+) -> bool {
+    boolclosure! {{
+        let input = pchar_to_string(input)?;
+        let e = super::parse_error(&input)?;
+        *out_line = e.line;
+        *out_column = e.column as u32;
+        *out_message = std::ffi::CString::new(e.message).ok()?.into_raw();
+        Some(())
+    }}
+}
+
+/// if [v4_try_transform] gives no result because of an unknown enum member,
+/// `out_message` receives the reason with the closest member names
+#[no_mangle]
+pub unsafe extern "C" fn v4_get_enum_error(
+    input: PChar,
+    ns: *const Namespaces,
+    out_column: *mut usize,
+    out_message: *mut PChar,
+) -> bool {
+    boolclosure! {{
+        let input = pchar_to_string(input)?;
+        let e = super::enum_error(&input, ns.as_ref()?)?;
+        *out_column = token_column(&input, &e.token);
+        *out_message = std::ffi::CString::new(e.message).ok()?.into_raw();
+        Some(())
+    }}
+}
+
+/// if a `v4_try_transform*` function gives no result because of a type mismatch, `out_message` receives
+/// the mismatch number `index` (starting from 0). `var_types` is the same as in [v4_try_transform_with_types],
+/// it is empty for the functions without declared types
+#[no_mangle]
+pub unsafe extern "C" fn v4_get_type_error(
+    input: PChar,
+    ns: *const Namespaces,
+    var_types: *const DictStrByStr,
+    index: u32,
+    out_column: *mut u32,
+    out_message: *mut PChar,
+) -> bool {
+    boolclosure! {{
+        let input = pchar_to_string(input)?;
+        let symbols = declarations(var_types.as_ref()?);
+        let env = TypeEnv {
+            ns: ns.as_ref()?,
+            symbols: &symbols,
+            line_number: 0,
+        };
+        let e = super::type_check(&input, &env).into_iter().nth(index as usize)?;
+        *out_column = token_column(&input, &e.token) as u32;
+        *out_message = std::ffi::CString::new(e.message).ok()?.into_raw();
+        Some(())
+    }}
+}
+
+/// if [v4_try_transform] gives no result because a constant can't be folded,
+/// `out_message` receives the reason (overflow, division by zero, type mismatch, etc)
+#[no_mangle]
+pub unsafe extern "C" fn v4_get_eval_error(
+    input: PChar,
+    ns: *const Namespaces,
+    const_lookup: *const DictStrByStr,
+    out_message: *mut PChar,
+) -> bool {
+    boolclosure! {{
+        let input = pchar_to_string(input)?;
+        let e = super::eval_error(&input, ns.as_ref()?, const_lookup.as_ref()?)?;
+        *out_message = std::ffi::CString::new(e.to_string()).ok()?.into_raw();
+        Some(())
+    }}
+}
+
+/// folds a constant expression (e.g. `WIDTH * 2 + 1`) into a number,
+/// if it fails `out_error` receives the reason (overflow, type mismatch, etc)
+#[no_mangle]
+pub unsafe extern "C" fn v4_evaluate(
+    input: PChar,
+    const_lookup: *const DictStrByStr,
+    out: *mut PChar,
+    out_error: *mut PChar,
+) -> bool {
+    boolclosure! {{
+        let input = pchar_to_string(input)?;
+        let const_lookup = const_lookup.as_ref()?;
+        let lookup = |name: &str| -> Option<String> {
+            let value = const_lookup.map.get(&std::ffi::CString::new(name).ok()?)?;
+            Some(String::from(value.to_str().ok()?))
+        };
+        match super::eval::evaluate_str(&input, &lookup) {
+            Ok(value) => {
+                *out = std::ffi::CString::new(value.to_string()).ok()?.into_raw();
+                Some(())
+            }
+            Err(e) => {
+                *out_error = std::ffi::CString::new(e.to_string()).ok()?.into_raw();
+                None
+            }
+        }
+    }}
+}
+
+/// declares the variables of `var_types` (`$car` -> `Car`) for [TypeEnv]
+fn declarations(var_types: &DictStrByStr) -> SymbolTable {
+    let mut symbols = SymbolTable::new();
+    for (name, _type) in &var_types.map {
+        if let (Ok(name), Ok(_type)) = (name.to_str(), _type.to_str()) {
+            process_var_declaration(name, &mut symbols, 0, 0, _type);
+        }
+    }
+    symbols
+}
+
+/// operand types are checked before an opcode is picked, [v4_get_type_error] gives the mismatches
+fn check_types(input: &str, ns: &Namespaces) -> Option<()> {
+    let symbols = SymbolTable::new();
+    let env = TypeEnv {
+        ns,
+        symbols: &symbols,
+        line_number: 0,
+    };
+    super::type_check(input, &env).is_empty().then_some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::legacy_ini::Game;
+    use std::ffi::{CStr, CString};
+
+    #[test]
+    fn test_type_error() {
+        let mut table = OpcodeTable::new(Game::SA);
+        table.load_from_file("SASCM.INI");
+        let mut ns = Namespaces::new();
+        ns.load_library("sa.json");
+        let const_lookup = DictStrByStr::default();
+        let mut var_types = DictStrByStr::default();
+        var_types.add(CString::new("$f").unwrap(), CString::new("float").unwrap());
+        extern "C" fn callback(_: u32, _: PChar) {}
+
+        let try_transform = |input: &str| {
+            let input = CString::new(input).unwrap();
+            let mut out = std::ptr::null();
+            unsafe {
+                v4_try_transform(
+                    input.as_ptr(),
+                    &ns,
+                    &table,
+                    &const_lookup,
+                    callback,
+                    &mut out,
+                )
+            }
+        };
+        let type_error = |input: &str, var_types: &DictStrByStr, index: u32| {
+            let input = CString::new(input).unwrap();
+            let mut column = 0;
+            let mut message = std::ptr::null();
+            unsafe {
+                v4_get_type_error(
+                    input.as_ptr(),
+                    &ns,
+                    var_types,
+                    index,
+                    &mut column,
+                    &mut message,
+                )
+                .then(|| {
+                    let message = CStr::from_ptr(message).to_str().unwrap().to_string();
+                    (column, message)
+                })
+            }
+        };
+
+        // bitwise operators take ints
+        assert!(try_transform("0@ &= 1"));
+        assert!(!try_transform("0@ &= 1.5"));
+        let empty = DictStrByStr::default();
+        assert_eq!(
+            type_error("0@ &= 1.5", &empty, 0),
+            Some((7, String::from("expected int, found float value `1.5`")))
+        );
+        assert_eq!(type_error("0@ &= 1.5", &empty, 1), None);
+        assert_eq!(type_error("0@ &= 1", &empty, 0), None);
+
+        let input = CString::new("$f = 1").unwrap();
+        let mut out = std::ptr::null();
+        assert!(!unsafe {
+            v4_try_transform_with_types(
+                input.as_ptr(),
+                &ns,
+                &table,
+                &var_types,
+                &const_lookup,
+                &mut out,
+            )
+        });
+        assert_eq!(
+            type_error("$f = 1", &var_types, 0),
+            Some((
+                6,
+                String::from("cannot assign int value `1` to float variable")
+            ))
+        );
+        assert_eq!(
+            type_error("if $f > 1", &var_types, 0),
+            Some((9, String::from("cannot compare float with int value `1`")))
+        );
+    }
+}
@@ -1,4 +1,12 @@
-use crate::{legacy_ini::OpcodeTable, namespaces::namespaces::OpId, parser::interface::*};
+use super::instruction::{Operand, OperandType};
+use crate::{
+    legacy_ini::OpcodeTable,
+    namespaces::{
+        namespaces::{Namespaces, OpId},
+        OperandKind, Operator, ValueType,
+    },
+    parser::interface::*,
+};
 
 pub fn is_unary(node: &Node) -> bool {
     as_unary(node).is_some()
@@ -40,6 +48,7 @@ pub fn is_token(node: &Node) -> bool {
 pub fn as_token(node: &Node) -> Option<&Token> {
     match node {
         Node::Literal(e) => Some(e),
+        Node::String(e) => Some(&e.token),
         Node::Unary(e) => Some(&e.token),
         Node::Variable(e) => match e {
             Variable::Local(v) => Some(&v.token),
@@ -54,6 +63,15 @@ pub fn as_token(node: &Node) -> Option<&Token> {
     }
 }
 
+/// the token spanning the whole node, including binary and member expressions
+pub fn node_token(node: &Node) -> Option<&Token> {
+    match node {
+        Node::Binary(e) => Some(&e.token),
+        Node::Member(e) => Some(&e.token),
+        _ => as_token(node),
+    }
+}
+
 pub fn is_identifier(node: &Node) -> bool {
     as_identifier(&node).is_some()
 }
@@ -87,6 +105,176 @@ pub fn as_number(node: &Node) -> Option<&Token> {
     }
 }
 
+/// call of a function with the variables receiving the results:
+/// `MyFunc(1)`, `0@ = MyFunc(1)` or `0@, 1@ = MyFunc(1)`
+pub fn as_function_call(node: &Node) -> Option<(&CallExpr, &[Node])> {
+    match node {
+        Node::Call(call) => Some((call, &[])),
+        Node::Binary(e) if e.get_operator() == &SyntaxKind::OperatorEqual => {
+            let Node::Call(call) = e.right.as_ref() else {
+                return None;
+            };
+            match e.left.as_ref() {
+                Node::Tuple(targets) => Some((call, &targets.items)),
+                target => Some((call, std::slice::from_ref(target))),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// type of a string literal or a string variable (`s$var`, `0@v`, `$arr(0@,1s)`),
+/// either `ShortString` or `LongString`
+pub fn as_string_type(node: &Node) -> Option<&VariableType> {
+    fn var_type(var: &Variable) -> Option<&VariableType> {
+        match var {
+            Variable::Global(v) | Variable::Local(v) | Variable::Adma(v) => Some(&v._type),
+            Variable::ArrayElement(e) => Some(&e._type),
+            Variable::Indexed(e) => var_type(&e.var),
+            Variable::Pop(_) | Variable::Push(_) => None,
+        }
+    }
+    let _type = match node {
+        Node::String(e) => &e._type,
+        Node::Variable(var) => var_type(var)?,
+        _ => return None,
+    };
+    match _type {
+        VariableType::ShortString | VariableType::LongString => Some(_type),
+        _ => None,
+    }
+}
+
+/// converts a resolved node (variable, number or string) into a typed operand
+pub fn as_operand(node: &Node, value: String) -> Option<Operand> {
+    let _type = match node {
+        Node::Variable(Variable::ArrayElement(_) | Variable::Indexed(_)) => {
+            OperandType::ArrayElement
+        }
+        Node::Variable(_) => OperandType::Variable,
+        Node::String(_) => OperandType::String,
+        _ => match as_number(node)?.syntax_kind {
+            SyntaxKind::IntegerLiteral => OperandType::IntLiteral,
+            SyntaxKind::FloatLiteral => OperandType::FloatLiteral,
+            SyntaxKind::LabelLiteral => OperandType::Label,
+            _ => return None,
+        },
+    };
+    Some(Operand { _type, value })
+}
+
+/// kind of a resolved node (variable or number) to find the command by operator.
+/// the type of a variable is unknown here, the value gives the command (`0@(1@,1i) = 0.0`)
+pub fn as_operand_kind(node: &Node) -> Option<OperandKind> {
+    match node {
+        Node::Variable(var) if var.is_global() => Some(OperandKind::GlobalVar(None)),
+        Node::Variable(_) => Some(OperandKind::LocalVar(None)),
+        _ => match as_number(node)?.syntax_kind {
+            SyntaxKind::IntegerLiteral | SyntaxKind::LabelLiteral => {
+                Some(OperandKind::Literal(ValueType::Int))
+            }
+            SyntaxKind::FloatLiteral => Some(OperandKind::Literal(ValueType::Float)),
+            _ => None,
+        },
+    }
+}
+
+/// kind of the operand of the operator. bitwise operators work on the bits of the value,
+/// so a float literal is passed as is (`0@ &= 42.01`)
+pub fn as_operator_operand_kind(operator: &SyntaxKind, node: &Node) -> Option<OperandKind> {
+    let kind = as_operand_kind(node)?;
+    let is_bitwise = matches!(
+        as_library_operator(operator)?,
+        Operator::And
+            | Operator::Or
+            | Operator::Xor
+            | Operator::Not
+            | Operator::Mod
+            | Operator::ShiftLeft
+            | Operator::ShiftRight
+    );
+    Some(match kind {
+        OperandKind::Literal(_) if is_bitwise => OperandKind::Literal(ValueType::Int),
+        kind => kind,
+    })
+}
+
+/// operator of the library commands, compound assignments (`+=`) share it with binary expressions (`+`),
+/// `<` and `<=` are `>` and `>=` with swapped operands, `<>` is `==` with the NOT bit
+pub fn as_library_operator(syntax_kind: &SyntaxKind) -> Option<Operator> {
+    Some(match syntax_kind {
+        SyntaxKind::OperatorEqual => Operator::Assignment,
+        SyntaxKind::OperatorPlus | SyntaxKind::OperatorPlusEqual => Operator::Addition,
+        SyntaxKind::OperatorMinus | SyntaxKind::OperatorMinusEqual => Operator::Subtraction,
+        SyntaxKind::OperatorMul | SyntaxKind::OperatorMulEqual => Operator::Multiplication,
+        SyntaxKind::OperatorDiv | SyntaxKind::OperatorDivEqual => Operator::Division,
+        SyntaxKind::OperatorTimedAdditionEqual => Operator::TimedAddition,
+        SyntaxKind::OperatorTimedSubtractionEqual => Operator::TimedSubtraction,
+        SyntaxKind::OperatorCastEqual => Operator::CastAssignment,
+        SyntaxKind::OperatorEqualEqual | SyntaxKind::OperatorLessGreater => Operator::IsEqualTo,
+        SyntaxKind::OperatorGreater | SyntaxKind::OperatorLess => Operator::IsGreaterThan,
+        SyntaxKind::OperatorGreaterEqual | SyntaxKind::OperatorLessEqual => {
+            Operator::IsGreaterOrEqualTo
+        }
+        SyntaxKind::OperatorBitwiseAnd | SyntaxKind::OperatorBitwiseAndEqual => Operator::And,
+        SyntaxKind::OperatorBitwiseOr | SyntaxKind::OperatorBitwiseOrEqual => Operator::Or,
+        SyntaxKind::OperatorBitwiseXor | SyntaxKind::OperatorBitwiseXorEqual => Operator::Xor,
+        SyntaxKind::OperatorBitwiseNot | SyntaxKind::OperatorBitwiseNotEqual => Operator::Not,
+        SyntaxKind::OperatorBitwiseMod | SyntaxKind::OperatorBitwiseModEqual => Operator::Mod,
+        SyntaxKind::OperatorBitwiseShl | SyntaxKind::OperatorBitwiseShlEqual => Operator::ShiftLeft,
+        SyntaxKind::OperatorBitwiseShr | SyntaxKind::OperatorBitwiseShrEqual => {
+            Operator::ShiftRight
+        }
+        _ => return None,
+    })
+}
+
+/// finds the command tagged with the operator in the library whose parameters accept the operands.
+/// there is no command if the kind of an operand is unknown or the library has no such command
+pub fn find_opcode(
+    ns: &Namespaces,
+    operator: Option<Operator>,
+    operands: &[Option<OperandKind>],
+) -> Option<OpId> {
+    let kinds = operands.iter().copied().collect::<Option<Vec<_>>>()?;
+    ns.get_opcode_by_operator(operator?, &kinds).copied()
+}
+
+/// if the destination variable is the last parameter, then this is SCR mode
+fn is_scr_mode(op: OpId, legacy_ini: &OpcodeTable) -> bool {
+    let param_count = legacy_ini.get_params_count(op);
+    // get the index of the last parameter
+    let var_index = legacy_ini.get_param_real_index(op, (param_count - 1) as usize);
+    var_index + 1 == param_count
+}
+
+pub fn binary_operands(
+    op: OpId,
+    dest_var: Operand,
+    operand: Operand,
+    legacy_ini: &OpcodeTable,
+) -> Vec<Operand> {
+    if is_scr_mode(op, legacy_ini) {
+        vec![operand, dest_var]
+    } else {
+        vec![dest_var, operand]
+    }
+}
+
+pub fn ternary_operands(
+    op: OpId,
+    dest_var: Operand,
+    operand1: Operand,
+    operand2: Operand,
+    legacy_ini: &OpcodeTable,
+) -> Vec<Operand> {
+    if is_scr_mode(op, legacy_ini) {
+        vec![operand1, operand2, dest_var]
+    } else {
+        vec![dest_var, operand1, operand2]
+    }
+}
+
 pub fn format_unary(op: OpId, dest_var: &str) -> Option<String> {
     format!("{:04X}: {dest_var}", op).into()
 }
@@ -97,11 +285,7 @@ pub fn format_binary(
     operand: &str,
     legacy_ini: &OpcodeTable,
 ) -> Option<String> {
-    let param_count = legacy_ini.get_params_count(op);
-    // get the index of the last parameter
-    let var_index = legacy_ini.get_param_real_index(op, (param_count - 1) as usize);
-    if var_index + 1 == param_count {
-        // if variable is the last parameter, then this is SCR mode
+    if is_scr_mode(op, legacy_ini) {
         format_binary_no_reorder(op, operand, dest_var)
     } else {
         format_binary_no_reorder(op, dest_var, operand)
@@ -119,12 +303,7 @@ pub fn format_ternary(
     operand2: &str,
     legacy_ini: &OpcodeTable,
 ) -> Option<String> {
-    let param_count = legacy_ini.get_params_count(op);
-    // get the index of the last parameter
-    let var_index = legacy_ini.get_param_real_index(op, (param_count - 1) as usize);
-
-    if var_index + 1 == param_count {
-        // if variable is the last parameter, then this is SCR mode
+    if is_scr_mode(op, legacy_ini) {
         format_ternary_no_reorder(op, operand1, operand2, dest_var)
     } else {
         format_ternary_no_reorder(op, dest_var, operand1, operand2)
@@ -145,3 +324,9 @@ pub fn token_str<'a>(s: &'a str, token: &Token) -> &'a str {
     let end = start + token.len;
     &s[start..end]
 }
+
+/// 1-based column of the token on its line in the source text
+pub fn token_column(s: &str, token: &Token) -> usize {
+    let start = token.start - 1;
+    start - s[..start].rfind('\n').map_or(0, |i| i + 1) + 1
+}
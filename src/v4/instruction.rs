@@ -0,0 +1,102 @@
+use super::eval::parse_int;
+use crate::namespaces::{namespaces::Namespaces, Command, OpId};
+use crate::parser::{interface::VariableType, parse_string};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum OperandType {
+    /// global, local or ADMA variable, e.g. `$var`, `0@`, `&10`, `|<`
+    Variable,
+    /// `$var[0]`, `0@(1@,1i)`
+    ArrayElement,
+    IntLiteral,
+    FloatLiteral,
+    /// `@label`
+    Label,
+    /// `'text'` or `"text"`
+    String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Operand {
+    pub _type: OperandType,
+    /// source text of the operand with constants resolved, e.g. `$var`, `-100.0`, `@label`
+    pub value: String,
+}
+
+impl Operand {
+    pub fn new(_type: OperandType, value: impl Into<String>) -> Self {
+        Self {
+            _type,
+            value: value.into(),
+        }
+    }
+
+    pub fn is_variable(&self) -> bool {
+        matches!(
+            self._type,
+            OperandType::Variable | OperandType::ArrayElement
+        )
+    }
+
+    pub fn as_int(&self) -> Option<i32> {
+        if self._type != OperandType::IntLiteral {
+            return None;
+        }
+        parse_int(&self.value)
+    }
+
+    pub fn as_float(&self) -> Option<f32> {
+        if self._type != OperandType::FloatLiteral {
+            return None;
+        }
+        self.value.parse::<f32>().ok()
+    }
+
+    /// text of the string literal with escape sequences replaced and whether it is a long string (`"text"`)
+    pub fn as_string(&self) -> Option<(String, bool)> {
+        if self._type != OperandType::String {
+            return None;
+        }
+        let (_, literal) = parse_string(&self.value).ok()?;
+        Some((literal.value, literal._type == VariableType::LongString))
+    }
+}
+
+#[derive(Debug)]
+pub struct Instruction<'a> {
+    pub id: OpId,
+    /// none for opcodes known only from the INI file, the operands are then given in the INI order
+    pub command: Option<&'a Command>,
+    /// the result of the condition is inverted (`8038:` instead of `0038:`)
+    pub not: bool,
+    /// operands in the order they are written to the script
+    pub operands: Vec<Operand>,
+}
+
+impl<'a> Instruction<'a> {
+    pub fn new(ns: &'a Namespaces, id: OpId, operands: Vec<Operand>) -> Self {
+        Self {
+            id,
+            command: ns.get_command(id),
+            not: false,
+            operands,
+        }
+    }
+
+    /// inverts the result of the condition
+    pub fn negate(mut self) -> Self {
+        self.not = !self.not;
+        self
+    }
+}
+
+impl std::fmt::Display for Instruction<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let id = if self.not { self.id | 0x8000 } else { self.id };
+        write!(f, "{:04X}:", id)?;
+        for operand in &self.operands {
+            write!(f, " {}", operand.value)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use super::helpers::*;
+use super::instruction::{Instruction, Operand, OperandType};
+use super::transform::resolve;
+use crate::{
+    dictionary::dictionary_str_by_str::DictStrByStr,
+    legacy_ini::OpcodeTable,
+    namespaces::{
+        namespaces::{Namespaces, OpId},
+        OperandKind, Operator, ValueType,
+    },
+    parser::interface::{Node, SyntaxKind, AST},
+};
+
+/// variables that can hold intermediate results of a chained expression
+pub struct TempPool {
+    free: Vec<String>,
+}
+
+impl TempPool {
+    /// variables are taken in the given order
+    pub fn new<S: Into<String>>(vars: impl IntoIterator<Item = S>) -> Self {
+        let mut free: Vec<String> = vars.into_iter().map(|v| v.into()).collect();
+        free.reverse();
+        Self { free }
+    }
+
+    fn take(&mut self) -> Option<Operand> {
+        self.free
+            .pop()
+            .map(|name| Operand::new(OperandType::Variable, name))
+    }
+
+    fn release(&mut self, operand: Operand) {
+        self.free.push(operand.value);
+    }
+}
+
+/// operands of the integer operations, the scope of temporary variables is not known
+fn kind(operand: &Operand) -> Option<OperandKind> {
+    match operand._type {
+        OperandType::Variable | OperandType::ArrayElement => {
+            Some(OperandKind::AnyVar(Some(ValueType::Int)))
+        }
+        OperandType::IntLiteral => Some(OperandKind::Literal(ValueType::Int)),
+        _ => None,
+    }
+}
+
+struct Lowering<'a, 'b> {
+    expr: &'b str,
+    ns: &'a Namespaces,
+    legacy_ini: &'b OpcodeTable,
+    const_lookup: &'b DictStrByStr,
+    temps: &'b mut TempPool,
+    instructions: Vec<Instruction<'a>>,
+}
+
+/// lowers `var = <expression>` into a sequence of integer operations,
+/// e.g. `0@ = 1@ * 2 + 3@ / 4` becomes INT_MUL, INT_DIV and INT_ADD.
+/// intermediate results are stored in variables taken from `temps`
+pub fn lower_assignment<'a>(
+    ast: &AST,
+    expr: &str,
+    ns: &'a Namespaces,
+    legacy_ini: &OpcodeTable,
+    const_lookup: &DictStrByStr,
+    temps: &mut TempPool,
+) -> Option<Vec<Instruction<'a>>> {
+    let Node::Binary(e) = ast.body.first()? else {
+        return None;
+    };
+    if e.get_operator() != &SyntaxKind::OperatorEqual {
+        return None;
+    }
+    let (var, var_name) = resolve(&e.left, expr, ns, const_lookup)?;
+    if !is_variable(&var) {
+        return None;
+    }
+    let dest = as_operand(&var, var_name)?;
+
+    let mut lowering = Lowering {
+        expr,
+        ns,
+        legacy_ini,
+        const_lookup,
+        temps,
+        instructions: vec![],
+    };
+    match e.right.as_ref() {
+        Node::Binary(_) | Node::Unary(_) => lowering.lower(&e.right, Some(dest))?,
+        _ => return None,
+    };
+    Some(lowering.instructions)
+}
+
+impl<'a, 'b> Lowering<'a, 'b> {
+    /// returns the operand holding the value of the node and whether it is a temporary variable
+    fn lower(&mut self, node: &Node, dest: Option<Operand>) -> Option<(Operand, bool)> {
+        match node {
+            Node::Binary(e) => {
+                let operator = match e.get_operator() {
+                    SyntaxKind::OperatorPlus
+                    | SyntaxKind::OperatorMinus
+                    | SyntaxKind::OperatorMul
+                    | SyntaxKind::OperatorDiv
+                    | SyntaxKind::OperatorBitwiseAnd
+                    | SyntaxKind::OperatorBitwiseOr
+                    | SyntaxKind::OperatorBitwiseXor
+                    | SyntaxKind::OperatorBitwiseMod
+                    | SyntaxKind::OperatorBitwiseShr
+                    | SyntaxKind::OperatorBitwiseShl => as_library_operator(e.get_operator()),
+                    _ => return None,
+                };
+                let left = self.lower(&e.left, None)?;
+                let right = self.lower(&e.right, None)?;
+                // operands are read before the result is written, so their temporaries can be reused
+                self.release(&left);
+                self.release(&right);
+                let (target, is_temp) = self.target(dest)?;
+                let op_id = find_opcode(
+                    self.ns,
+                    operator,
+                    &[kind(&target), kind(&left.0), kind(&right.0)],
+                )?;
+                let operands =
+                    ternary_operands(op_id, target.clone(), left.0, right.0, self.legacy_ini);
+                self.emit(op_id, operands)?;
+                Some((target, is_temp))
+            }
+            Node::Unary(e) if e.get_operator() == &SyntaxKind::OperatorBitwiseNot => {
+                let operand = self.lower(&e.operand, None)?;
+                self.release(&operand);
+                let (target, is_temp) = self.target(dest)?;
+                let op_id = find_opcode(
+                    self.ns,
+                    Some(Operator::Not),
+                    &[kind(&target), kind(&operand.0)],
+                )?;
+                let operands = binary_operands(op_id, target.clone(), operand.0, self.legacy_ini);
+                self.emit(op_id, operands)?;
+                Some((target, is_temp))
+            }
+            _ => {
+                let (node, text) = resolve(node, self.expr, self.ns, self.const_lookup)?;
+                if !is_variable(&node) && !is_number(&node) {
+                    return None;
+                }
+                let operand = as_operand(&node, text)?;
+                // integer operations only
+                if !matches!(
+                    operand._type,
+                    OperandType::Variable | OperandType::ArrayElement | OperandType::IntLiteral
+                ) {
+                    return None;
+                }
+                match dest {
+                    // var = var2 is handled by the single instruction transform
+                    Some(_) => None,
+                    None => Some((operand, false)),
+                }
+            }
+        }
+    }
+
+    fn target(&mut self, dest: Option<Operand>) -> Option<(Operand, bool)> {
+        match dest {
+            Some(dest) => Some((dest, false)),
+            None => Some((self.temps.take()?, true)),
+        }
+    }
+
+    fn release(&mut self, (operand, is_temp): &(Operand, bool)) {
+        if *is_temp {
+            self.temps.release(operand.clone());
+        }
+    }
+
+    fn emit(&mut self, op_id: OpId, operands: Vec<Operand>) -> Option<()> {
+        self.instructions
+            .push(Instruction::new(self.ns, op_id, operands));
+        Some(())
+    }
+}
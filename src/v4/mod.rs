@@ -1,11 +1,19 @@
 use crate::{
     dictionary::dictionary_str_by_str::DictStrByStr, legacy_ini::OpcodeTable,
-    namespaces::namespaces::Namespaces,
+    namespaces::namespaces::Namespaces, parser::ParseError,
 };
 
+pub mod eval;
 pub mod ffi;
 pub mod helpers;
+pub mod instruction;
+pub mod lower;
 pub mod transform;
+pub mod typecheck;
+
+use instruction::Instruction;
+use lower::TempPool;
+use typecheck::{TypeEnv, TypeError};
 
 pub fn transform(
     expr: &str,
@@ -13,8 +21,149 @@ pub fn transform(
     legacy_ini: &OpcodeTable,
     const_lookup: &DictStrByStr,
 ) -> Option<String> {
+    transform_instruction(expr, ns, legacy_ini, const_lookup).map(|i| i.to_string())
+}
+
+/// same as [transform], but returns the resolved command and typed operands instead of text
+pub fn transform_instruction<'a>(
+    expr: &str,
+    ns: &'a Namespaces,
+    legacy_ini: &OpcodeTable,
+    const_lookup: &DictStrByStr,
+) -> Option<Instruction<'a>> {
+    let body = crate::parser::parse(expr).ok()?.1;
+    transform::try_transform_instruction(&body, expr, ns, legacy_ini, const_lookup)
+}
+
+/// lowers the header of `if` or `while` (e.g. `if 0@ > 1 and $x <> 2.0`)
+/// into the `ANDOR` prologue followed by the conditional opcodes, see [transform_checked] for `env`
+pub fn transform_conditions<'a>(
+    header: &str,
+    env: &TypeEnv<'a>,
+    const_lookup: &DictStrByStr,
+) -> Option<Vec<Instruction<'a>>> {
+    let conditions = crate::parser::parse_conditions(header).ok()?.1;
+    transform::try_transform_conditions(&conditions, header, env, const_lookup)
+}
+
+/// same as [transform_instruction], but also lowers assignments with chained operations
+/// (e.g. `0@ = 1@ * 2 + 3@ / 4`) into several instructions using variables from `temps`
+pub fn transform_sequence<'a>(
+    expr: &str,
+    ns: &'a Namespaces,
+    legacy_ini: &OpcodeTable,
+    const_lookup: &DictStrByStr,
+    temps: &mut TempPool,
+) -> Option<Vec<Instruction<'a>>> {
+    let body = crate::parser::parse(expr).ok()?.1;
+    if let Some(i) = transform::try_transform_instruction(&body, expr, ns, legacy_ini, const_lookup)
+    {
+        return Some(vec![i]);
+    }
+    lower::lower_assignment(&body, expr, ns, legacy_ini, const_lookup, temps)
+}
+
+/// checks operand types against the declared variable types and command signatures,
+/// `expr` is a statement or the header of `if` or `while` (see [transform_conditions])
+pub fn type_check(expr: &str, env: &TypeEnv) -> Vec<TypeError> {
+    if let Ok((_, body)) = crate::parser::parse(expr) {
+        return typecheck::check(&body, expr, env);
+    }
+    match crate::parser::parse_conditions(expr) {
+        Ok((_, conditions)) => typecheck::check_conditions(&conditions, expr, env),
+        Err(_) => vec![],
+    }
+}
+
+/// same as [transform_instruction], but reports type mismatches before the opcode is picked.
+/// the variables declared in `env` (`int x`, `Car $car`) also lower the cast assignment (`var =# var`),
+/// comparisons of variables (`var > var`) and the class syntax (`$car.Health = 100`)
+pub fn transform_checked<'a>(
+    expr: &str,
+    env: &TypeEnv<'a>,
+    legacy_ini: &OpcodeTable,
+    const_lookup: &DictStrByStr,
+) -> Result<Option<Instruction<'a>>, Vec<TypeError>> {
+    let body = match crate::parser::parse(expr) {
+        Ok((_, body)) => body,
+        Err(_) => return Ok(None),
+    };
+    let errors = typecheck::check(&body, expr, env);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(
+        transform::try_transform_instruction(&body, expr, env.ns, legacy_ini, const_lookup)
+            .or_else(|| transform::try_transform_cast(&body, expr, env, const_lookup))
+            .or_else(|| {
+                transform::try_transform_comparison(body.body.first()?, expr, env, const_lookup)
+            })
+            .or_else(|| transform::try_transform_member(&body, expr, env, const_lookup)),
+    )
+}
+
+/// lowers a call of the function declared with `signature` as kept by the preprocessor
+/// (e.g. `function MyFunc(a: int, b: float): int`): `MyFunc(2@, 3.0)`, `0@ = MyFunc(2@, 3.0)`.
+/// returns `Ok(None)` if the expression is not a call of this function. declared variables (`int x`)
+/// are taken from `const_lookup` or the variable they alias, a variable without either is an error
+pub fn transform_call<'a>(
+    expr: &str,
+    signature: &str,
+    env: &TypeEnv<'a>,
+    const_lookup: &DictStrByStr,
+) -> Result<Option<Instruction<'a>>, Vec<TypeError>> {
+    let (Ok((_, body)), Ok((_, declaration))) = (
+        crate::parser::parse(expr),
+        crate::parser::function_signature(crate::parser::Span::from(signature)),
+    ) else {
+        return Ok(None);
+    };
+    let Some((call, targets)) = body.body.get(0).and_then(helpers::as_function_call) else {
+        return Ok(None);
+    };
+    let is_callee = helpers::as_identifier(&call.callee).is_some_and(|callee| {
+        helpers::token_str(expr, callee)
+            .eq_ignore_ascii_case(helpers::token_str(signature, &declaration.name))
+    });
+    if !is_callee {
+        return Ok(None);
+    }
+    let errors = typecheck::check_call(call, targets, expr, &declaration, signature, env);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    transform::try_transform_call(&body, expr, env, &declaration, signature, const_lookup)
+        .map_err(|e| vec![e])
+}
+
+/// returns the first reference to an unknown enum member with the closest member names
+/// (e.g. `PedType.CivMal`, did you mean `PedType.CIVMALE`?), [transform] gives no result for such expressions
+pub fn enum_error(expr: &str, ns: &Namespaces) -> Option<TypeError> {
+    let body = crate::parser::parse(expr).ok()?.1;
+    typecheck::check_enum_members(&body, expr, ns)
+        .into_iter()
+        .next()
+}
+
+/// returns why a constant expression referenced in `expr` can't be folded
+/// (e.g. `x = 0x7FFFFFFF + 1` overflows), [transform] gives no result for such expressions
+pub fn eval_error(
+    expr: &str,
+    ns: &Namespaces,
+    const_lookup: &DictStrByStr,
+) -> Option<eval::EvalError> {
     let body = crate::parser::parse(expr).ok()?.1;
-    transform::try_tranform(&body, expr, ns, legacy_ini, const_lookup)
+    body.body
+        .iter()
+        .find_map(|node| transform::fold_error(node, expr, ns, const_lookup))
+}
+
+/// returns the location and the reason if the expression can't be parsed
+pub fn parse_error(expr: &str) -> Option<ParseError> {
+    match crate::parser::parse(expr) {
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => Some(e),
+        _ => None,
+    }
 }
 
 #[cfg(test)]
@@ -27,7 +176,7 @@ mod tests {
     #[test]
     fn test_command_unary() {
         let mut table = OpcodeTable::new(Game::SA);
-        table.load_from_file("SASCM.ini");
+        table.load_from_file("SASCM.INI");
         let mut ns = Namespaces::new();
         ns.load_library("sa.json");
         let mut const_lookup = DictStrByStr::default();
@@ -49,10 +198,327 @@ mod tests {
         assert_eq!(t("~x(1@,1i)"), "0B1A: x(1@,1i)");
     }
 
+    #[test]
+    fn test_token_column() {
+        use crate::parser::interface::*;
+
+        let s = "0@ = 1\n  wait 0";
+        let (_, ast) = crate::parser::parse(s).unwrap();
+        let Node::CommandCall(call) = &ast.body[1] else {
+            panic!("expected command")
+        };
+        let name = call.name.as_ref().unwrap();
+        assert_eq!(helpers::token_str(s, name), "wait");
+        assert_eq!(name.start, 10);
+        assert_eq!(helpers::token_column(s, name), 3);
+        let Node::Binary(e) = &ast.body[0] else {
+            panic!("expected assignment")
+        };
+        assert_eq!(helpers::token_column(s, &e.token), 1);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        use crate::parser::interface::SyntaxKind;
+
+        assert_eq!(parse_error("0@ = 1"), None);
+
+        let e = parse_error("0@ = 1 +").unwrap();
+        assert_eq!((e.line, e.column), (1, 9));
+        assert_eq!(
+            e.expected,
+            vec![
+                SyntaxKind::LocalVariable,
+                SyntaxKind::GlobalVariable,
+                SyntaxKind::IntegerLiteral,
+                SyntaxKind::FloatLiteral,
+                SyntaxKind::StringLiteral,
+                SyntaxKind::Identifier,
+            ]
+        );
+        assert!(e.message.starts_with("expected local variable"));
+
+        let e = parse_error("0@ = 1 *\n0@ = 2").unwrap();
+        assert_eq!((e.line, e.column), (1, 9));
+        assert!(e.message.ends_with("found end of line"));
+
+        let e = parse_error("0@ = 1 + )").unwrap();
+        assert_eq!(e.message, "unexpected `+`");
+
+        let e = parse_error("0@ += ").unwrap();
+        assert_eq!((e.line, e.column), (1, 7));
+        assert!(e.message.ends_with("found end of input"));
+    }
+
+    #[test]
+    fn test_instruction() {
+        use instruction::{Operand, OperandType};
+
+        let mut table = OpcodeTable::new(Game::SA);
+        table.load_from_file("SASCM.INI");
+        let mut ns = Namespaces::new();
+        ns.load_library("sa.json");
+        let mut const_lookup = DictStrByStr::default();
+        const_lookup.add(CString::new("f").unwrap(), CString::new("-100.0").unwrap());
+
+        let i = transform_instruction("0@ = 1 + $var[2]", &ns, &table, &const_lookup).unwrap();
+        assert_eq!(i.id, 0x0A8E);
+        assert_eq!(i.command.unwrap().name, "INT_ADD");
+        assert_eq!(
+            i.operands,
+            vec![
+                Operand::new(OperandType::Variable, "0@"),
+                Operand::new(OperandType::IntLiteral, "1"),
+                Operand::new(OperandType::ArrayElement, "$var[2]"),
+            ]
+        );
+        assert_eq!(i.to_string(), "0A8E: 0@ 1 $var[2]");
+
+        let i = transform_instruction("$x = -f", &ns, &table, &const_lookup).unwrap();
+        assert_eq!(i.command.unwrap().name, "SET_VAR_FLOAT");
+        assert_eq!(i.operands[1]._type, OperandType::FloatLiteral);
+        assert_eq!(i.operands[1].as_float(), Some(100.0));
+
+        let i = transform_instruction("0@ = @label", &ns, &table, &const_lookup).unwrap();
+        assert_eq!(i.operands[1], Operand::new(OperandType::Label, "@label"));
+
+        let i = transform_instruction("0@ = -0x10", &ns, &table, &const_lookup).unwrap();
+        assert_eq!(i.operands[1].as_int(), Some(-16));
+
+        // unresolved identifiers are not valid operands
+        assert!(transform_instruction("0@ = ~abc", &ns, &table, &const_lookup).is_none());
+    }
+
+    #[test]
+    fn test_const_folding() {
+        let mut table = OpcodeTable::new(Game::SA);
+        table.load_from_file("SASCM.INI");
+        let mut ns = Namespaces::new();
+        ns.load_library("sa.json");
+        let mut const_lookup = DictStrByStr::default();
+        let mut add = |k: &str, v: &str| {
+            const_lookup.add(CString::new(k).unwrap(), CString::new(v).unwrap());
+        };
+        add("width", "10");
+        add("max", "WIDTH * 2 + 1");
+        add("neg", "-max");
+        add("half", "1.0 / 2.0");
+        add("big", "2147483647 + 1");
+        add("mixed", "WIDTH + half");
+        add("tiny", "1.0 / 10000000.0");
+
+        let t = |input: &str| -> String {
+            transform(input, &ns, &table, &const_lookup).unwrap_or_default()
+        };
+
+        assert_eq!(t("0@ = max"), "0006: 0@ 21");
+        assert_eq!(t("0@ = -max"), "0006: 0@ -21");
+        assert_eq!(t("0@ = neg"), "0006: 0@ -21");
+        assert_eq!(t("0@ = -neg"), "0006: 0@ 21");
+        assert_eq!(t("$var = half"), "0005: $var 0.5");
+        assert_eq!(t("0@ &= max"), "0B17: 0@ 21");
+        assert_eq!(t("0@ = 1@ + max"), "0A8E: 0@ 1@ 21");
+        assert_eq!(t("$var = tiny"), "0005: $var 0.0000001");
+
+        // the folded literal spans its whole text
+        let node = crate::parser::parse_expression("neg").unwrap().1;
+        let (node, text) = transform::resolve(&node, "neg", &ns, &const_lookup).unwrap();
+        assert_eq!(text, "-21");
+        assert_eq!(
+            helpers::token_str(&text, helpers::as_token(&node).unwrap()),
+            "-21"
+        );
+
+        // overflow and type mismatch
+        assert_eq!(t("0@ = big"), "");
+        assert_eq!(t("0@ = mixed"), "");
+
+        let e = |input: &str| eval_error(input, &ns, &const_lookup).map(|e| e.to_string());
+        assert_eq!(e("0@ = max"), None);
+        assert_eq!(
+            e("0@ = big").unwrap(),
+            "integer overflow in `2147483647 + 1`"
+        );
+        assert_eq!(
+            e("0@ = 1@ + mixed").unwrap(),
+            "type mismatch in `WIDTH + half`"
+        );
+        assert_eq!(
+            e("0@ = -big").unwrap(),
+            "integer overflow in `2147483647 + 1`"
+        );
+    }
+
+    #[test]
+    fn test_type_check() {
+        use crate::language_service::scanner::{
+            process_const_declaration, process_var_declaration,
+        };
+        use crate::language_service::symbol_table::SymbolTable;
+
+        let mut table = OpcodeTable::new(Game::SA);
+        table.load_from_file("SASCM.INI");
+        let mut ns = Namespaces::new();
+        ns.load_library("sa.json");
+        let const_lookup = DictStrByStr::default();
+
+        let mut symbols = SymbolTable::new();
+        process_var_declaration("x", &mut symbols, 0, 0, "int");
+        process_var_declaration("y", &mut symbols, 0, 0, "float");
+        process_var_declaration("$car", &mut symbols, 0, 0, "Car");
+        process_var_declaration("$ped", &mut symbols, 0, 0, "Char");
+        process_var_declaration("$f", &mut symbols, 0, 0, "float");
+        process_const_declaration("MAX = 10", &mut symbols, 0, 0);
+        process_const_declaration("alias = 0@", &mut symbols, 0, 0);
+        process_var_declaration("0@", &mut symbols, 0, 0, "float");
+
+        let env = TypeEnv {
+            ns: &ns,
+            symbols: &symbols,
+            line_number: 1,
+        };
+        let t = |input: &str| -> Vec<String> {
+            type_check(input, &env)
+                .iter()
+                .map(|e| e.to_string())
+                .collect()
+        };
+
+        assert!(t("x = 1").is_empty());
+        assert!(t("y = 1.0").is_empty());
+        assert!(t("x = max").is_empty());
+        assert!(t("$car = 0").is_empty());
+        assert!(t("x = 1@ + 2").is_empty());
+        assert_eq!(
+            t("x = 1.0"),
+            vec!["cannot assign float value `1.0` to int variable"]
+        );
+        assert_eq!(
+            t("y = max"),
+            vec!["cannot assign int value `max` to float variable"]
+        );
+        assert_eq!(
+            t("1@s = 5"),
+            vec!["cannot assign int value `5` to string variable"]
+        );
+        assert_eq!(
+            t("alias = 5"),
+            vec!["cannot assign int value `5` to float variable"]
+        );
+        assert_eq!(
+            t("x = y * 2.0"),
+            vec!["cannot assign float value `y * 2.0` to int variable"]
+        );
+        assert_eq!(
+            t("x = 1 + 1.0"),
+            vec!["expected int, found float value `1.0`"]
+        );
+        assert_eq!(t("x &= y"), vec!["expected int, found float value `y`"]);
+        assert_eq!(t("y > 5"), vec!["cannot compare float with int value `5`"]);
+
+        // loops, switches and function bodies
+        assert!(t("for x = 1 to max step 2\nx = 1\nend").is_empty());
+        assert_eq!(
+            t("for x = 1.0 to 10\nend"),
+            vec!["cannot assign float value `1.0` to int variable"]
+        );
+        assert_eq!(
+            t("for x = 0 to y\nend"),
+            vec!["cannot compare int with float value `y`"]
+        );
+        assert_eq!(
+            t("for y = 0.0 to 1.0 step 1\nend"),
+            vec!["expected float, found int value `1`"]
+        );
+        assert_eq!(
+            t("for x = 0 to 10\ny = 1\nend"),
+            vec!["cannot assign int value `1` to float variable"]
+        );
+        assert!(t("switch x\ncase 1, max\nx = 1\ndefault\ny = 1.0\nend").is_empty());
+        assert_eq!(
+            t("switch x\ncase 1, 2.0\nx = 1.0\ndefault\ny = 1\nend"),
+            vec![
+                "cannot compare int with float value `2.0`",
+                "cannot assign float value `1.0` to int variable",
+                "cannot assign int value `1` to float variable"
+            ]
+        );
+        assert_eq!(
+            t("function foo\nx = 1.0\nend"),
+            vec!["cannot assign float value `1.0` to int variable"]
+        );
+
+        assert!(t("set_car_heading $car 10.0").is_empty());
+        assert_eq!(
+            t("set_car_heading $ped 10.0"),
+            vec!["expected Car, found Char value `$ped`"]
+        );
+        assert_eq!(
+            t("0175: $car 10"),
+            vec!["expected float, found int value `10`"]
+        );
+
+        // headers of if and while
+        assert!(t("if x > max and y <> 1.0").is_empty());
+        assert_eq!(
+            t("while not x > 1.0"),
+            vec!["cannot compare int with float value `1.0`"]
+        );
+
+        assert!(transform_checked("$f = 1", &env, &table, &const_lookup).is_err());
+        let i = transform_checked("$f = 1.0", &env, &table, &const_lookup)
+            .unwrap()
+            .unwrap();
+        assert_eq!(i.to_string(), "0005: $f 1.0");
+    }
+
+    #[test]
+    fn test_sequence() {
+        let mut table = OpcodeTable::new(Game::SA);
+        table.load_from_file("SASCM.INI");
+        let mut ns = Namespaces::new();
+        ns.load_library("sa.json");
+        let mut const_lookup = DictStrByStr::default();
+        const_lookup.add(CString::new("x").unwrap(), CString::new("3@").unwrap());
+        const_lookup.add(CString::new("width").unwrap(), CString::new("2").unwrap());
+
+        let t = |input: &str, temps: &[&str]| -> Vec<String> {
+            let mut temps = TempPool::new(temps.iter().copied());
+            transform_sequence(input, &ns, &table, &const_lookup, &mut temps)
+                .unwrap_or_default()
+                .iter()
+                .map(|i| i.to_string())
+                .collect()
+        };
+
+        // single operations don't need temporaries
+        assert_eq!(t("0@ = 1@ + 2", &[]), vec!["0A8E: 0@ 1@ 2"]);
+        assert_eq!(t("0@ = 1", &[]), vec!["0006: 0@ 1"]);
+
+        assert_eq!(
+            t("0@ = 1@ * 2 + 3@ / 4", &["30@", "31@"]),
+            vec!["0A90: 30@ 1@ 2", "0A91: 31@ 3@ 4", "0A8E: 0@ 30@ 31@"]
+        );
+        // left-associative chain reuses the same temporary
+        assert_eq!(
+            t("$var = 1@ + 2 - x + width", &["30@", "31@"]),
+            vec!["0A8E: 30@ 1@ 2", "0A8F: 30@ 30@ 3@", "0A8E: $var 30@ 2"]
+        );
+        assert_eq!(
+            t("0@ = ~1@ & 0xFF", &["30@"]),
+            vec!["0B13: 30@ 1@", "0B10: 0@ 30@ 0xFF"]
+        );
+
+        // not enough temporaries
+        assert!(t("0@ = 1@ * 2 + 3@ / 4", &["30@"]).is_empty());
+        // float arithmetic is not supported
+        assert!(t("0@ = 1@ * 2.0 + 3@", &["30@"]).is_empty());
+    }
+
     #[test]
     fn test_command_binary() {
         let mut table = OpcodeTable::new(Game::SA);
-        table.load_from_file("SASCM.ini");
+        table.load_from_file("SASCM.INI");
         let mut ns = Namespaces::new();
         ns.load_library("sa.json");
         let mut const_lookup = DictStrByStr::default();
@@ -132,7 +598,7 @@ mod tests {
     #[test]
     fn test_ternary() {
         let mut table = OpcodeTable::new(Game::SA);
-        table.load_from_file("SASCM.ini");
+        table.load_from_file("SASCM.INI");
         let mut ns = Namespaces::new();
         ns.load_library("sa.json");
         let mut const_lookup = DictStrByStr::default();
@@ -225,7 +691,7 @@ mod tests {
     #[test]
     fn test_not() {
         let mut table = OpcodeTable::new(Game::SA);
-        table.load_from_file("SASCM.ini");
+        table.load_from_file("SASCM.INI");
         let mut ns = Namespaces::new();
         ns.load_library("sa.json");
         let mut const_lookup = DictStrByStr::default();
@@ -246,7 +712,7 @@ mod tests {
     #[test]
     fn test_timed_addition_assignment() {
         let mut table = OpcodeTable::new(Game::SA);
-        table.load_from_file("SASCM.ini");
+        table.load_from_file("SASCM.INI");
         let mut ns = Namespaces::new();
         ns.load_library("sa.json");
         let mut const_lookup = DictStrByStr::default();
@@ -289,44 +755,521 @@ mod tests {
         assert_eq!(t("1@[x] -=@ y"), "0081: 1@[x] 4@");
     }
 
-    // #[test]
-    // fn test_cast_assignment() {
-    //     use crate::utils::compiler_const::{TOKEN_FLOAT, TOKEN_INT};
-
-    //     let mut table = OpcodeTable::new(Game::SA);
-    //     table.load_from_file("SASCM.ini");
-    //     let mut ns = Namespaces::new();
-    //     ns.load_library("sa.json");
-    //     let mut dict = DictNumByStr::default();
-    //     let mut const_lookup = DictStrByStr::default();
-    //     const_lookup.add(CString::new("x").unwrap(), CString::new("3@").unwrap());
-    //     const_lookup.add(CString::new("y").unwrap(), CString::new("4@").unwrap());
-
-    //     dict.add(CString::new("$i").unwrap(), TOKEN_INT);
-    //     dict.add(CString::new("0@").unwrap(), TOKEN_INT);
-    //     dict.add(CString::new("3@").unwrap(), TOKEN_INT);
-    //     dict.add(CString::new("$f").unwrap(), TOKEN_FLOAT);
-    //     dict.add(CString::new("1@").unwrap(), TOKEN_FLOAT);
-    //     dict.add(CString::new("4@").unwrap(), TOKEN_FLOAT);
-    //     let t = |input: &str| -> String {
-    //         transform(input, &ns, &table, &dict, &const_lookup).unwrap_or_default()
-    //     };
-    //     assert_eq!(t("$i =# $f"), "008C: $i $f");
-    //     assert_eq!(t("$f =# $i"), "008D: $f $i");
-    //     assert_eq!(t("0@ =# $f"), "008E: 0@ $f");
-    //     assert_eq!(t("1@ =# $i"), "008F: 1@ $i");
-    //     assert_eq!(t("$i =# 1@"), "0090: $i 1@");
-    //     assert_eq!(t("$f =# 0@"), "0091: $f 0@");
-    //     assert_eq!(t("0@ =# 1@"), "0092: 0@ 1@");
-    //     assert_eq!(t("1@ =# 0@"), "0093: 1@ 0@");
-
-    //     assert_eq!(t("$i(0@,1i) =# $f(1@,1f)"), "008C: $i(0@,1i) $f(1@,1f)");
-    //     assert_eq!(t("$i[0] =# $f[0]"), "008C: $i[0] $f[0]");
-    // todo:
-    // assert_eq!(t("$m(0@,1i) =# $n(0@,1f)"), "008C: $m(0@,1i) $n(0@,1f)"); // should not require decl
-    // assert_eq!(t("x =# y"), "0092: 3@ 4@"); // should resolve const name
-    // assert_eq!(t("x[0] =# y"), "0092: x[0] 4@");
-    // assert_eq!(t("x[1@] =# y"), "0092: x[1@] 4@");
-    // assert_eq!(t("x[n] =# y"), "0092: x[n] 4@");
-    // }
+    #[test]
+    fn test_cast_assignment() {
+        use crate::language_service::scanner::process_var_declaration;
+        use crate::language_service::symbol_table::SymbolTable;
+
+        let mut table = OpcodeTable::new(Game::SA);
+        table.load_from_file("SASCM.INI");
+        let mut ns = Namespaces::new();
+        ns.load_library("sa.json");
+        let mut symbols = SymbolTable::new();
+        let mut const_lookup = DictStrByStr::default();
+        const_lookup.add(CString::new("x").unwrap(), CString::new("3@").unwrap());
+        const_lookup.add(CString::new("y").unwrap(), CString::new("4@").unwrap());
+        const_lookup.add(CString::new("g").unwrap(), CString::new("$f").unwrap());
+
+        process_var_declaration("$i", &mut symbols, 0, 0, "int");
+        process_var_declaration("0@", &mut symbols, 0, 0, "int");
+        process_var_declaration("3@", &mut symbols, 0, 0, "int");
+        process_var_declaration("$f", &mut symbols, 0, 0, "float");
+        process_var_declaration("1@", &mut symbols, 0, 0, "float");
+        process_var_declaration("4@", &mut symbols, 0, 0, "float");
+        let env = TypeEnv {
+            ns: &ns,
+            symbols: &symbols,
+            line_number: 0,
+        };
+        let t = |input: &str| -> String {
+            transform_checked(input, &env, &table, &const_lookup)
+                .ok()
+                .flatten()
+                .map(|i| i.to_string())
+                .unwrap_or_default()
+        };
+        assert_eq!(t("$i =# $f"), "008C: $i $f");
+        assert_eq!(t("$f =# $i"), "008D: $f $i");
+        assert_eq!(t("0@ =# $f"), "008E: 0@ $f");
+        assert_eq!(t("1@ =# $i"), "008F: 1@ $i");
+        assert_eq!(t("$i =# 1@"), "0090: $i 1@");
+        assert_eq!(t("$f =# 0@"), "0091: $f 0@");
+        assert_eq!(t("0@ =# 1@"), "0092: 0@ 1@");
+        assert_eq!(t("1@ =# 0@"), "0093: 1@ 0@");
+
+        assert_eq!(t("$i(0@,1i) =# $f(1@,1f)"), "008C: $i(0@,1i) $f(1@,1f)");
+        assert_eq!(t("$i[0] =# $f[0]"), "008C: $i[0] $f[0]");
+        assert_eq!(t("$m(0@,1i) =# $n(0@,1f)"), "008C: $m(0@,1i) $n(0@,1f)");
+        assert_eq!(t("x =# y"), "0092: 3@ 4@");
+        assert_eq!(t("x[0] =# y"), "0092: x[0] 4@");
+        assert_eq!(t("x[1@] =# y"), "0092: x[1@] 4@");
+        assert_eq!(t("x[n] =# y"), "0092: x[n] 4@");
+        assert_eq!(t("0@ =# g"), "008E: 0@ $f");
+
+        // same types or unknown variables
+        assert_eq!(t("$i =# 0@"), "");
+        assert_eq!(t("$i =# $unknown"), "");
+        assert_eq!(t("$i =# 1.0"), "");
+        // other operators don't need the types
+        assert_eq!(t("0@ &= 1"), "0B17: 0@ 1");
+    }
+
+    #[test]
+    fn test_comparison() {
+        use crate::language_service::scanner::process_var_declaration;
+        use crate::language_service::symbol_table::SymbolTable;
+
+        let mut table = OpcodeTable::new(Game::SA);
+        table.load_from_file("SASCM.INI");
+        let mut ns = Namespaces::new();
+        ns.load_library("sa.json");
+        let mut symbols = SymbolTable::new();
+        let mut const_lookup = DictStrByStr::default();
+        const_lookup.add(CString::new("x").unwrap(), CString::new("3@").unwrap());
+        const_lookup.add(CString::new("max").unwrap(), CString::new("10").unwrap());
+
+        process_var_declaration("$i", &mut symbols, 0, 0, "int");
+        process_var_declaration("0@", &mut symbols, 0, 0, "int");
+        process_var_declaration("3@", &mut symbols, 0, 0, "int");
+        process_var_declaration("$f", &mut symbols, 0, 0, "float");
+        process_var_declaration("1@", &mut symbols, 0, 0, "float");
+        let env = TypeEnv {
+            ns: &ns,
+            symbols: &symbols,
+            line_number: 0,
+        };
+        let t = |input: &str| -> String {
+            transform_checked(input, &env, &table, &const_lookup)
+                .ok()
+                .flatten()
+                .map(|i| i.to_string())
+                .unwrap_or_default()
+        };
+
+        assert_eq!(t("$i > 5"), "0018: $i 5");
+        assert_eq!(t("0@ > 5"), "0019: 0@ 5");
+        assert_eq!(t("5 > $i"), "001A: 5 $i");
+        assert_eq!(t("$f >= 1.5"), "0030: $f 1.5");
+        assert_eq!(t("1@ >= -1.5"), "0031: 1@ -1.5");
+        assert_eq!(t("$i > 0@"), "001E: $i 0@");
+        assert_eq!(t("$f >= 1@"), "0036: $f 1@");
+        assert_eq!(t("x > max"), "0019: 3@ 10");
+
+        // < and <= swap the operands
+        assert_eq!(t("0@ < 5"), "001B: 5 0@");
+        assert_eq!(t("5.0 <= $f"), "0030: $f 5.0");
+        assert_eq!(t("0@ < $i"), "001E: $i 0@");
+        assert_eq!(t("1@ <= 2.0"), "0033: 2.0 1@");
+
+        assert_eq!(t("$i == 1"), "0038: $i 1");
+        assert_eq!(t("1 == 0@"), "0039: 0@ 1");
+        assert_eq!(t("$f == 1@"), "0046: $f 1@");
+        assert_eq!(t("0@ == $i"), "003C: $i 0@");
+        // <> sets the NOT bit
+        assert_eq!(t("$i <> 1"), "8038: $i 1");
+        assert_eq!(t("1@ <> 0.0"), "8043: 1@ 0.0");
+
+        // different or unknown types
+        assert_eq!(t("$i > 1@"), "");
+        assert_eq!(t("$f > 1"), "");
+        assert_eq!(t("$x > 1"), "0018: $x 1");
+        assert_eq!(t("$i > $unknown"), "");
+        assert_eq!(t("1 > 2"), "");
+        assert_eq!(t("0@ > @label"), "");
+    }
+
+    #[test]
+    fn test_conditions() {
+        use crate::language_service::scanner::process_var_declaration;
+        use crate::language_service::symbol_table::SymbolTable;
+
+        let mut ns = Namespaces::new();
+        ns.load_library("sa.json");
+        let mut symbols = SymbolTable::new();
+        process_var_declaration("$i", &mut symbols, 0, 0, "int");
+        process_var_declaration("0@", &mut symbols, 0, 0, "int");
+        let env = TypeEnv {
+            ns: &ns,
+            symbols: &symbols,
+            line_number: 0,
+        };
+        let const_lookup = DictStrByStr::default();
+        let t = |input: &str| -> Vec<String> {
+            transform_conditions(input, &env, &const_lookup)
+                .unwrap_or_default()
+                .iter()
+                .map(|i| i.to_string())
+                .collect()
+        };
+
+        assert_eq!(t("if 0@ > 1"), vec!["00D6: 0", "0019: 0@ 1"]);
+        assert_eq!(
+            t("if 0@ > 1 and $i <> 0@ then"),
+            vec!["00D6: 1", "0019: 0@ 1", "803C: $i 0@"]
+        );
+        assert_eq!(
+            t("while not 0@ >= 1 or 1@ < 1.0 or 2 > $i"),
+            vec!["00D6: 22", "8029: 0@ 1", "0023: 1.0 1@", "001A: 2 $i"]
+        );
+        assert_eq!(
+            t("if and\n  0@ == 1\n  not $i <> 2 // comment\nthen"),
+            vec!["00D6: 1", "0039: 0@ 1", "0038: $i 2"]
+        );
+
+        // unsupported conditions, more than 8 conditions, mixed and/or
+        assert!(t("if is_char_dead $p").is_empty());
+        assert!(t(&format!("if 0@ > 1{}", " and 0@ > 1".repeat(8))).is_empty());
+        assert!(t("if 0@ > 1 and 0@ > 1 or 0@ > 1").is_empty());
+    }
+
+    #[test]
+    fn test_member() {
+        use crate::language_service::scanner::process_var_declaration;
+        use crate::language_service::symbol_table::SymbolTable;
+
+        let mut table = OpcodeTable::new(Game::SA);
+        table.load_from_file("SASCM.INI");
+        let mut ns = Namespaces::new();
+        ns.load_library("sa.json");
+        ns.load_classes("src/namespaces/test/classes_many.db");
+        let mut symbols = SymbolTable::new();
+        process_var_declaration("$a", &mut symbols, 0, 0, "Actor");
+        process_var_declaration("3@", &mut symbols, 0, 0, "Car");
+        let mut const_lookup = DictStrByStr::default();
+        const_lookup.add(CString::new("mycar").unwrap(), CString::new("3@").unwrap());
+        const_lookup.add(CString::new("full").unwrap(), CString::new("100").unwrap());
+
+        let env = TypeEnv {
+            ns: &ns,
+            symbols: &symbols,
+            line_number: 0,
+        };
+        let t = |input: &str| -> String {
+            transform_checked(input, &env, &table, &const_lookup)
+                .ok()
+                .flatten()
+                .map(|i| i.to_string())
+                .unwrap_or_default()
+        };
+
+        // setters and getters follow prop_pos
+        assert_eq!(t("Actor.Health($a) = 100"), "0223: $a 100");
+        assert_eq!(t("$hp = Actor.Health($a)"), "0226: $a $hp");
+        assert_eq!(t("$a.Health = full"), "0223: $a 100");
+        assert_eq!(t("0@ = $a.Health"), "0226: $a 0@");
+        assert_eq!(t("mycar.Angle = 90.0"), "0175: 3@ 90.0");
+        assert_eq!(t("1@ = mycar.Angle"), "0174: 3@ 1@");
+
+        // other operations
+        assert_eq!(t("Player.Money($p) += 100"), "0109: $p 100");
+        assert_eq!(t("player.money($p) > 0@"), "010A: $p 0@");
+        assert_eq!(t("Car.Model(mycar) == 400"), "0137: 3@ 400");
+        assert_eq!(t("$m = Car.Model(mycar)"), "0441: 3@ $m");
+
+        // constructors take the handle first
+        assert_eq!(
+            t("$x = Actor.Create(4, 0@, 1.0, 2.0, 3.0)"),
+            "009A: $x 4 0@ 1.0 2.0 3.0"
+        );
+        assert_eq!(
+            t("Actor.Create($x, 4, 0@, 1.0, 2.0, 3.0)"),
+            "009A: $x 4 0@ 1.0 2.0 3.0"
+        );
+
+        // methods
+        assert_eq!(t("Actor.SetMaxHealth($a, 200)"), "08AF: $a 200");
+        assert_eq!(t("$a.SetMaxHealth(200)"), "08AF: $a 200");
+
+        // unknown classes, members and variables, unsupported operations
+        assert_eq!(t("Foo.Health($a) = 100"), "");
+        assert_eq!(t("Actor.Foo($a) = 100"), "");
+        assert_eq!(t("$b.Health = 100"), "");
+        assert_eq!(t("Actor.Health($a) -= 100"), "");
+        assert_eq!(t("100 = Actor.Health($a)"), "");
+
+        // opcodes without a library definition
+        let mut ns = Namespaces::new();
+        ns.load_classes("src/namespaces/test/classes_many.db");
+        let env = TypeEnv {
+            ns: &ns,
+            symbols: &symbols,
+            line_number: 0,
+        };
+        assert_eq!(
+            transform_checked("Actor.Health($a) = 100", &env, &table, &const_lookup)
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "0223: $a 100"
+        );
+    }
+
+    #[test]
+    fn test_operator_lookup() {
+        use crate::language_service::scanner::process_var_declaration;
+        use crate::language_service::symbol_table::SymbolTable;
+
+        // command names differ from SA, commands are found by the operator tags
+        let mut table = OpcodeTable::new(Game::SA);
+        table.load_from_file("SASCM.INI");
+        let mut ns = Namespaces::new();
+        ns.load_library("src/namespaces/test/operators.json");
+        let const_lookup = DictStrByStr::default();
+        let mut symbols = SymbolTable::new();
+        process_var_declaration("$i", &mut symbols, 0, 0, "int");
+        process_var_declaration("$f", &mut symbols, 0, 0, "float");
+
+        let env = TypeEnv {
+            ns: &ns,
+            symbols: &symbols,
+            line_number: 0,
+        };
+        let t = |input: &str| -> String {
+            transform_checked(input, &env, &table, &const_lookup)
+                .ok()
+                .flatten()
+                .map(|i| i.to_string())
+                .unwrap_or_default()
+        };
+        assert_eq!(t("$x = 1"), "0004: $x 1");
+        assert_eq!(t("0@ = -1.5"), "0007: 0@ -1.5");
+        assert_eq!(t("$x +=@ 1.0"), "0078: $x 1.0");
+        assert_eq!(t("0@ &= 1"), "0B17: 0@ 1");
+        assert_eq!(t("~0@"), "0B1A: 0@");
+        assert_eq!(t("0@ = 1 & 2"), "0B10: 0@ 1 2");
+        assert_eq!(t("$i =# $f"), "008C: $i $f");
+        assert_eq!(t("0@ > 1"), "0019: 0@ 1");
+        assert_eq!(t("1 > $x"), "001A: 1 $x");
+        assert_eq!(t("$x <> 1"), "8038: $x 1");
+
+        // no tagged command for the operands
+        assert_eq!(t("0@ +=@ 1.0"), "");
+        assert_eq!(t("0@ == 1"), "");
+        assert_eq!(t("0@ += 1"), "");
+
+        let mut temps = TempPool::new(["10@"]);
+        let result = transform_sequence("0@ = 1@ + 2 & 3", &ns, &table, &const_lookup, &mut temps)
+            .unwrap()
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(result, vec!["0A8E: 10@ 1@ 2", "0B10: 0@ 10@ 3"]);
+    }
+
+    #[test]
+    fn test_text_label() {
+        use crate::language_service::symbol_table::SymbolTable;
+
+        let mut table = OpcodeTable::new(Game::SA);
+        table.load_from_file("SASCM.INI");
+        let mut ns = Namespaces::new();
+        ns.load_library("sa.json");
+        let mut const_lookup = DictStrByStr::default();
+        const_lookup.add(
+            CString::new("greeting").unwrap(),
+            CString::new("'HELLO'").unwrap(),
+        );
+        let t = |input: &str| -> String {
+            transform(input, &ns, &table, &const_lookup).unwrap_or_default()
+        };
+
+        assert_eq!(t("s$a = 'text'"), "05A9: s$a 'text'");
+        assert_eq!(t("0@s = s$b"), "05AA: 0@s s$b");
+        assert_eq!(t(r#"v$a = "long\"text""#), r#"06D1: v$a "long\"text""#);
+        assert_eq!(t("1@v = 0@v"), "06D2: 1@v 0@v");
+        assert_eq!(t("$arr(0@,4s) = greeting"), "05A9: $arr(0@,4s) 'HELLO'");
+        assert_eq!(t("s$a == 'text'"), "05AD: s$a 'text'");
+        assert_eq!(t("'text' <> 0@s"), "85AE: 0@s 'text'");
+        assert_eq!(t(r#"v$a == "text""#), "08F9: v$a \"text\"");
+        assert_eq!(t("0@v <> v$b"), "88FA: 0@v v$b");
+
+        // the variable must be a string
+        assert_eq!(t("$a = 'text'"), "");
+        assert_eq!(t("0@ == 'text'"), "");
+        assert_eq!(t("s$a += 'text'"), "");
+        assert_eq!(t("'text' = s$a"), "");
+
+        let symbols = SymbolTable::new();
+        let env = TypeEnv {
+            ns: &ns,
+            symbols: &symbols,
+            line_number: 0,
+        };
+        let conditions = transform_conditions("if s$a == 'text' and 0@ > 1", &env, &const_lookup)
+            .unwrap()
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            conditions,
+            vec!["00D6: 1", "05AD: s$a 'text'", "0019: 0@ 1"]
+        );
+
+        // an assignment is not a condition
+        assert!(transform_conditions("if s$a = 'text'", &env, &const_lookup).is_none());
+    }
+
+    #[test]
+    fn test_function_call() {
+        use crate::language_service::scanner::{
+            process_const_declaration, process_var_declaration,
+        };
+        use crate::language_service::symbol_table::SymbolTable;
+
+        let mut ns = Namespaces::new();
+        ns.load_library("sa.json");
+        let mut const_lookup = DictStrByStr::default();
+        const_lookup.add(CString::new("max").unwrap(), CString::new("10").unwrap());
+        // x is allocated by the compiler, y is not
+        const_lookup.add(CString::new("x").unwrap(), CString::new("3@").unwrap());
+
+        let mut symbols = SymbolTable::new();
+        process_var_declaration("x", &mut symbols, 0, 0, "int");
+        process_var_declaration("y", &mut symbols, 0, 0, "float");
+        process_const_declaration("z = 5@", &mut symbols, 0, 0);
+        let env = TypeEnv {
+            ns: &ns,
+            symbols: &symbols,
+            line_number: 1,
+        };
+        let t = |input: &str, signature: &str| -> String {
+            match transform_call(input, signature, &env, &const_lookup) {
+                Ok(i) => i.map(|i| i.to_string()).unwrap_or_default(),
+                Err(errors) => errors
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            }
+        };
+
+        let local = "function MyFunc(a: int, b: float): int, float";
+        assert_eq!(t("MyFunc(2@, 3.0)", local), "0AB1: @MyFunc 2 2@ 3.0");
+        assert_eq!(
+            t("0@, 1@ = myfunc(max, 3.0)", local),
+            "0AB1: @MyFunc 2 10 3.0 0@ 1@"
+        );
+        assert_eq!(t("Other(2@, 3.0)", local), "");
+        assert_eq!(
+            t("MyFunc(2@)", local),
+            "function `MyFunc` expects 2 arguments, found 1"
+        );
+        assert_eq!(
+            t("MyFunc(1.0, 3.0)", local),
+            "expected int, found float value `1.0`"
+        );
+        assert_eq!(
+            t("0@ = MyFunc(1, 3.0)", local),
+            "function `MyFunc` returns 2 values, found 1 variable"
+        );
+        assert_eq!(
+            t("y, x = MyFunc(1, 3.0)", local),
+            "cannot assign int result to float variable `y`; cannot assign float result to int variable `x`"
+        );
+        assert_eq!(
+            t("x, y = MyFunc(1, 3.0)", local),
+            "variable `y` is not allocated"
+        );
+        assert_eq!(t("MyFunc(1, y)", local), "variable `y` is not allocated");
+
+        let pair = "function Pair(a: int): int, int";
+        assert_eq!(t("x, z = Pair(z)", pair), "0AB1: @Pair 1 5@ 3@ 5@");
+
+        let variadic = "function Log(format: string, ...args: int)";
+        assert_eq!(
+            t("Log('%d %d', 1, 2)", variadic),
+            "0AB1: @Log 3 '%d %d' 1 2"
+        );
+        assert_eq!(
+            t("Log()", variadic),
+            "function `Log` expects at least 1 argument, found 0"
+        );
+
+        // native calls take the arguments in reverse order
+        let cdecl = "function Foo<cdecl, 0x400000>(a: int, b: float): int";
+        assert_eq!(t("Foo(1, 2.0)", cdecl), "0AA5: 0x400000 2 2 2.0 1");
+        assert_eq!(t("0@ = Foo(1, 2.0)", cdecl), "0AA7: 0x400000 2 2 2.0 1 0@");
+        let stdcall = "function Bar<stdcall, 0x400000>(a: int)";
+        assert_eq!(t("Bar(1)", stdcall), "0AA5: 0x400000 1 0 1");
+        let thiscall = "function Baz<thiscall, 0x400000>(struct: int, a: int): int";
+        assert_eq!(t("Baz(0@, 1)", thiscall), "0AA6: 0x400000 0@ 1 0 1");
+        assert_eq!(t("1@ = Baz(0@, 1)", thiscall), "0AA8: 0x400000 0@ 1 0 1 1@");
+        let native_pair = "function Qux<cdecl, 0x400000>(): int, int";
+        assert_eq!(
+            t("0@, 1@ = Qux()", native_pair),
+            "native function `Qux` returns one value at most, found 2 variables"
+        );
+    }
+
+    #[test]
+    fn test_enum_member() {
+        use crate::language_service::symbol_table::SymbolTable;
+
+        let mut table = OpcodeTable::new(Game::SA);
+        table.load_from_file("SASCM.INI");
+        let mut ns = Namespaces::new();
+        ns.load_library("sa.json");
+        ns.load_enums("src/namespaces/test/enums_1.txt");
+        ns.load_enums("src/namespaces/test/enums_2.txt");
+        ns.load_enums("src/namespaces/test/enums_3.txt");
+        let mut const_lookup = DictStrByStr::default();
+        const_lookup.add(
+            CString::new("medic").unwrap(),
+            CString::new("PedType.Medic").unwrap(),
+        );
+
+        let t = |input: &str| -> String {
+            transform(input, &ns, &table, &const_lookup).unwrap_or_default()
+        };
+        assert_eq!(t("0@ = PedType.CivMale"), "0006: 0@ 4");
+        assert_eq!(t("0@ += WeaponType.Pistol"), "000A: 0@ 22");
+        assert_eq!(t("$a -= 1.5"), "000D: $a 1.5");
+        assert_eq!(t("$a = pedtype.COP"), "0004: $a 6");
+        assert_eq!(t("0@ = medic"), "0006: 0@ 18");
+        assert_eq!(t("0@ = 1@ + PedType.Medic"), "0A8E: 0@ 1@ 18");
+        assert_eq!(t("s$a = String.Y"), "05A9: s$a 'Y'");
+        assert_eq!(t("s$a = Quote.Apostrophe"), "05A9: s$a 'it\\'s'");
+        assert_eq!(t("s$a = Quote.Backslash"), "05A9: s$a 'a\\\\b'");
+        assert_eq!(t("0@ = PedType.CivMal"), "");
+
+        let enum_error = |input: &str| enum_error(input, &ns).map(|e| e.to_string());
+        assert_eq!(
+            enum_error("0@ = PedType.CivMal"),
+            Some(String::from(
+                "`CivMal` is not a member of enum `PedType`, did you mean `PedType.CIVMALE`?"
+            ))
+        );
+        assert_eq!(enum_error("0@ = PedType.CivMale"), None);
+        assert_eq!(enum_error("s$a = PedType.Cop"), None);
+
+        let symbols = SymbolTable::new();
+        let env = TypeEnv {
+            ns: &ns,
+            symbols: &symbols,
+            line_number: 1,
+        };
+        let errors = |input: &str| -> Vec<String> {
+            type_check(input, &env)
+                .iter()
+                .map(|e| e.to_string())
+                .collect()
+        };
+        assert!(errors("0@ = PedType.CivMale").is_empty());
+        assert_eq!(
+            errors("0@ = PedType.CivMal"),
+            vec!["`CivMal` is not a member of enum `PedType`, did you mean `PedType.CIVMALE`?"]
+        );
+        assert_eq!(
+            errors("0@ = WeaponType.Pistl + 1"),
+            vec!["`Pistl` is not a member of enum `WeaponType`, did you mean `WeaponType.PISTOL`?"]
+        );
+        assert_eq!(
+            errors("0@ = PedType.Xyz"),
+            vec!["`Xyz` is not a member of enum `PedType`"]
+        );
+        assert_eq!(
+            errors("s$a = PedType.Cop"),
+            vec!["cannot assign int value `PedType.Cop` to string variable"]
+        );
+    }
 }
@@ -1,64 +1,43 @@
+use super::eval::{evaluate, format_float, EvalError, Value};
 use super::helpers::*;
+use super::instruction::{Instruction, Operand, OperandType};
+use super::typecheck::{plural, Type, TypeEnv, TypeError};
 use crate::{
     dictionary::dictionary_str_by_str::DictStrByStr,
+    language_service::symbol_table::SymbolType,
     legacy_ini::OpcodeTable,
-    namespaces::namespaces::Namespaces,
+    namespaces::{
+        namespaces::{EnumMemberValue, Namespaces, OpcodeType},
+        OperandKind, Operator, ValueType,
+    },
     parser::{
-        interface::{Node, SyntaxKind, AST},
-        parse,
+        escape,
+        interface::{
+            Conditions, FunctionCC, FunctionSignature, MemberExpr, Node, StringLiteral, SyntaxKind,
+            Token, Variable, VariableType, AST,
+        },
+        parse_expression,
     },
 };
 use std::ffi::CString;
 
-static OP_AND: &'static str = "BIT_AND";
-static OP_OR: &'static str = "BIT_OR";
-static OP_XOR: &'static str = "BIT_XOR";
-static OP_NOT: &'static str = "BIT_NOT";
-static OP_MOD: &'static str = "MOD";
-static OP_SHR: &'static str = "BIT_SHR";
-static OP_SHL: &'static str = "BIT_SHL";
-
-static OP_AND_COMPOUND: &'static str = "BIT_AND_COMPOUND";
-static OP_OR_COMPOUND: &'static str = "BIT_OR_COMPOUND";
-static OP_XOR_COMPOUND: &'static str = "BIT_XOR_COMPOUND";
-static OP_NOT_UNARY: &'static str = "BIT_NOT_COMPOUND";
-static OP_MOD_COMPOUND: &'static str = "MOD_COMPOUND";
-static OP_SHR_COMPOUND: &'static str = "BIT_SHR_COMPOUND";
-static OP_SHL_COMPOUND: &'static str = "BIT_SHL_COMPOUND";
-
-static OP_SET_VAR_INT: &'static str = "SET_VAR_INT";
-static OP_SET_VAR_FLOAT: &'static str = "SET_VAR_FLOAT";
-static OP_SET_LVAR_INT: &'static str = "SET_LVAR_INT";
-static OP_SET_LVAR_FLOAT: &'static str = "SET_LVAR_FLOAT";
-
-static OP_INT_ADD: &'static str = "INT_ADD";
-static OP_INT_SUB: &'static str = "INT_SUB";
-static OP_INT_MUL: &'static str = "INT_MUL";
-static OP_INT_DIV: &'static str = "INT_DIV";
-
-static OP_ADD_TIMED_VAL_TO_FLOAT_VAR: &'static str = "ADD_TIMED_VAL_TO_FLOAT_VAR";
-static OP_ADD_TIMED_VAL_TO_FLOAT_LVAR: &'static str = "ADD_TIMED_VAL_TO_FLOAT_LVAR";
-static OP_ADD_TIMED_FLOAT_VAR_TO_FLOAT_VAR: &'static str = "ADD_TIMED_FLOAT_VAR_TO_FLOAT_VAR";
-static OP_ADD_TIMED_FLOAT_LVAR_TO_FLOAT_LVAR: &'static str = "ADD_TIMED_FLOAT_LVAR_TO_FLOAT_LVAR";
-static OP_ADD_TIMED_FLOAT_VAR_TO_FLOAT_LVAR: &'static str = "ADD_TIMED_FLOAT_VAR_TO_FLOAT_LVAR";
-static OP_ADD_TIMED_FLOAT_LVAR_TO_FLOAT_VAR: &'static str = "ADD_TIMED_FLOAT_LVAR_TO_FLOAT_VAR";
-static OP_SUB_TIMED_VAL_FROM_FLOAT_VAR: &'static str = "SUB_TIMED_VAL_FROM_FLOAT_VAR";
-static OP_SUB_TIMED_VAL_FROM_FLOAT_LVAR: &'static str = "SUB_TIMED_VAL_FROM_FLOAT_LVAR";
-static OP_SUB_TIMED_FLOAT_VAR_FROM_FLOAT_VAR: &'static str = "SUB_TIMED_FLOAT_VAR_FROM_FLOAT_VAR";
-static OP_SUB_TIMED_FLOAT_LVAR_FROM_FLOAT_LVAR: &'static str =
-    "SUB_TIMED_FLOAT_LVAR_FROM_FLOAT_LVAR";
-static OP_SUB_TIMED_FLOAT_VAR_FROM_FLOAT_LVAR: &'static str = "SUB_TIMED_FLOAT_VAR_FROM_FLOAT_LVAR";
-static OP_SUB_TIMED_FLOAT_LVAR_FROM_FLOAT_VAR: &'static str = "SUB_TIMED_FLOAT_LVAR_FROM_FLOAT_VAR";
-
-// static OP_CSET_VAR_INT_TO_VAR_FLOAT: &'static str = "CSET_VAR_INT_TO_VAR_FLOAT";
-// static OP_CSET_VAR_FLOAT_TO_VAR_INT: &'static str = "CSET_VAR_FLOAT_TO_VAR_INT";
-// static OP_CSET_LVAR_INT_TO_VAR_FLOAT: &'static str = "CSET_LVAR_INT_TO_VAR_FLOAT";
-// static OP_CSET_LVAR_FLOAT_TO_VAR_INT: &'static str = "CSET_LVAR_FLOAT_TO_VAR_INT";
-
-// static OP_CSET_VAR_INT_TO_LVAR_FLOAT: &'static str = "CSET_VAR_INT_TO_LVAR_FLOAT";
-// static OP_CSET_VAR_FLOAT_TO_LVAR_INT: &'static str = "CSET_VAR_FLOAT_TO_LVAR_INT";
-// static OP_CSET_LVAR_INT_TO_LVAR_FLOAT: &'static str = "CSET_LVAR_INT_TO_LVAR_FLOAT";
-// static OP_CSET_LVAR_FLOAT_TO_LVAR_INT: &'static str = "CSET_LVAR_FLOAT_TO_LVAR_INT";
+// ANDOR in the original compiler
+static OP_IF: &'static str = "IF";
+
+static OP_SET_VAR_TEXT_LABEL: &'static str = "SET_VAR_TEXT_LABEL";
+static OP_SET_LVAR_TEXT_LABEL: &'static str = "SET_LVAR_TEXT_LABEL";
+static OP_SET_VAR_TEXT_LABEL16: &'static str = "SET_VAR_TEXT_LABEL16";
+static OP_SET_LVAR_TEXT_LABEL16: &'static str = "SET_LVAR_TEXT_LABEL16";
+static OP_IS_VAR_TEXT_LABEL_EQUAL: &'static str = "IS_VAR_TEXT_LABEL_EQUAL_TO_TEXT_LABEL";
+static OP_IS_LVAR_TEXT_LABEL_EQUAL: &'static str = "IS_LVAR_TEXT_LABEL_EQUAL_TO_TEXT_LABEL";
+static OP_IS_VAR_TEXT_LABEL16_EQUAL: &'static str = "IS_VAR_TEXT_LABEL16_EQUAL_TO_TEXT_LABEL";
+static OP_IS_LVAR_TEXT_LABEL16_EQUAL: &'static str = "IS_LVAR_TEXT_LABEL16_EQUAL_TO_TEXT_LABEL";
+
+static OP_CLEO_CALL: &'static str = "CLEO_CALL";
+static OP_CALL_FUNCTION: &'static str = "CALL_FUNCTION";
+static OP_CALL_FUNCTION_RETURN: &'static str = "CALL_FUNCTION_RETURN";
+static OP_CALL_METHOD: &'static str = "CALL_METHOD";
+static OP_CALL_METHOD_RETURN: &'static str = "CALL_METHOD_RETURN";
 
 pub fn try_tranform(
     ast: &AST,
@@ -67,70 +46,34 @@ pub fn try_tranform(
     legacy_ini: &OpcodeTable,
     const_lookup: &DictStrByStr,
 ) -> Option<String> {
-    let e = ast.body.get(0)?;
+    try_transform_instruction(ast, expr, ns, legacy_ini, const_lookup).map(|i| i.to_string())
+}
 
-    macro_rules! resolve {
-        ($node: expr) => {{
-            let x = |token| -> Option<(Node, String)> {
-                // keys in DictStrByStr are lower-case
-                let name = token_str(expr, token).to_ascii_lowercase();
-
-                let const_value = const_lookup
-                    .map
-                    .get(&CString::new(name).unwrap())
-                    .map(|v| v.to_str())?
-                    .ok()?;
-
-                let ast = parse(const_value).ok()?.1;
-                let node = ast.body.get(0)?.clone();
-                let text = token_str(const_value, as_token(&node)?);
-                match node {
-                    Node::Unary(unary) if unary.get_operator() == &SyntaxKind::OperatorMinus => {
-                        Some((unary.operand.as_ref().clone(), String::from(text)))
-                    }
-                    _ => Some((node, String::from(text))),
-                }
-            };
-            match $node.as_ref() {
-                Node::Literal(token) if is_identifier($node) => x(token)?,
-                Node::Unary(unary) if unary.get_operator() == &SyntaxKind::OperatorMinus => {
-                    match unary.operand.as_ref() {
-                        Node::Literal(_) if is_identifier(&unary.operand) => {
-                            let (token, text) = x(as_token(&unary.operand)?)?;
-
-                            // todo: operate on the number
-                            if text.starts_with('-') {
-                                (token, format!("{}", &text[1..]))
-                            } else {
-                                (token, format!("-{}", text))
-                            }
-                        }
-                        Node::Literal(_) if is_number(&unary.operand) => (
-                            unary.operand.as_ref().clone(),
-                            format!("-{}", token_str(expr, as_token(&unary.operand)?)),
-                        ),
-                        _ => (
-                            $node.as_ref().clone(),
-                            String::from(token_str(expr, as_token($node)?)),
-                        ),
-                    }
-                }
-                _ => (
-                    $node.as_ref().clone(),
-                    String::from(token_str(expr, as_token($node)?)),
-                ),
-            }
-        }};
+pub fn try_transform_instruction<'a>(
+    ast: &AST,
+    expr: &str,
+    ns: &'a Namespaces,
+    legacy_ini: &OpcodeTable,
+    const_lookup: &DictStrByStr,
+) -> Option<Instruction<'a>> {
+    let e = ast.body.get(0)?;
+    if let Some(instruction) = try_transform_text_label(e, expr, ns, const_lookup, false) {
+        return Some(instruction);
     }
 
     return match e {
         Node::Unary(e) => {
             if e.get_operator() == &SyntaxKind::OperatorBitwiseNot {
-                let (var, var_name) = resolve!(&e.operand);
+                let (var, var_name) = resolve(&e.operand, expr, ns, const_lookup)?;
                 if is_variable(&var) {
                     // ~var
-                    let op_id = *ns.get_opcode_by_command_name(OP_NOT_UNARY)?;
-                    return format_unary(op_id, &var_name);
+                    let kind = as_operator_operand_kind(e.get_operator(), &var);
+                    let op_id = find_opcode(ns, Some(Operator::Not), &[kind])?;
+                    return Some(Instruction::new(
+                        ns,
+                        op_id,
+                        vec![as_operand(&var, var_name)?],
+                    ));
                 }
             }
             None
@@ -145,63 +88,91 @@ pub fn try_tranform(
                     if !matches!(operator, SyntaxKind::OperatorEqual) {
                         return None;
                     }
-                    let (var, var_name) = resolve!(left);
-                    let (_, operand_name) = resolve!(&unary.operand);
+                    let (var, var_name) = resolve(left, expr, ns, const_lookup)?;
+                    let (operand, operand_name) = resolve(&unary.operand, expr, ns, const_lookup)?;
                     if !is_variable(&var) {
                         return None;
                     }
                     // var = ~var
-                    return format_binary(
-                        *ns.get_opcode_by_command_name(OP_NOT)?,
-                        &var_name,
-                        &operand_name,
-                        legacy_ini,
-                    );
+                    let op_id = find_opcode(
+                        ns,
+                        Some(Operator::Not),
+                        &[
+                            as_operator_operand_kind(unary.get_operator(), &var),
+                            as_operator_operand_kind(unary.get_operator(), &operand),
+                        ],
+                    )?;
+                    return Some(Instruction::new(
+                        ns,
+                        op_id,
+                        binary_operands(
+                            op_id,
+                            as_operand(&var, var_name)?,
+                            as_operand(&operand, operand_name)?,
+                            legacy_ini,
+                        ),
+                    ));
                 }
                 Node::Binary(binary_expr) => {
                     if !matches!(operator, SyntaxKind::OperatorEqual) {
                         return None;
                     }
 
-                    let op = |op| {
-                        let (var, var_name) = resolve!(left);
-                        let (left_operand, left_operand_name) = resolve!(&binary_expr.left);
-                        let (right_operand, right_operand_name) = resolve!(&binary_expr.right);
-                        if !is_variable(&var) {
-                            return None;
-                        }
-                        if !is_variable(&left_operand) && !is_number(&left_operand) {
-                            return None;
-                        }
-                        if !is_variable(&right_operand) && !is_number(&right_operand) {
-                            return None;
-                        }
-                        format_ternary(
-                            *ns.get_opcode_by_command_name(op)?,
-                            &var_name,
-                            &left_operand_name,
-                            &right_operand_name,
-                            legacy_ini,
-                        )
-                    };
-                    match binary_expr.get_operator() {
-                        SyntaxKind::OperatorBitwiseAnd => op(OP_AND),
-                        SyntaxKind::OperatorBitwiseOr => op(OP_OR),
-                        SyntaxKind::OperatorBitwiseXor => op(OP_XOR),
-                        SyntaxKind::OperatorBitwiseMod => op(OP_MOD),
-                        SyntaxKind::OperatorBitwiseShr => op(OP_SHR),
-                        SyntaxKind::OperatorBitwiseShl => op(OP_SHL),
-                        SyntaxKind::OperatorPlus => op(OP_INT_ADD),
-                        SyntaxKind::OperatorMinus => op(OP_INT_SUB),
-                        SyntaxKind::OperatorMul => op(OP_INT_MUL),
-                        SyntaxKind::OperatorDiv => op(OP_INT_DIV),
-                        _ => None,
+                    // var = a op b
+                    if !matches!(
+                        binary_expr.get_operator(),
+                        SyntaxKind::OperatorBitwiseAnd
+                            | SyntaxKind::OperatorBitwiseOr
+                            | SyntaxKind::OperatorBitwiseXor
+                            | SyntaxKind::OperatorBitwiseMod
+                            | SyntaxKind::OperatorBitwiseShr
+                            | SyntaxKind::OperatorBitwiseShl
+                            | SyntaxKind::OperatorPlus
+                            | SyntaxKind::OperatorMinus
+                            | SyntaxKind::OperatorMul
+                            | SyntaxKind::OperatorDiv
+                    ) {
+                        return None;
+                    }
+                    let (var, var_name) = resolve(left, expr, ns, const_lookup)?;
+                    let (left_operand, left_operand_name) =
+                        resolve(&binary_expr.left, expr, ns, const_lookup)?;
+                    let (right_operand, right_operand_name) =
+                        resolve(&binary_expr.right, expr, ns, const_lookup)?;
+                    if !is_variable(&var) {
+                        return None;
+                    }
+                    if !is_variable(&left_operand) && !is_number(&left_operand) {
+                        return None;
+                    }
+                    if !is_variable(&right_operand) && !is_number(&right_operand) {
+                        return None;
                     }
+                    let op_id = find_opcode(
+                        ns,
+                        as_library_operator(binary_expr.get_operator()),
+                        &[
+                            as_operator_operand_kind(binary_expr.get_operator(), &var),
+                            as_operator_operand_kind(binary_expr.get_operator(), &left_operand),
+                            as_operator_operand_kind(binary_expr.get_operator(), &right_operand),
+                        ],
+                    )?;
+                    Some(Instruction::new(
+                        ns,
+                        op_id,
+                        ternary_operands(
+                            op_id,
+                            as_operand(&var, var_name)?,
+                            as_operand(&left_operand, left_operand_name)?,
+                            as_operand(&right_operand, right_operand_name)?,
+                            legacy_ini,
+                        ),
+                    ))
                 }
-                Node::Literal(_) | Node::Variable(_) | Node::Unary(_) => {
-                    let (var, var_name) = resolve!(left);
-                    let (right_operand, right_operand_name) = resolve!(&right);
-                    let right_token = as_token(&right_operand)?;
+                Node::Literal(_) | Node::Variable(_) | Node::Unary(_) | Node::Member(_) => {
+                    let (var, var_name) = resolve(left, expr, ns, const_lookup)?;
+                    let (right_operand, right_operand_name) =
+                        resolve(&right, expr, ns, const_lookup)?;
                     if !is_variable(&var) {
                         return None;
                     }
@@ -209,205 +180,37 @@ pub fn try_tranform(
                         return None;
                     }
 
-                    let op = |op| {
-                        format_binary_no_reorder(
-                            *ns.get_opcode_by_command_name(op)?,
-                            &var_name,
-                            &right_operand_name,
-                        )
-                    };
                     match operator {
-                        SyntaxKind::OperatorBitwiseAndEqual => op(OP_AND_COMPOUND),
-                        SyntaxKind::OperatorBitwiseOrEqual => op(OP_OR_COMPOUND),
-                        SyntaxKind::OperatorBitwiseXorEqual => op(OP_XOR_COMPOUND),
-                        SyntaxKind::OperatorBitwiseModEqual => op(OP_MOD_COMPOUND),
-                        SyntaxKind::OperatorBitwiseShrEqual => op(OP_SHR_COMPOUND),
-                        SyntaxKind::OperatorBitwiseShlEqual => op(OP_SHL_COMPOUND),
-                        SyntaxKind::OperatorTimedAdditionEqual => {
-                            let left_var = as_variable(&var)?;
-                            match right_token.syntax_kind {
-                                SyntaxKind::GlobalVariable if left_var.is_global() => {
-                                    // var +=@ var
-                                    return op(OP_ADD_TIMED_FLOAT_VAR_TO_FLOAT_VAR);
-                                }
-                                SyntaxKind::LocalVariable if left_var.is_local() => {
-                                    // lvar +=@ lvar
-                                    return op(OP_ADD_TIMED_FLOAT_LVAR_TO_FLOAT_LVAR)
-                                        .or(op(OP_ADD_TIMED_FLOAT_VAR_TO_FLOAT_VAR));
-                                    // vcs
-                                }
-                                SyntaxKind::LocalVariable if left_var.is_global() => {
-                                    // var +=@ lvar
-                                    return op(OP_ADD_TIMED_FLOAT_LVAR_TO_FLOAT_VAR)
-                                        .or(op(OP_ADD_TIMED_FLOAT_VAR_TO_FLOAT_VAR));
-                                    // vcs
-                                }
-                                SyntaxKind::GlobalVariable if left_var.is_local() => {
-                                    // lvar +=@ var
-                                    return op(OP_ADD_TIMED_FLOAT_VAR_TO_FLOAT_LVAR)
-                                        .or(op(OP_ADD_TIMED_FLOAT_VAR_TO_FLOAT_VAR));
-                                    // vcs
-                                }
-
-                                _ => {
-                                    let right_number = as_number(&right_operand)?;
-                                    if right_number.syntax_kind == SyntaxKind::FloatLiteral {
-                                        if left_var.is_global() {
-                                            // var +=@ float
-                                            return op(OP_ADD_TIMED_VAL_TO_FLOAT_VAR);
-                                        }
-                                        if left_var.is_local() {
-                                            // lvar +=@ float
-                                            return op(OP_ADD_TIMED_VAL_TO_FLOAT_LVAR)
-                                                .or(op(OP_ADD_TIMED_VAL_TO_FLOAT_VAR));
-                                            // vcs
-                                        }
-                                    }
-                                    None
-                                }
-                            }
-                        }
-                        SyntaxKind::OperatorTimedSubtractionEqual => {
-                            let left_var = as_variable(&var)?;
-                            match right_token.syntax_kind {
-                                SyntaxKind::GlobalVariable if left_var.is_global() => {
-                                    // var -=@ var
-                                    return op(OP_SUB_TIMED_FLOAT_VAR_FROM_FLOAT_VAR);
-                                }
-                                SyntaxKind::LocalVariable if left_var.is_local() => {
-                                    // lvar -=@ lvar
-                                    return op(OP_SUB_TIMED_FLOAT_LVAR_FROM_FLOAT_LVAR)
-                                        .or(op(OP_SUB_TIMED_FLOAT_VAR_FROM_FLOAT_VAR));
-                                }
-                                SyntaxKind::LocalVariable if left_var.is_global() => {
-                                    // var -=@ lvar
-                                    return op(OP_SUB_TIMED_FLOAT_LVAR_FROM_FLOAT_VAR)
-                                        .or(op(OP_SUB_TIMED_FLOAT_VAR_FROM_FLOAT_VAR));
-                                }
-                                SyntaxKind::GlobalVariable if left_var.is_local() => {
-                                    // lvar -=@ var
-                                    return op(OP_SUB_TIMED_FLOAT_VAR_FROM_FLOAT_LVAR)
-                                        .or(op(OP_SUB_TIMED_FLOAT_VAR_FROM_FLOAT_VAR));
-                                }
-                                _ => {
-                                    let right_number = as_number(&right_operand)?;
-
-                                    if right_number.syntax_kind == SyntaxKind::FloatLiteral {
-                                        if left_var.is_global() {
-                                            // var -=@ float
-                                            return op(OP_SUB_TIMED_VAL_FROM_FLOAT_VAR);
-                                        }
-                                        if left_var.is_local() {
-                                            // lvar -=@ float
-                                            return op(OP_SUB_TIMED_VAL_FROM_FLOAT_LVAR)
-                                                .or(op(OP_SUB_TIMED_VAL_FROM_FLOAT_VAR));
-                                        }
-                                    }
-                                    None
-                                }
-                            }
-                        }
-                        // SyntaxKind::OperatorCastEqual => {
-                        //     // requires type info
-                        //     if !is_variable(&right_operand) {
-                        //         return None;
-                        //     }
-                        //     let left_var = as_variable(&var)?;
-                        //     let right_var = as_variable(&right_operand)?;
-
-                        //     let l1 =
-                        //         &CString::new(token_str(expr, left_var.get_var_name())).unwrap();
-                        //     let l2 =
-                        //         &CString::new(token_str(expr, right_var.get_var_name())).unwrap();
-                        //     let t1 = *var_types.map.get(l1)?;
-                        //     let t2 = *var_types.map.get(l2)?;
-
-                        //     use crate::utils::compiler_const::*;
-                        //     if right_var.is_global() {
-                        //         if left_var.is_global() {
-                        //             if t1 == TOKEN_INT && t2 == TOKEN_FLOAT {
-                        //                 // var =# var // int = float
-                        //                 return op(OP_CSET_VAR_INT_TO_VAR_FLOAT);
-                        //             };
-                        //             if t1 == TOKEN_FLOAT && t2 == TOKEN_INT {
-                        //                 // var =# var // float = int
-                        //                 return op(OP_CSET_VAR_FLOAT_TO_VAR_INT);
-                        //             };
-                        //         }
-
-                        //         if left_var.is_local() {
-                        //             if t1 == TOKEN_INT && t2 == TOKEN_FLOAT {
-                        //                 // lvar =# var // int = float
-                        //                 return op(OP_CSET_LVAR_INT_TO_VAR_FLOAT)
-                        //                     .or(op(OP_CSET_VAR_INT_TO_VAR_FLOAT));
-                        //             }
-                        //             if t1 == TOKEN_FLOAT && t2 == TOKEN_INT {
-                        //                 // lvar =# var // float = int
-                        //                 return op(OP_CSET_LVAR_FLOAT_TO_VAR_INT)
-                        //                     .or(op(OP_CSET_VAR_FLOAT_TO_VAR_INT));
-                        //             }
-                        //         }
-                        //     }
-
-                        //     if right_var.is_local() {
-                        //         if left_var.is_local() {
-                        //             if t1 == TOKEN_INT && t2 == TOKEN_FLOAT {
-                        //                 // lvar =# lvar // int = float
-                        //                 return op(OP_CSET_LVAR_INT_TO_LVAR_FLOAT)
-                        //                     .or(op(OP_CSET_VAR_INT_TO_VAR_FLOAT));
-                        //             }
-
-                        //             if t1 == TOKEN_FLOAT && t2 == TOKEN_INT {
-                        //                 // lvar =# lvar // float = int
-                        //                 return op(OP_CSET_LVAR_FLOAT_TO_LVAR_INT)
-                        //                     .or(op(OP_CSET_VAR_FLOAT_TO_VAR_INT));
-                        //             }
-                        //         }
-
-                        //         if left_var.is_global() {
-                        //             if t1 == TOKEN_INT && t2 == TOKEN_FLOAT {
-                        //                 // var =# lvar // int = float
-                        //                 return op(OP_CSET_VAR_INT_TO_LVAR_FLOAT)
-                        //                     .or(op(OP_CSET_VAR_INT_TO_VAR_FLOAT));
-                        //             }
-
-                        //             if t1 == TOKEN_FLOAT && t2 == TOKEN_INT {
-                        //                 // var =# lvar // float = int
-                        //                 return op(OP_CSET_VAR_FLOAT_TO_LVAR_INT)
-                        //                     .or(op(OP_CSET_VAR_FLOAT_TO_VAR_INT));
-                        //             }
-                        //         }
-                        //     }
-
-                        //     return None;
-                        // }
-                        SyntaxKind::OperatorEqual => {
-                            let left_var = as_variable(&var)?;
-                            let right_number = as_number(&right_operand)?;
-
-                            match right_number.syntax_kind {
-                                // var = int
-                                SyntaxKind::IntegerLiteral | SyntaxKind::LabelLiteral
-                                    if left_var.is_global() =>
-                                {
-                                    op(OP_SET_VAR_INT)
-                                }
-                                // var = float
-                                SyntaxKind::FloatLiteral if left_var.is_global() => {
-                                    op(OP_SET_VAR_FLOAT)
-                                }
-                                // lvar = int
-                                SyntaxKind::IntegerLiteral | SyntaxKind::LabelLiteral
-                                    if left_var.is_local() =>
-                                {
-                                    op(OP_SET_LVAR_INT).or(op(OP_SET_VAR_INT))
-                                }
-                                // lvar = float
-                                SyntaxKind::FloatLiteral if left_var.is_local() => {
-                                    op(OP_SET_LVAR_FLOAT).or(op(OP_SET_VAR_FLOAT))
-                                }
-                                _ => None,
-                            }
+                        // var = value, var op= value, var +=@ value
+                        SyntaxKind::OperatorEqual
+                        | SyntaxKind::OperatorPlusEqual
+                        | SyntaxKind::OperatorMinusEqual
+                        | SyntaxKind::OperatorMulEqual
+                        | SyntaxKind::OperatorDivEqual
+                        | SyntaxKind::OperatorTimedAdditionEqual
+                        | SyntaxKind::OperatorTimedSubtractionEqual
+                        | SyntaxKind::OperatorBitwiseAndEqual
+                        | SyntaxKind::OperatorBitwiseOrEqual
+                        | SyntaxKind::OperatorBitwiseXorEqual
+                        | SyntaxKind::OperatorBitwiseModEqual
+                        | SyntaxKind::OperatorBitwiseShrEqual
+                        | SyntaxKind::OperatorBitwiseShlEqual => {
+                            let op_id = find_opcode(
+                                ns,
+                                as_library_operator(operator),
+                                &[
+                                    as_operator_operand_kind(operator, &var),
+                                    as_operator_operand_kind(operator, &right_operand),
+                                ],
+                            )?;
+                            Some(Instruction::new(
+                                ns,
+                                op_id,
+                                vec![
+                                    as_operand(&var, var_name)?,
+                                    as_operand(&right_operand, right_operand_name)?,
+                                ],
+                            ))
                         }
                         _ => None,
                     }
@@ -418,3 +221,668 @@ pub fn try_tranform(
         _ => None,
     };
 }
+
+/// lowers string assignments and comparisons (`s$a = 'text'`, `0@v <> v$b`) into the text label commands.
+/// the variable on the left gives the command: `s$`/`@s` use `SET_VAR_TEXT_LABEL`, `v$`/`@v` use `SET_VAR_TEXT_LABEL16`.
+/// `in_condition` only accepts comparisons, an assignment is not a condition
+pub fn try_transform_text_label<'a>(
+    node: &Node,
+    expr: &str,
+    ns: &'a Namespaces,
+    const_lookup: &DictStrByStr,
+    in_condition: bool,
+) -> Option<Instruction<'a>> {
+    let Node::Binary(e) = node else {
+        return None;
+    };
+    let (not, is_comparison) = match e.get_operator() {
+        SyntaxKind::OperatorEqual if !in_condition => (false, false),
+        SyntaxKind::OperatorEqualEqual => (false, true),
+        SyntaxKind::OperatorLessGreater => (true, true),
+        _ => return None,
+    };
+    let mut left = resolve(&e.left, expr, ns, const_lookup)?;
+    let mut right = resolve(&e.right, expr, ns, const_lookup)?;
+    // 'text' == s$var is s$var == 'text'
+    if is_comparison && !is_variable(&left.0) {
+        std::mem::swap(&mut left, &mut right);
+    }
+    let var = as_variable(&left.0)?;
+    let long = *as_string_type(&left.0)? == VariableType::LongString;
+    as_string_type(&right.0)?;
+
+    let op = |op| {
+        Some(Instruction::new(
+            ns,
+            *ns.get_opcode_by_command_name(op)?,
+            vec![
+                as_operand(&left.0, left.1.clone())?,
+                as_operand(&right.0, right.1.clone())?,
+            ],
+        ))
+    };
+    let instruction = match (is_comparison, var.is_global(), long) {
+        // s$var = 'text'
+        (false, true, false) => op(OP_SET_VAR_TEXT_LABEL),
+        // v$var = "text"
+        (false, true, true) => op(OP_SET_VAR_TEXT_LABEL16),
+        // 0@s = 'text'
+        (false, false, false) => op(OP_SET_LVAR_TEXT_LABEL).or(op(OP_SET_VAR_TEXT_LABEL)),
+        // 0@v = "text"
+        (false, false, true) => op(OP_SET_LVAR_TEXT_LABEL16).or(op(OP_SET_VAR_TEXT_LABEL16)),
+        // s$var == 'text'
+        (true, true, false) => op(OP_IS_VAR_TEXT_LABEL_EQUAL),
+        // v$var == "text"
+        (true, true, true) => op(OP_IS_VAR_TEXT_LABEL16_EQUAL),
+        // 0@s == 'text'
+        (true, false, false) => op(OP_IS_LVAR_TEXT_LABEL_EQUAL).or(op(OP_IS_VAR_TEXT_LABEL_EQUAL)),
+        // 0@v == "text"
+        (true, false, true) => {
+            op(OP_IS_LVAR_TEXT_LABEL16_EQUAL).or(op(OP_IS_VAR_TEXT_LABEL16_EQUAL))
+        }
+    }?;
+    Some(if not {
+        instruction.negate()
+    } else {
+        instruction
+    })
+}
+
+/// lowers the cast assignment (`var =# var`), which needs the declared types of both variables
+pub fn try_transform_cast<'a>(
+    ast: &AST,
+    expr: &str,
+    env: &TypeEnv<'a>,
+    const_lookup: &DictStrByStr,
+) -> Option<Instruction<'a>> {
+    let ns = env.ns;
+    let Node::Binary(e) = ast.body.get(0)? else {
+        return None;
+    };
+    if e.get_operator() != &SyntaxKind::OperatorCastEqual {
+        return None;
+    }
+    let (var, var_name) = resolve(&e.left, expr, ns, const_lookup)?;
+    let (right, right_name) = resolve(&e.right, expr, ns, const_lookup)?;
+    let (left_global, t1) = cast_var_type(&var, &var_name, env, const_lookup)?;
+    let (right_global, t2) = cast_var_type(&right, &right_name, env, const_lookup)?;
+    let kinds = [
+        Some(typed_var_kind(left_global, t1)),
+        Some(typed_var_kind(right_global, t2)),
+    ];
+
+    // int =# float or float =# int
+    let op_id = find_opcode(ns, Some(Operator::CastAssignment), &kinds)?;
+    Some(Instruction::new(
+        ns,
+        op_id,
+        vec![as_operand(&var, var_name)?, as_operand(&right, right_name)?],
+    ))
+}
+
+/// kind of a variable with the declared type
+fn typed_var_kind(global: bool, _type: ValueType) -> OperandKind {
+    if global {
+        OperandKind::GlobalVar(Some(_type))
+    } else {
+        OperandKind::LocalVar(Some(_type))
+    }
+}
+
+/// returns whether the variable is global and its declared type, class instances are ints.
+/// elements of SCR arrays (`$arr(0@,1f)`) carry the type themselves,
+/// the array name can be a constant aliasing a variable (`x[0]`, `x = 3@`)
+fn cast_var_type(
+    node: &Node,
+    text: &str,
+    env: &TypeEnv,
+    const_lookup: &DictStrByStr,
+) -> Option<(bool, ValueType)> {
+    let var = as_variable(node)?;
+    // tokens of the node can be relative to the constant value, not to the expression
+    let offset = as_token(node)?.start;
+    let name_token = var.get_var_name();
+    let name = text.get(name_token.start - offset..name_token.start - offset + name_token.len)?;
+
+    let declared = |name: &str| match env.declared_type(name) {
+        Type::Int | Type::Class(_) => Some(ValueType::Int),
+        Type::Float => Some(ValueType::Float),
+        _ => None,
+    };
+    let alias = || {
+        let value = const_lookup
+            .map
+            .get(&CString::new(name.to_ascii_lowercase()).ok()?)?
+            .to_str()
+            .ok()?;
+        match parse_expression(value).ok()?.1 {
+            Node::Variable(var) => Some((
+                var.is_global(),
+                declared(token_str(value, var.get_var_name())),
+            )),
+            _ => None,
+        }
+    };
+
+    let element_type = match var {
+        Variable::ArrayElement(e) => match e._type {
+            VariableType::Int => Some(ValueType::Int),
+            VariableType::Float => Some(ValueType::Float),
+            _ => None,
+        },
+        _ => None,
+    };
+    let alias = alias();
+    let global = alias.map_or(var.is_global(), |(global, _)| global);
+    let _type = element_type
+        .or_else(|| declared(name))
+        .or(alias.and_then(|(_, t)| t))?;
+    Some((global, _type))
+}
+
+/// lowers a comparison (`0@ > 5`, `$a <> 1.0`) into a conditional opcode, e.g. `IS_INT_LVAR_GREATER_THAN_NUMBER`.
+/// `<` and `<=` swap the operands, `<>` is `==` with the NOT bit set.
+/// comparing two variables needs their declared types, see [try_transform_cast]
+pub fn try_transform_comparison<'a>(
+    node: &Node,
+    expr: &str,
+    env: &TypeEnv<'a>,
+    const_lookup: &DictStrByStr,
+) -> Option<Instruction<'a>> {
+    let ns = env.ns;
+    let Node::Binary(e) = node else {
+        return None;
+    };
+    let (swap, not) = match e.get_operator() {
+        SyntaxKind::OperatorEqualEqual => (false, false),
+        SyntaxKind::OperatorLessGreater => (false, true),
+        SyntaxKind::OperatorGreater | SyntaxKind::OperatorGreaterEqual => (false, false),
+        SyntaxKind::OperatorLess | SyntaxKind::OperatorLessEqual => (true, false),
+        _ => return None,
+    };
+    let is_equality = matches!(
+        e.get_operator(),
+        SyntaxKind::OperatorEqualEqual | SyntaxKind::OperatorLessGreater
+    );
+    let mut left = resolve(&e.left, expr, ns, const_lookup)?;
+    let mut right = resolve(&e.right, expr, ns, const_lookup)?;
+    // a < b is b > a, there is no IS_NUMBER_EQUAL_TO_* so 1 == $var is $var == 1
+    if swap || (is_equality && is_number(&left.0)) {
+        std::mem::swap(&mut left, &mut right);
+    }
+    // labels are not numbers here
+    let number_kind = |node: &Node| match as_number(node)?.syntax_kind {
+        SyntaxKind::IntegerLiteral | SyntaxKind::FloatLiteral => as_operand_kind(node),
+        _ => None,
+    };
+    let var_kind = |var: &(Node, String)| {
+        let (global, _type) = cast_var_type(&var.0, &var.1, env, const_lookup)?;
+        Some(typed_var_kind(global, _type))
+    };
+
+    // the number gives the type, unless the variable is declared with another one
+    let number_var = |var: &(Node, String), number: &Node| {
+        let Some(OperandKind::Literal(_type)) = number_kind(number) else {
+            return None;
+        };
+        match var_kind(var) {
+            Some(kind) => Some(kind).filter(|kind| {
+                matches!(kind, OperandKind::GlobalVar(Some(t)) | OperandKind::LocalVar(Some(t)) if *t == _type)
+            }),
+            None if as_variable(&var.0)?.is_global() => Some(OperandKind::GlobalVar(Some(_type))),
+            None => Some(OperandKind::LocalVar(Some(_type))),
+        }
+    };
+
+    let kinds = if is_variable(&left.0) && is_number(&right.0) {
+        // var > int
+        [number_var(&left, &right.0)?, number_kind(&right.0)?]
+    } else if is_number(&left.0) && is_variable(&right.0) {
+        // int > var
+        [number_kind(&left.0)?, number_var(&right, &left.0)?]
+    } else if is_variable(&left.0) && is_variable(&right.0) {
+        // var > var
+        let left_kind = var_kind(&left)?;
+        let right_kind = var_kind(&right)?;
+        if is_equality
+            && matches!(left_kind, OperandKind::LocalVar(_))
+            && matches!(right_kind, OperandKind::GlobalVar(_))
+        {
+            // lvar == var is var == lvar, IS_*_LVAR_EQUAL_TO_*_VAR are missing in the older games
+            std::mem::swap(&mut left, &mut right);
+            [right_kind, left_kind]
+        } else {
+            [left_kind, right_kind]
+        }
+    } else {
+        return None;
+    };
+
+    let op_id = find_opcode(ns, as_library_operator(e.get_operator()), &kinds.map(Some))?;
+    let instruction = Instruction::new(
+        ns,
+        op_id,
+        vec![as_operand(&left.0, left.1)?, as_operand(&right.0, right.1)?],
+    );
+    Some(if not {
+        instruction.negate()
+    } else {
+        instruction
+    })
+}
+
+/// lowers the conditions of `if` or `while` into the `ANDOR` prologue followed by the comparisons.
+/// the prologue is 0 for a single condition, 1..7 for `and` and 21..27 for `or` (the number of extra conditions)
+pub fn try_transform_conditions<'a>(
+    conditions: &Conditions,
+    expr: &str,
+    env: &TypeEnv<'a>,
+    const_lookup: &DictStrByStr,
+) -> Option<Vec<Instruction<'a>>> {
+    let ns = env.ns;
+    let count = conditions.items.len();
+    if count == 0 || count > 8 {
+        return None;
+    }
+    let andor = match conditions.operator.as_ref().map(|t| &t.syntax_kind) {
+        _ if count == 1 => 0,
+        Some(SyntaxKind::KeywordAnd) => count - 1,
+        Some(SyntaxKind::KeywordOr) => 20 + count - 1,
+        _ => return None,
+    };
+
+    let mut instructions = vec![Instruction::new(
+        ns,
+        *ns.get_opcode_by_command_name(OP_IF)?,
+        vec![Operand::new(OperandType::IntLiteral, andor.to_string())],
+    )];
+    for condition in &conditions.items {
+        let instruction =
+            try_transform_text_label(&condition.expr, expr, ns, const_lookup, true)
+                .or_else(|| try_transform_comparison(&condition.expr, expr, env, const_lookup))?;
+        instructions.push(match condition.not {
+            Some(_) => instruction.negate(),
+            None => instruction,
+        });
+    }
+    Some(instructions)
+}
+
+/// lowers a call of the function declared with `signature` (`signature_text` is the source of its tokens).
+/// local functions use `CLEO_CALL` with the results following the arguments (`0AB1: @MyFunc 2 2@ 3.0 0@ 1@`),
+/// foreign functions (`<cdecl, 0xADDR>`) call the native code with the arguments pushed in reverse order
+/// (`0AA7: 0xADDR 2 2 3.0 2@ 0@`), `thiscall` takes the object from the first argument.
+/// an operand that can't be resolved (`int x` not allocated yet) or a native call with more than one result is an error
+pub fn try_transform_call<'a>(
+    ast: &AST,
+    expr: &str,
+    env: &TypeEnv<'a>,
+    signature: &FunctionSignature,
+    signature_text: &str,
+    const_lookup: &DictStrByStr,
+) -> Result<Option<Instruction<'a>>, TypeError> {
+    let ns = env.ns;
+    let Some((call, targets)) = ast.body.get(0).and_then(as_function_call) else {
+        return Ok(None);
+    };
+    let name = token_str(signature_text, &signature.name);
+    let is_callee = as_identifier(&call.callee)
+        .is_some_and(|callee| token_str(expr, callee).eq_ignore_ascii_case(name));
+    if !is_callee {
+        return Ok(None);
+    }
+
+    // a declared variable is either allocated by the compiler (and listed in const_lookup)
+    // or an alias of another variable (`const x = 0@`)
+    let alias = |node: &Node| -> Option<(Node, String)> {
+        let name = token_str(expr, as_identifier(node)?);
+        let (SymbolType::Var, Some(value)) = env.find_symbol(name)? else {
+            return None;
+        };
+        let node = parse_expression(&value).ok()?.1;
+        Some((node, value))
+    };
+    let operand = |node: &Node, is_target: bool| -> Result<Operand, TypeError> {
+        let accepts = |node: &Node| {
+            is_variable(node) || !is_target && (is_number(node) || as_string_type(node).is_some())
+        };
+        resolve(node, expr, ns, const_lookup)
+            .or_else(|| alias(node))
+            .filter(|(node, _)| accepts(node))
+            .and_then(|(node, text)| as_operand(&node, text))
+            .ok_or_else(|| {
+                let token = node_token(node).unwrap_or(&call.token).clone();
+                let text = token_str(expr, &token);
+                let message = match env.find_symbol(text) {
+                    Some((SymbolType::Var, _)) => format!("variable `{text}` is not allocated"),
+                    _ if is_target => format!("expected variable, found `{text}`"),
+                    _ => format!("expected variable or constant value, found `{text}`"),
+                };
+                TypeError { token, message }
+            })
+    };
+
+    let mut arguments = vec![];
+    for argument in &call.arguments {
+        arguments.push(operand(argument, false)?);
+    }
+    let mut results = vec![];
+    for target in targets {
+        results.push(operand(target, true)?);
+    }
+
+    let op = |name: &str, operands: Vec<Operand>| {
+        Some(Instruction::new(
+            ns,
+            *ns.get_opcode_by_command_name(name)?,
+            operands,
+        ))
+    };
+    let count =
+        |operands: &[Operand]| Operand::new(OperandType::IntLiteral, operands.len().to_string());
+
+    if signature.cc == FunctionCC::Local {
+        let mut operands = vec![
+            Operand::new(OperandType::Label, format!("@{name}")),
+            count(&arguments),
+        ];
+        operands.extend(arguments);
+        operands.extend(results);
+        return Ok(op(OP_CLEO_CALL, operands));
+    }
+
+    // the native code returns one value at most
+    if results.len() > 1 {
+        return Err(TypeError {
+            token: call.token.clone(),
+            message: format!(
+                "native function `{name}` returns one value at most, found {}",
+                plural(results.len(), "variable")
+            ),
+        });
+    }
+    let Some(address) = signature.address.as_ref() else {
+        return Ok(None);
+    };
+    let mut operands = vec![Operand::new(
+        OperandType::IntLiteral,
+        token_str(signature_text, address),
+    )];
+    if signature.cc == FunctionCC::Thiscall {
+        if arguments.is_empty() {
+            return Ok(None);
+        }
+        operands.push(arguments.remove(0));
+    }
+    let pop = match signature.cc {
+        FunctionCC::Cdecl => arguments.len(),
+        _ => 0,
+    };
+    operands.push(count(&arguments));
+    operands.push(Operand::new(OperandType::IntLiteral, pop.to_string()));
+    operands.extend(arguments.into_iter().rev());
+
+    let name = match (&signature.cc, results.is_empty()) {
+        (FunctionCC::Thiscall, true) => OP_CALL_METHOD,
+        (FunctionCC::Thiscall, false) => OP_CALL_METHOD_RETURN,
+        (_, true) => OP_CALL_FUNCTION,
+        (_, false) => OP_CALL_FUNCTION_RETURN,
+    };
+    operands.extend(results);
+    Ok(op(name, operands))
+}
+
+/// lowers the class syntax: methods (`Class.Method(args)`, `$obj.Method(args)`),
+/// property setters and comparisons (`Class.Prop(obj) = value`, `$obj.Prop += value`)
+/// and getters (`var = Class.Prop(obj)`, `var = $obj.Prop`).
+/// the class of an object variable is its declared type (`Car $car`)
+pub fn try_transform_member<'a>(
+    ast: &AST,
+    expr: &str,
+    env: &TypeEnv<'a>,
+    const_lookup: &DictStrByStr,
+) -> Option<Instruction<'a>> {
+    let ns = env.ns;
+    match ast.body.get(0)? {
+        node @ Node::Call(_) => {
+            let (class, member, operands) = member_target(node, expr, env, const_lookup)?;
+            let op_index = ns.get_opcode_index_by_name(&class, &member)?;
+            let opcode = ns.get_opcode_by_index(*op_index)?;
+            Some(Instruction::new(ns, opcode.id, operands))
+        }
+        Node::Binary(e) => {
+            let operation = token_str(expr, &e.operator);
+            if operation == "=" {
+                if let Some((class, member, mut operands)) =
+                    member_target(&e.right, expr, env, const_lookup)
+                {
+                    // var = Class.Prop(obj)
+                    let (var, var_name) = resolve(&e.left, expr, ns, const_lookup)?;
+                    if !is_variable(&var) {
+                        return None;
+                    }
+                    let op_index = ns.get_class_property_index_by_name(&class, &member, 2, "=")?;
+                    let opcode = ns.get_opcode_by_index(*op_index)?;
+                    let var = as_operand(&var, var_name)?;
+                    if opcode.op_type == OpcodeType::Property {
+                        // constructors take the handle first: 009A: $actor ...
+                        operands.insert(0, var);
+                    } else {
+                        operands.push(var);
+                    }
+                    return Some(Instruction::new(ns, opcode.id, operands));
+                }
+            }
+
+            // Class.Prop(obj) op value
+            let (class, member, mut operands) = member_target(&e.left, expr, env, const_lookup)?;
+            let op_index = ns.get_class_property_index_by_name(&class, &member, 1, operation)?;
+            let opcode = ns.get_opcode_by_index(*op_index)?;
+            let (value, value_name) = resolve(&e.right, expr, ns, const_lookup)?;
+            if !is_variable(&value) && !is_number(&value) {
+                return None;
+            }
+            operands.push(as_operand(&value, value_name)?);
+            Some(Instruction::new(ns, opcode.id, operands))
+        }
+        _ => None,
+    }
+}
+
+/// returns the class name, the member name and the operands of a member expression:
+/// the object variable (`$obj.Prop`) or the call arguments (`Class.Prop(obj)`)
+fn member_target(
+    node: &Node,
+    expr: &str,
+    env: &TypeEnv,
+    const_lookup: &DictStrByStr,
+) -> Option<(String, String, Vec<Operand>)> {
+    let ns = env.ns;
+    let (member, arguments): (&MemberExpr, &[Node]) = match node {
+        Node::Call(call) => match call.callee.as_ref() {
+            Node::Member(member) => (member, &call.arguments),
+            _ => return None,
+        },
+        Node::Member(member) => (member, &[]),
+        _ => return None,
+    };
+
+    let mut operands = vec![];
+    let class = match as_identifier(&member.object) {
+        Some(token)
+            if ns
+                .map_op_by_name
+                .contains_key(&token_str(expr, token).to_ascii_lowercase()) =>
+        {
+            String::from(token_str(expr, token))
+        }
+        _ => {
+            let (var, var_name) = resolve(&member.object, expr, ns, const_lookup)?;
+            if !is_variable(&var) {
+                return None;
+            }
+            let Type::Class(class) = env.declared_type(&var_name) else {
+                return None;
+            };
+            operands.push(as_operand(&var, var_name)?);
+            class
+        }
+    };
+
+    for argument in arguments {
+        let (node, text) = resolve(argument, expr, ns, const_lookup)?;
+        if !is_variable(&node) && !is_number(&node) {
+            return None;
+        }
+        operands.push(as_operand(&node, text)?);
+    }
+    Some((
+        class,
+        String::from(token_str(expr, &member.member)),
+        operands,
+    ))
+}
+
+/// resolves a constant name to its value, returns the node to operate on and its source text.
+/// constant expressions (e.g. `WIDTH * 2 + 1`) are folded into a number
+/// enum members (`PedType.CivMale`) are replaced with their values
+pub fn resolve(
+    node: &Node,
+    expr: &str,
+    ns: &Namespaces,
+    const_lookup: &DictStrByStr,
+) -> Option<(Node, String)> {
+    try_resolve(node, expr, ns, const_lookup).ok()?
+}
+
+/// returns why the first constant expression in the node can't be folded
+/// (e.g. `x = 0x7FFFFFFF + 1` overflows), [resolve] gives no result for such nodes
+pub fn fold_error(
+    node: &Node,
+    expr: &str,
+    ns: &Namespaces,
+    const_lookup: &DictStrByStr,
+) -> Option<EvalError> {
+    if let Err(e) = try_resolve(node, expr, ns, const_lookup) {
+        return Some(e);
+    }
+    let children: Vec<&Node> = match node {
+        Node::Binary(e) => vec![&e.left, &e.right],
+        Node::Unary(e) => vec![&e.operand],
+        Node::Call(e) => e.arguments.iter().collect(),
+        Node::CommandCall(e) => e.arguments.iter().collect(),
+        Node::Tuple(e) => e.items.iter().collect(),
+        _ => vec![],
+    };
+    children
+        .into_iter()
+        .find_map(|node| fold_error(node, expr, ns, const_lookup))
+}
+
+fn try_resolve(
+    node: &Node,
+    expr: &str,
+    ns: &Namespaces,
+    const_lookup: &DictStrByStr,
+) -> Result<Option<(Node, String)>, EvalError> {
+    let lookup = |name: &str| -> Option<String> {
+        let value = const_lookup.map.get(&CString::new(name).ok()?)?;
+        Some(String::from(value.to_str().ok()?))
+    };
+    let source = |node: Node, text: &str| {
+        let text = String::from(token_str(text, as_token(&node)?));
+        Some((node, text))
+    };
+    let x = |token| -> Result<Option<(Node, String)>, EvalError> {
+        // keys in DictStrByStr are lower-case
+        let name = token_str(expr, token).to_ascii_lowercase();
+        let Some(const_value) = lookup(&name) else {
+            return Ok(None);
+        };
+        let Ok((_, node)) = parse_expression(&const_value) else {
+            return Ok(None);
+        };
+        Ok(match node {
+            Node::Unary(unary)
+                if unary.get_operator() == &SyntaxKind::OperatorMinus
+                    && is_number(&unary.operand) =>
+            {
+                let text = token_str(&const_value, &unary.token);
+                Some((unary.operand.as_ref().clone(), String::from(text)))
+            }
+            Node::Binary(_) | Node::Unary(_) => Some(fold(&node, &const_value, &lookup)?),
+            Node::Literal(_) if is_identifier(&node) => Some(fold(&node, &const_value, &lookup)?),
+            Node::Member(member) => enum_member(&member, &const_value, ns),
+            _ => source(node, &const_value),
+        })
+    };
+    Ok(match node {
+        Node::Literal(token) if is_identifier(node) => x(token)?,
+        Node::Unary(unary) if unary.get_operator() == &SyntaxKind::OperatorMinus => {
+            match unary.operand.as_ref() {
+                Node::Literal(_) if is_identifier(&unary.operand) => {
+                    Some(fold(node, expr, &lookup)?)
+                }
+                Node::Literal(token) if is_number(&unary.operand) => Some((
+                    unary.operand.as_ref().clone(),
+                    format!("-{}", token_str(expr, token)),
+                )),
+                _ => source(node.clone(), expr),
+            }
+        }
+        Node::Member(member) => enum_member(member, expr, ns),
+        _ => source(node.clone(), expr),
+    })
+}
+
+/// value of `Enum.Member` as a number or string literal (text enums)
+fn enum_member(member: &MemberExpr, expr: &str, ns: &Namespaces) -> Option<(Node, String)> {
+    let enum_name = token_str(expr, as_identifier(&member.object)?);
+    let value = ns.get_enum_value_by_name(enum_name, token_str(expr, &member.member))?;
+    let literal = |syntax_kind, text: String| {
+        let token = Token {
+            syntax_kind,
+            start: 1,
+            len: text.len(),
+        };
+        (Node::Literal(token), text)
+    };
+    Some(match value {
+        EnumMemberValue::Int(value) => literal(SyntaxKind::IntegerLiteral, value.to_string()),
+        EnumMemberValue::Float(value) => literal(SyntaxKind::FloatLiteral, format_float(*value)),
+        EnumMemberValue::Text(value) => {
+            let text = format!("'{}'", escape(value, '\''));
+            let node = Node::String(StringLiteral {
+                value: value.clone(),
+                _type: VariableType::ShortString,
+                token: Token {
+                    syntax_kind: SyntaxKind::StringLiteral,
+                    start: 1,
+                    len: text.len(),
+                },
+            });
+            (node, text)
+        }
+    })
+}
+
+/// evaluates a constant expression into a number literal, the token spans the whole text including the sign
+fn fold<F>(node: &Node, expr: &str, lookup: &F) -> Result<(Node, String), EvalError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let value = evaluate(node, expr, lookup)?;
+    let text = value.to_string();
+    let syntax_kind = match value {
+        Value::Int(_) => SyntaxKind::IntegerLiteral,
+        Value::Float(_) => SyntaxKind::FloatLiteral,
+    };
+    Ok((
+        Node::Literal(Token {
+            syntax_kind,
+            start: 1,
+            len: text.len(),
+        }),
+        text,
+    ))
+}
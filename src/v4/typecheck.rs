@@ -0,0 +1,619 @@
+use super::eval::{evaluate_str, Value};
+use super::helpers::*;
+use crate::{
+    language_service::{
+        scanner::get_type,
+        symbol_table::{SymbolTable, SymbolType},
+    },
+    namespaces::namespaces::{EnumMemberValue, Namespaces},
+    parser::{interface::*, parse_expression},
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Type {
+    Int,
+    Float,
+    String,
+    /// handle of a class instance (e.g. `Car`), compatible with int
+    Class(String),
+    /// unknown type, compatible with anything
+    Any,
+}
+
+impl Type {
+    /// maps a type name from a variable declaration (`int x`, `Car $car`) or a command parameter
+    pub fn from_name(name: &str, ns: &Namespaces) -> Self {
+        match name.to_ascii_lowercase().as_str() {
+            "int" | "bool" | "handle" | "label" | "script_id" => Type::Int,
+            "float" => Type::Float,
+            "string" | "longstring" | "gxt_key" | "zone_key" => Type::String,
+            "any" | "arguments" => Type::Any,
+            s if s.starts_with("model_") => Type::Int,
+            _ if ns.map_enum.contains_key(name) => Type::Int,
+            _ => Type::Class(String::from(name)),
+        }
+    }
+
+    fn from_variable_type(_type: &VariableType) -> Self {
+        match _type {
+            VariableType::Int => Type::Int,
+            VariableType::Float => Type::Float,
+            VariableType::ShortString | VariableType::LongString => Type::String,
+            VariableType::Unknown => Type::Any,
+        }
+    }
+
+    /// checks if a value of type `found` can be stored where `self` is expected
+    pub fn accepts(&self, found: &Type) -> bool {
+        match (self, found) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (Type::Int, Type::Int | Type::Class(_)) => true,
+            (Type::Class(_), Type::Int) => true,
+            (Type::Class(a), Type::Class(b)) => a.eq_ignore_ascii_case(b),
+            (Type::Float, Type::Float) => true,
+            (Type::String, Type::String) => true,
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Float => write!(f, "float"),
+            Type::String => write!(f, "string"),
+            Type::Class(name) => write!(f, "{name}"),
+            Type::Any => write!(f, "any"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TypeError {
+    /// location of the offending expression
+    pub token: Token,
+    pub message: String,
+}
+
+impl std::fmt::Display for TypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for TypeError {}
+
+/// declared types visible at the line being checked
+pub struct TypeEnv<'a> {
+    pub ns: &'a Namespaces,
+    pub symbols: &'a SymbolTable,
+    pub line_number: usize,
+}
+
+impl TypeEnv<'_> {
+    /// the type and the value of the symbol visible at the line
+    pub fn find_symbol(&self, name: &str) -> Option<(SymbolType, Option<String>)> {
+        let symbols = self.symbols.symbols.get(&name.to_ascii_lowercase())?;
+        symbols
+            .iter()
+            .find(|symbol| symbol.is_visible_at(self.line_number))
+            .map(|symbol| (symbol._type, symbol.value.clone()))
+    }
+
+    /// the declared type of the variable (`int x`, `Car $car`), [Type::Any] if it is not declared
+    pub fn declared_type(&self, name: &str) -> Type {
+        match self.find_symbol(name) {
+            Some((SymbolType::Var, Some(value))) => self.var_symbol_type(&value),
+            _ => Type::Any,
+        }
+    }
+
+    /// the value of a var symbol is either the declared type or the aliased variable (`const x = 0@`)
+    fn var_symbol_type(&self, value: &str) -> Type {
+        if get_type(&value.to_ascii_lowercase()) != Some(SymbolType::Var) {
+            return Type::from_name(value, self.ns);
+        }
+        let Ok((_, node)) = parse_expression(value) else {
+            return Type::Any;
+        };
+        match node {
+            Node::Variable(var) => Checker {
+                env: self,
+                expr: value,
+                errors: vec![],
+                unknown_members: vec![],
+            }
+            .variable_type(&var),
+            _ => Type::Any,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Usage {
+    Assign,
+    Compare,
+    Operand,
+}
+
+struct Checker<'a, 'b> {
+    env: &'b TypeEnv<'a>,
+    expr: &'b str,
+    errors: Vec<TypeError>,
+    /// references to unknown enum members, also listed in `errors`
+    unknown_members: Vec<TypeError>,
+}
+
+/// checks operand types against declared variable types and command signatures
+pub fn check(ast: &AST, expr: &str, env: &TypeEnv) -> Vec<TypeError> {
+    let mut checker = Checker {
+        env,
+        expr,
+        errors: vec![],
+        unknown_members: vec![],
+    };
+    checker.statements(&ast.body);
+    checker.errors
+}
+
+/// checks the conditions of an `if` or `while` header
+pub fn check_conditions(conditions: &Conditions, expr: &str, env: &TypeEnv) -> Vec<TypeError> {
+    let mut checker = Checker {
+        env,
+        expr,
+        errors: vec![],
+        unknown_members: vec![],
+    };
+    checker.conditions(conditions);
+    checker.errors
+}
+
+/// reports references to unknown enum members (`PedType.CivMal`), the transform gives no result for them
+pub fn check_enum_members(ast: &AST, expr: &str, ns: &Namespaces) -> Vec<TypeError> {
+    let symbols = SymbolTable::new();
+    let env = TypeEnv {
+        ns,
+        symbols: &symbols,
+        line_number: 0,
+    };
+    let mut checker = Checker {
+        env: &env,
+        expr,
+        errors: vec![],
+        unknown_members: vec![],
+    };
+    checker.statements(&ast.body);
+    checker.unknown_members
+}
+
+/// checks a call of the function declared with `signature` (`signature_text` is the source of its tokens):
+/// the number of arguments and result variables and their types
+pub fn check_call(
+    call: &CallExpr,
+    targets: &[Node],
+    expr: &str,
+    signature: &FunctionSignature,
+    signature_text: &str,
+    env: &TypeEnv,
+) -> Vec<TypeError> {
+    let mut checker = Checker {
+        env,
+        expr,
+        errors: vec![],
+        unknown_members: vec![],
+    };
+    checker.function_call(call, targets, signature, signature_text);
+    checker.errors
+}
+
+impl<'a, 'b> Checker<'a, 'b> {
+    fn statements(&mut self, body: &[Node]) {
+        for node in body {
+            self.statement(node);
+        }
+    }
+
+    fn statement(&mut self, node: &Node) {
+        match node {
+            Node::Binary(e) => self.binary_statement(e),
+            Node::CommandCall(call) => self.command_call(call),
+            Node::If(e) => {
+                self.conditions(&e.conditions);
+                self.statements(&e.body);
+                if let Some(body) = &e.else_body {
+                    self.statements(body);
+                }
+            }
+            Node::While(e) => {
+                self.conditions(&e.conditions);
+                self.statements(&e.body);
+            }
+            Node::For(e) => {
+                // the bounds and the step are stored in or compared with the counter
+                let counter = self.infer(&e.var);
+                let start = self.infer(&e.start);
+                self.expect(&counter, &start, &e.start, Usage::Assign);
+                let end = self.infer(&e.end);
+                self.expect(&counter, &end, &e.end, Usage::Compare);
+                if let Some(step) = &e.step {
+                    let found = self.infer(step);
+                    self.expect(&counter, &found, step, Usage::Operand);
+                }
+                self.statements(&e.body);
+            }
+            Node::Switch(e) => {
+                let subject = self.infer(&e.subject);
+                for case in &e.cases {
+                    for value in &case.values {
+                        let found = self.infer(value);
+                        self.expect(&subject, &found, value, Usage::Compare);
+                    }
+                    self.statements(&case.body);
+                }
+                if let Some(body) = &e.default {
+                    self.statements(body);
+                }
+            }
+            Node::FunctionDeclaration(e) => self.statements(&e.body),
+            _ => {
+                self.infer(node);
+            }
+        }
+    }
+
+    fn conditions(&mut self, conditions: &Conditions) {
+        for condition in &conditions.items {
+            self.statement(&condition.expr);
+        }
+    }
+
+    fn binary_statement(&mut self, e: &BinaryExpr) {
+        use SyntaxKind::*;
+
+        let left = self.infer(&e.left);
+        let right = self.infer(&e.right);
+        match e.get_operator() {
+            OperatorEqual | OperatorPlusEqual | OperatorMinusEqual | OperatorMulEqual
+            | OperatorDivEqual => {
+                self.expect(&left, &right, &e.right, Usage::Assign);
+            }
+            OperatorBitwiseAndEqual
+            | OperatorBitwiseOrEqual
+            | OperatorBitwiseXorEqual
+            | OperatorBitwiseModEqual
+            | OperatorBitwiseShrEqual
+            | OperatorBitwiseShlEqual
+            | OperatorBitwiseNotEqual => {
+                self.expect(&Type::Int, &left, &e.left, Usage::Operand);
+                self.expect(&Type::Int, &right, &e.right, Usage::Operand);
+            }
+            OperatorTimedAdditionEqual | OperatorTimedSubtractionEqual => {
+                self.expect(&Type::Float, &left, &e.left, Usage::Operand);
+                self.expect(&Type::Float, &right, &e.right, Usage::Operand);
+            }
+            OperatorEqualEqual | OperatorLessGreater | OperatorGreater | OperatorGreaterEqual
+            | OperatorLess | OperatorLessEqual => {
+                self.expect(&left, &right, &e.right, Usage::Compare);
+            }
+            // =# converts between int and float
+            _ => {}
+        }
+    }
+
+    fn command_call(&mut self, call: &CommandCall) {
+        let arguments = call
+            .arguments
+            .iter()
+            .map(|arg| self.infer(arg))
+            .collect::<Vec<_>>();
+
+        let ns = self.env.ns;
+        let id = match (&call.name, &call.opcode) {
+            (Some(name), _) => ns
+                .get_opcode_by_command_name(token_str(self.expr, name))
+                .copied(),
+            // the highest bit negates the condition
+            (None, Some(opcode)) => u16::from_str_radix(token_str(self.expr, opcode), 16)
+                .ok()
+                .map(|op| op & 0x7FFF),
+            _ => None,
+        };
+        let Some(command) = id.and_then(|id| ns.get_command(id)) else {
+            return;
+        };
+
+        let params = command.input.iter().chain(command.output.iter());
+        // variadic commands are not checked
+        if params.clone().count() != arguments.len()
+            || params
+                .clone()
+                .any(|p| p.r#type.eq_ignore_ascii_case("arguments"))
+        {
+            return;
+        }
+        for ((param, found), node) in params.zip(arguments).zip(&call.arguments) {
+            let expected = Type::from_name(&param.r#type, ns);
+            self.expect(&expected, &found, node, Usage::Operand);
+        }
+    }
+
+    fn function_call(
+        &mut self,
+        call: &CallExpr,
+        targets: &[Node],
+        signature: &FunctionSignature,
+        signature_text: &str,
+    ) {
+        let ns = self.env.ns;
+        let name = token_str(signature_text, &signature.name);
+        let params = &signature.parameters;
+        let count = call.arguments.len();
+
+        let (fits, expected) = if signature.is_variadic() {
+            let required = params.len() - 1;
+            (
+                count >= required,
+                format!("at least {}", plural(required, "argument")),
+            )
+        } else {
+            (count == params.len(), plural(params.len(), "argument"))
+        };
+        if !fits {
+            self.errors.push(TypeError {
+                token: call.token.clone(),
+                message: format!("function `{name}` expects {expected}, found {count}"),
+            });
+            return;
+        }
+
+        // the variadic parameter takes the rest of the arguments
+        let params = params
+            .iter()
+            .chain(std::iter::repeat(params.last()).flatten());
+        for (node, param) in call.arguments.iter().zip(params) {
+            let expected = Type::from_name(token_str(signature_text, &param._type), ns);
+            let found = self.infer(node);
+            self.expect(&expected, &found, node, Usage::Operand);
+        }
+
+        // the results can be ignored
+        let returns = &signature.return_types;
+        if targets.is_empty() {
+            return;
+        }
+        if targets.len() != returns.len() {
+            self.errors.push(TypeError {
+                token: call.token.clone(),
+                message: format!(
+                    "function `{name}` returns {}, found {}",
+                    plural(returns.len(), "value"),
+                    plural(targets.len(), "variable")
+                ),
+            });
+            return;
+        }
+        for (ret, node) in returns.iter().zip(targets) {
+            let Some(token) = node_token(node) else {
+                continue;
+            };
+            let text = token_str(self.expr, token);
+            let is_declared = |name| matches!(self.find_symbol(name), Some((SymbolType::Var, _)));
+            let is_target = is_variable(node) || is_identifier(node) && is_declared(text);
+            if !is_target {
+                self.errors.push(TypeError {
+                    token: token.clone(),
+                    message: format!("expected variable, found `{text}`"),
+                });
+                continue;
+            }
+            let expected = Type::from_name(token_str(signature_text, &ret._type), ns);
+            let found = self.infer(node);
+            if !found.accepts(&expected) {
+                self.errors.push(TypeError {
+                    token: token.clone(),
+                    message: format!(
+                        "cannot assign {expected} result to {found} variable `{text}`"
+                    ),
+                });
+            }
+        }
+    }
+
+    /// returns the type of the expression, reporting mismatches in its operands
+    fn infer(&mut self, node: &Node) -> Type {
+        match node {
+            Node::Literal(token) => match token.syntax_kind {
+                SyntaxKind::IntegerLiteral | SyntaxKind::LabelLiteral => Type::Int,
+                SyntaxKind::FloatLiteral => Type::Float,
+                SyntaxKind::Identifier => self.symbol_type(token_str(self.expr, token)),
+                _ => Type::Any,
+            },
+            Node::String(_) => Type::String,
+            Node::Variable(var) => self.variable_type(var),
+            Node::Member(e) => self.enum_member_type(e),
+            Node::Unary(e) => {
+                let operand = self.infer(&e.operand);
+                match e.get_operator() {
+                    SyntaxKind::OperatorBitwiseNot => {
+                        self.expect(&Type::Int, &operand, &e.operand, Usage::Operand);
+                        Type::Int
+                    }
+                    _ => operand,
+                }
+            }
+            Node::Binary(e) => {
+                use SyntaxKind::*;
+
+                let left = self.infer(&e.left);
+                let right = self.infer(&e.right);
+                match e.get_operator() {
+                    OperatorPlus | OperatorMinus | OperatorMul | OperatorDiv => {
+                        if !self.expect(&left, &right, &e.right, Usage::Operand) {
+                            return Type::Any;
+                        }
+                        match (left, right) {
+                            (Type::Any, t) | (t, Type::Any) => t,
+                            (Type::Class(_), _) | (_, Type::Class(_)) => Type::Int,
+                            (t, _) => t,
+                        }
+                    }
+                    OperatorBitwiseAnd | OperatorBitwiseOr | OperatorBitwiseXor
+                    | OperatorBitwiseMod | OperatorBitwiseShr | OperatorBitwiseShl => {
+                        self.expect(&Type::Int, &left, &e.left, Usage::Operand);
+                        self.expect(&Type::Int, &right, &e.right, Usage::Operand);
+                        Type::Int
+                    }
+                    _ => Type::Any,
+                }
+            }
+            _ => Type::Any,
+        }
+    }
+
+    /// type of the value of `Enum.Member`, reports unknown members with the closest names
+    fn enum_member_type(&mut self, e: &MemberExpr) -> Type {
+        let ns = self.env.ns;
+        let Some(object) = as_identifier(&e.object) else {
+            return Type::Any;
+        };
+        let enum_name = token_str(self.expr, object);
+        let key = enum_name.to_ascii_lowercase();
+        // Class.Member is a command
+        if ns.map_op_by_name.contains_key(&key) {
+            return Type::Any;
+        }
+        let Some(members) = ns.map_enum.get(&key) else {
+            return Type::Any;
+        };
+        let member_name = token_str(self.expr, &e.member);
+        match members.get(&member_name.to_ascii_lowercase()) {
+            Some(member) => match member.value {
+                EnumMemberValue::Int(_) => Type::Int,
+                EnumMemberValue::Float(_) => Type::Float,
+                EnumMemberValue::Text(_) => Type::String,
+            },
+            None => {
+                let names = members.values().filter_map(|m| m.name.to_str().ok());
+                let matches = close_matches(member_name, names)
+                    .iter()
+                    .map(|name| format!("`{enum_name}.{name}`"))
+                    .collect::<Vec<_>>();
+                let mut message = format!("`{member_name}` is not a member of enum `{enum_name}`");
+                if !matches.is_empty() {
+                    message += &format!(", did you mean {}?", matches.join(", "));
+                }
+                let error = TypeError {
+                    token: e.token.clone(),
+                    message,
+                };
+                self.unknown_members.push(error.clone());
+                self.errors.push(error);
+                Type::Any
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: &Type, found: &Type, node: &Node, usage: Usage) -> bool {
+        if expected.accepts(found) {
+            return true;
+        }
+        let Some(token) = node_token(node) else {
+            return false;
+        };
+        let text = token_str(self.expr, token);
+        let message = match usage {
+            Usage::Assign => {
+                format!("cannot assign {found} value `{text}` to {expected} variable")
+            }
+            Usage::Compare => format!("cannot compare {expected} with {found} value `{text}`"),
+            Usage::Operand => format!("expected {expected}, found {found} value `{text}`"),
+        };
+        self.errors.push(TypeError {
+            token: token.clone(),
+            message,
+        });
+        false
+    }
+
+    fn variable_type(&self, var: &Variable) -> Type {
+        let declared = self
+            .env
+            .declared_type(token_str(self.expr, var.get_var_name()));
+        if declared != Type::Any {
+            return declared;
+        }
+        match var {
+            Variable::Global(v) | Variable::Local(v) | Variable::Adma(v) => {
+                Type::from_variable_type(&v._type)
+            }
+            Variable::ArrayElement(v) => Type::from_variable_type(&v._type),
+            Variable::Indexed(v) => self.variable_type(&v.var),
+            Variable::Pop(_) | Variable::Push(_) => Type::Any,
+        }
+    }
+
+    fn symbol_type(&self, name: &str) -> Type {
+        match self.find_symbol(name) {
+            Some((SymbolType::Var, Some(value))) => self.env.var_symbol_type(&value),
+            Some((SymbolType::Number, Some(value))) => {
+                let lookup = |name: &str| match self.find_symbol(name) {
+                    Some((SymbolType::Number, value)) => value,
+                    _ => None,
+                };
+                match evaluate_str(&value, &lookup) {
+                    Ok(Value::Int(_)) => Type::Int,
+                    Ok(Value::Float(_)) => Type::Float,
+                    Err(_) => Type::Any,
+                }
+            }
+            Some((SymbolType::String, _)) => Type::String,
+            Some((SymbolType::Label | SymbolType::ModelName, _)) => Type::Int,
+            _ => Type::Any,
+        }
+    }
+
+    fn find_symbol(&self, name: &str) -> Option<(SymbolType, Option<String>)> {
+        self.env.find_symbol(name)
+    }
+}
+
+/// up to three names closest to `name`, ignoring the case
+fn close_matches<'n>(name: &str, names: impl Iterator<Item = &'n str>) -> Vec<&'n str> {
+    let name = name.to_ascii_lowercase();
+    let max_distance = (name.len() / 3).max(2);
+    let mut matches = names
+        .filter_map(|candidate| {
+            let lower = candidate.to_ascii_lowercase();
+            let distance = edit_distance(&name, &lower);
+            (distance <= max_distance || lower.starts_with(&name)).then_some((distance, candidate))
+        })
+        .collect::<Vec<_>>();
+    matches.sort();
+    matches.into_iter().take(3).map(|(_, name)| name).collect()
+}
+
+/// number of single character insertions, deletions and substitutions turning `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// `1 argument`, `2 arguments`
+pub fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("{count} {noun}")
+    } else {
+        format!("{count} {noun}s")
+    }
+}